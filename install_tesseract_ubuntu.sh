@@ -1,3 +1,4 @@
 #!/bin/bash
 
 sudo apt-get install libleptonica-dev libtesseract-dev clang
+sudo apt-get install tesseract-ocr-nld tesseract-ocr-eng tesseract-ocr-deu tesseract-ocr-fra
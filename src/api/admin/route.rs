@@ -0,0 +1,138 @@
+use actix_web::http::header;
+use actix_web::{post, HttpRequest, HttpResponse};
+use serde_json::json;
+use tracing::{error, info, warn};
+
+// crate imports
+use crate::config::get_admin_token;
+use crate::error::AuroraError;
+use crate::parser::caster::caster_registry::load_configured_providers;
+
+/// Reloads the provider definitions of `config.yaml` without restarting the server.
+///
+/// The new definitions are validated first and only swapped in when all of
+/// them are valid, documents already being processed finish with the
+/// definitions they started with. Requires `Authorization: Bearer <AURORA_ADMIN_TOKEN>`.
+#[post("/admin/providers/reload")]
+async fn reload_providers(req: HttpRequest) -> Result<HttpResponse, AuroraError> {
+    let Some(token) = get_admin_token() else {
+        return Err(AuroraError::Forbidden(
+            "Admin endpoints are disabled, set AURORA_ADMIN_TOKEN to enable them".to_string(),
+        ));
+    };
+
+    let authorized: bool = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|provided| tokens_match(provided, &token));
+    if !authorized {
+        warn!("Rejected provider reload with a missing or invalid token");
+        return Err(AuroraError::Unauthorized(
+            "Missing or invalid admin token".to_string(),
+        ));
+    }
+
+    match load_configured_providers() {
+        Ok(providers) => {
+            info!("Reloaded provider definitions: {:?}", providers);
+            Ok(HttpResponse::Ok().json(json!({"status": "ok", "providers": providers})))
+        }
+        Err(e) => {
+            error!("Provider reload rejected, keeping the running definitions: {:#}", e);
+            Err(AuroraError::Config(format!("{:#}", e)))
+        }
+    }
+}
+
+/// Compares a provided token with the configured one in constant time, so the
+/// response time doesn't reveal how much of a guessed token was right.
+fn tokens_match(provided: &str, expected: &str) -> bool {
+    let provided: &[u8] = provided.as_bytes();
+    let expected: &[u8] = expected.as_bytes();
+    provided.len() == expected.len()
+        && provided
+            .iter()
+            .zip(expected)
+            .fold(0u8, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::caster::caster_registry::{registry, CasterRegistry};
+    use actix_web::dev::ServiceResponse;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::App;
+    use serde_json::Value;
+    use std::path::PathBuf;
+    use std::sync::Arc;
+    use tokio::sync::Mutex;
+
+    /// The route reads its token and config path from the environment, which is shared by every test.
+    static ENV: Mutex<()> = Mutex::const_new(());
+
+    async fn reload(authorization: Option<&str>) -> ServiceResponse {
+        let app = init_service(App::new().service(reload_providers)).await;
+        let mut request = TestRequest::post().uri("/admin/providers/reload");
+        if let Some(authorization) = authorization {
+            request = request.insert_header((header::AUTHORIZATION, authorization));
+        }
+        call_service(&app, request.to_request()).await
+    }
+
+    #[actix_web::test]
+    async fn admin_endpoints_are_disabled_without_a_token() {
+        let _env = ENV.lock().await;
+        std::env::remove_var("AURORA_ADMIN_TOKEN");
+
+        let response: ServiceResponse = reload(Some("Bearer secret")).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body: Value = read_body_json(response).await;
+        assert_eq!(body["code"], "forbidden");
+    }
+
+    #[actix_web::test]
+    async fn a_wrong_or_missing_token_is_unauthorized() {
+        let _env = ENV.lock().await;
+        std::env::set_var("AURORA_ADMIN_TOKEN", "secret");
+
+        for authorization in [Some("Bearer wrong"), Some("Bearer secret2"), Some("secret"), None] {
+            let response: ServiceResponse = reload(authorization).await;
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{:?}", authorization);
+        }
+        std::env::remove_var("AURORA_ADMIN_TOKEN");
+    }
+
+    #[actix_web::test]
+    async fn invalid_definitions_keep_the_running_ones() {
+        let _env = ENV.lock().await;
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("aurora-invalid-{}.yaml", std::process::id()));
+        std::fs::write(&path, "providers:\n  - name: broken_csv\n    columns: [\n").unwrap();
+        std::env::set_var("AURORA_ADMIN_TOKEN", "secret");
+        std::env::set_var("AURORA_CONFIG_PATH", &path);
+
+        let before: Arc<CasterRegistry> = registry();
+        let response: ServiceResponse = reload(Some("Bearer secret")).await;
+        std::env::remove_var("AURORA_ADMIN_TOKEN");
+        std::env::remove_var("AURORA_CONFIG_PATH");
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body: Value = read_body_json(response).await;
+        assert_eq!(body["code"], "invalid_config");
+        assert!(Arc::ptr_eq(&before, &registry()));
+    }
+
+    #[test]
+    fn tokens_only_match_when_equal() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secreT", "secret"));
+        assert!(!tokens_match("secret", "secret "));
+        assert!(!tokens_match("", "secret"));
+    }
+}
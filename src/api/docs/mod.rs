@@ -1,6 +1,10 @@
 use actix_files::NamedFile;
 use actix_web::{get, HttpResponse, Responder, web::Path};
 use std::env::var;
+use tracing::warn;
+
+// crate imports
+use crate::error::AuroraError;
 
 #[get("/docs")]
 pub async fn redirect_to_docs() -> impl Responder {
@@ -10,29 +14,37 @@ pub async fn redirect_to_docs() -> impl Responder {
 }
 
 #[get("/docs/{filename:.*}")]
-pub async fn serve_docs(path: Path<String>) -> impl Responder {
+pub async fn serve_docs(path: Path<String>) -> Result<NamedFile, AuroraError> {
     let filename: String = path.into_inner();
     let file_path: String = if filename.is_empty() {
-        var("AURORA_DOCS_INDEX_PATH").expect("AURORA_DOCS_INDEX_PATH environment variable not set")
+        docs_path("AURORA_DOCS_INDEX_PATH")?
     } else {
-        format!(
-            "{}/{}",
-            var("AURORA_DOCS_TARGET_PATH")
-                .expect("AURORA_DOCS_TARGET_PATH environment variable not set"),
-            filename
-        )
+        format!("{}/{}", docs_path("AURORA_DOCS_TARGET_PATH")?, filename)
     };
-    NamedFile::open_async(file_path).await.unwrap()
+    open_docs_file(file_path).await
 }
 
 #[get("/static.files/{filename:.*}")]
-pub async fn serve_static_files(path: Path<String>) -> impl Responder {
+pub async fn serve_static_files(path: Path<String>) -> Result<NamedFile, AuroraError> {
     let file_path: String = format!(
         "{}/{}",
-        var("AURORA_DOCS_STATIC_FILES_PATH")
-            .expect("AURORA_DOCS_STATIC_FILES_PATH environment variable not set"),
+        docs_path("AURORA_DOCS_STATIC_FILES_PATH")?,
         path.into_inner()
     );
+    open_docs_file(file_path).await
+}
+
+/// Reads a docs location from the environment, docs are simply not served when it isn't set.
+fn docs_path(name: &str) -> Result<String, AuroraError> {
+    var(name).map_err(|_| {
+        warn!("{} environment variable not set, docs are not served", name);
+        AuroraError::NotFound("Documentation is not available on this server".to_string())
+    })
+}
 
-    NamedFile::open_async(file_path).await.unwrap()
+/// Opens a file of the generated docs.
+async fn open_docs_file(file_path: String) -> Result<NamedFile, AuroraError> {
+    NamedFile::open_async(&file_path)
+        .await
+        .map_err(|_| AuroraError::NotFound("Documentation page not found".to_string()))
 }
@@ -2,4 +2,6 @@ pub mod health;
 pub mod server;
 pub mod foundry;
 pub mod proxy;
-pub mod docs;
\ No newline at end of file
+pub mod docs;
+pub mod upload;
+pub mod admin;
\ No newline at end of file
@@ -1,4 +1,4 @@
-use actix_web::{post, web, App, HttpResponse, HttpServer, Responder};
+use actix_web::{post, web, App, HttpResponse, HttpServer, Responder, ResponseError};
 use mime_guess::mime;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -8,9 +8,15 @@ use std::io::copy;
 use tracing::info;
 use uuid::Uuid;
 
+// crate imports
+use crate::error::AuroraError;
+use crate::parser::options::ParseOptions;
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct FileUrl {
     pub file_url: String,
+    #[serde(flatten, default)]
+    pub options: ParseOptions,
 }
 
 #[post("/proxy/download")]
@@ -32,16 +38,25 @@ async fn download_file(file_url: web::Json<FileUrl>) -> impl Responder {
         }
         Err(e) => {
             info!("Failed to download file from URL: {}. Error: {}", url, e);
-            HttpResponse::InternalServerError().json(
-                json!({"status": "error", "message": format!("Failed to download file: {}", e)}),
-            )
+            e.error_response()
         }
     }
 }
 
-async fn download_to_cache(url: &str, uuid: &str) -> Result<String, Box<dyn std::error::Error>> {
+async fn download_to_cache(url: &str, uuid: &str) -> Result<String, AuroraError> {
+    let fetch_error = |e: reqwest::Error| AuroraError::Fetch {
+        url: url.to_string(),
+        message: e.to_string(),
+    };
+    let storage_error = |e: std::io::Error| AuroraError::Storage(e.to_string());
+
     let client: Client = Client::new();
-    let response: reqwest::Response = client.get(url).send().await?;
+    let response: reqwest::Response = client
+        .get(url)
+        .send()
+        .await
+        .and_then(reqwest::Response::error_for_status)
+        .map_err(fetch_error)?;
     let mime_type: mime::Mime = response
         .headers()
         .get("content-type")
@@ -61,8 +76,8 @@ async fn download_to_cache(url: &str, uuid: &str) -> Result<String, Box<dyn std:
     };
 
     let file_path: String = format!("./cache/{}.{}", uuid, ext);
-    let mut file: File = File::create(&file_path)?;
-    let mut content: web::Bytes = response.bytes().await?;
-    copy(&mut content.as_ref(), &mut file)?;
+    let mut file: File = File::create(&file_path).map_err(storage_error)?;
+    let content: web::Bytes = response.bytes().await.map_err(fetch_error)?;
+    copy(&mut content.as_ref(), &mut file).map_err(storage_error)?;
     Ok(file_path)
 }
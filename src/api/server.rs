@@ -3,7 +3,7 @@ use actix_web::body::{BoxBody, EitherBody};
 use actix_web::dev::Service;
 use actix_web::dev::ServiceResponse;
 use actix_web::http::header;
-use actix_web::{get, post, web, web::Json, App, HttpResponse, HttpServer, Responder};
+use actix_web::{get, post, web, web::Json, App, HttpResponse, HttpServer, Responder, ResponseError};
 use dotenv::dotenv;
 use mime_guess::mime;
 use moka::future::Cache;
@@ -15,10 +15,12 @@ use std::time::Duration;
 use tokio::fs::File;
 use tokio::sync::Mutex;
 use uuid::Uuid;
+use tracing::error;
 use web::Data;
 
 // config
 use crate::config::get_api_port;
+use crate::error::AuroraError;
 
 // docs
 use crate::api::docs::{redirect_to_docs, serve_docs, serve_static_files};
@@ -26,9 +28,17 @@ use crate::api::docs::{redirect_to_docs, serve_docs, serve_static_files};
 // proxy
 use crate::api::proxy::route::download_file;
 
+// upload
+use crate::api::upload::route::{upload_batch, upload_file};
+
+// admin
+use crate::api::admin::route::reload_providers;
+
 // parser
 use crate::api::proxy::route::FileUrl;
 use crate::parser::builder::handle_bytestream;
+use crate::parser::caster::caster_registry::load_configured_providers;
+use crate::Parser;
 
 /// Define a type alias for the shared cache
 pub type SharedCache = Arc<Mutex<Cache<String, Value>>>;
@@ -47,28 +57,37 @@ async fn process_file(file_url: Json<FileUrl>) -> impl Responder {
     let client: Client = Client::new();
     let url: String = file_url.file_url.clone();
 
+    let fetch_error = |message: String| AuroraError::Fetch {
+        url: url.clone(),
+        message,
+    };
+
     match client.get(&url).send().await {
         Ok(response) if response.status().is_success() => {
             let content: web::Bytes = match response.bytes().await {
                 Ok(bytes) => bytes,
                 Err(e) => {
-                    return HttpResponse::InternalServerError()
-                        .body(format!("Error reading response bytes: {}", e))
+                    return fetch_error(format!("Error reading response bytes: {}", e))
+                        .error_response()
                 }
             };
 
-            handle_bytestream(&content).await
+            let parser: Parser = Parser::new(url);
+            handle_bytestream(&content, &parser, &file_url.options).await
         }
-        Ok(response) => HttpResponse::InternalServerError()
-            .body(format!("Error from server: {}", response.status())),
-        Err(e) => {
-            HttpResponse::InternalServerError().body(format!("Failed to send request: {}", e))
+        Ok(response) => {
+            fetch_error(format!("Error from server: {}", response.status())).error_response()
         }
+        Err(e) => fetch_error(format!("Failed to send request: {}", e)).error_response(),
     }
 }
 
 pub async fn api() -> Result<()> {
     dotenv().ok();
+    // a broken config.yaml shouldn't keep the built-in providers from being served
+    if let Err(e) = load_configured_providers() {
+        error!("Failed to load provider definitions: {:#}", e);
+    }
     let cache: SharedCache = Arc::new(Mutex::new(
         Cache::builder()
             .time_to_live(Duration::from_secs(60))
@@ -104,6 +123,11 @@ pub async fn api() -> Result<()> {
             .service(download_file)
             // process files at `/`
             .service(process_file)
+            // multipart uploads
+            .service(upload_file)
+            .service(upload_batch)
+            // admin
+            .service(reload_providers)
     })
     .workers(1)
     .bind(("0.0.0.0", get_api_port()))?
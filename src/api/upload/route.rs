@@ -0,0 +1,353 @@
+use actix_multipart::{Field, Multipart};
+use actix_web::http::StatusCode;
+use actix_web::web::{Bytes, BytesMut, Query};
+use actix_web::{post, HttpResponse, Responder, ResponseError};
+use futures_util::StreamExt;
+use serde::Serialize;
+use tracing::{info, warn};
+
+// crate imports
+use crate::config::get_max_upload_size;
+use crate::error::{AuroraError, ErrorBody};
+use crate::parser::builder::handle_bytestream;
+use crate::parser::document::ParsedDocument;
+use crate::parser::options::ParseOptions;
+use crate::Parser;
+
+/// The outcome of processing a single file of a batch upload.
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadResult {
+    /// The original filename of the uploaded file.
+    pub file_name: String,
+    /// The HTTP status the file would have gotten as a single upload.
+    pub status: u16,
+    /// The parsed document, present when processing succeeded.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<ParsedDocument>,
+    /// The error envelope describing why processing failed, present when it didn't succeed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorBody>,
+}
+
+/// The largest `options` form field accepted, options are a small JSON object.
+const MAX_OPTIONS_SIZE: usize = 64 * 1024;
+
+/// Processes the first file of a `multipart/form-data` upload.
+///
+/// `ParseOptions` are taken from the query string, or from a JSON `options`
+/// form field placed before the file, which can also hold the nested `ocr`
+/// options. Other form fields without a filename are ignored.
+#[post("/upload")]
+async fn upload_file(mut payload: Multipart, query: Query<ParseOptions>) -> impl Responder {
+    let max_size: usize = get_max_upload_size();
+    let mut options: ParseOptions = query.into_inner();
+
+    while let Some(field) = payload.next().await {
+        let field: Field = match field {
+            Ok(field) => field,
+            Err(e) => {
+                return AuroraError::BadRequest(format!("Invalid multipart payload: {}", e))
+                    .error_response()
+            }
+        };
+
+        let Some(file_name) = field_file_name(&field) else {
+            if is_options_field(&field) {
+                options = match read_options(field).await {
+                    Ok(options) => options,
+                    Err(e) => return e.error_response(),
+                };
+            }
+            continue;
+        };
+
+        let parser: Parser = parser_for_field(&field, &file_name);
+        return match read_field(field, max_size).await {
+            Ok(content) => handle_bytestream(&content, &parser, &options).await,
+            Err(e) => e.error_response(),
+        };
+    }
+
+    no_file_error().error_response()
+}
+
+/// Processes every file of a `multipart/form-data` upload independently.
+///
+/// A file failing to parse doesn't fail the request, its status and error are
+/// reported in its own entry of the response instead. `ParseOptions` are read
+/// like those of a single upload, a JSON `options` field applies to the files after it.
+#[post("/upload/batch")]
+async fn upload_batch(mut payload: Multipart, query: Query<ParseOptions>) -> impl Responder {
+    let max_size: usize = get_max_upload_size();
+    let mut options: ParseOptions = query.into_inner();
+    let mut results: Vec<UploadResult> = vec![];
+
+    while let Some(field) = payload.next().await {
+        let field: Field = match field {
+            Ok(field) => field,
+            Err(e) => {
+                return AuroraError::BadRequest(format!("Invalid multipart payload: {}", e))
+                    .error_response()
+            }
+        };
+
+        let Some(file_name) = field_file_name(&field) else {
+            if is_options_field(&field) {
+                options = match read_options(field).await {
+                    Ok(options) => options,
+                    Err(e) => return e.error_response(),
+                };
+            }
+            continue;
+        };
+
+        let parser: Parser = parser_for_field(&field, &file_name);
+        let processed: Result<ParsedDocument, AuroraError> =
+            match read_field(field, max_size).await {
+                Ok(content) => parser.parse(content, &options).await,
+                Err(e) => Err(e),
+            };
+
+        let result: UploadResult = match processed {
+            Ok(value) => UploadResult {
+                file_name,
+                status: StatusCode::OK.as_u16(),
+                result: Some(value),
+                error: None,
+            },
+            Err(e) => {
+                warn!("Failed to process uploaded file {}: {}", file_name, e);
+                UploadResult {
+                    file_name,
+                    status: e.status_code().as_u16(),
+                    result: None,
+                    error: Some(e.to_body()),
+                }
+            }
+        };
+        results.push(result);
+    }
+
+    if results.is_empty() {
+        return no_file_error().error_response();
+    }
+
+    HttpResponse::Ok().json(results)
+}
+
+/// The error returned when a multipart payload holds no file at all.
+fn no_file_error() -> AuroraError {
+    AuroraError::BadRequest("No file found in multipart payload".to_string())
+}
+
+/// Returns the original filename of a multipart field, if it is a file.
+fn field_file_name(field: &Field) -> Option<String> {
+    field
+        .content_disposition()
+        .and_then(|disposition| disposition.get_filename())
+        .map(String::from)
+}
+
+/// Whether a form field holds the JSON `ParseOptions` of the upload.
+fn is_options_field(field: &Field) -> bool {
+    field.name() == Some("options")
+}
+
+/// Reads the JSON `ParseOptions` of an `options` form field.
+async fn read_options(field: Field) -> Result<ParseOptions, AuroraError> {
+    let content: Bytes = read_field(field, MAX_OPTIONS_SIZE).await?;
+    serde_json::from_slice(&content)
+        .map_err(|e| AuroraError::BadRequest(format!("Invalid options field: {}", e)))
+}
+
+/// Builds a `Parser` from the filename of the field, falling back to the
+/// content type of the part when the filename gives no MIME hint.
+fn parser_for_field(field: &Field, file_name: &str) -> Parser {
+    let mut parser: Parser = Parser::new(file_name.to_string());
+
+    if parser.file_mime == mime_guess::mime::APPLICATION_OCTET_STREAM.as_ref() {
+        if let Some(content_type) = field.content_type() {
+            parser.file_mime = content_type.to_string();
+        }
+    }
+
+    parser
+}
+
+/// Streams a multipart field into memory, enforcing the per-file size limit.
+async fn read_field(mut field: Field, max_size: usize) -> Result<Bytes, AuroraError> {
+    let mut content: BytesMut = BytesMut::new();
+
+    while let Some(chunk) = field.next().await {
+        let chunk: Bytes = chunk
+            .map_err(|e| AuroraError::BadRequest(format!("Error reading uploaded file: {}", e)))?;
+
+        if content.len() + chunk.len() > max_size {
+            return Err(AuroraError::PayloadTooLarge { limit: max_size });
+        }
+        content.extend_from_slice(&chunk);
+    }
+
+    info!("Read uploaded file of {} bytes", content.len());
+    Ok(content.freeze())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::dev::ServiceResponse;
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use actix_web::App;
+    use serde_json::Value;
+    use tokio::sync::Mutex;
+
+    /// Serializes the tests that read `AURORA_MAX_UPLOAD_BYTES`.
+    static ENV: Mutex<()> = Mutex::const_new(());
+
+    const BOUNDARY: &str = "aurora-test-boundary";
+
+    const REVOLUT_CSV: &str = "Type,Product,Started Date,Completed Date,Description,Amount,Fee,Currency,State,Balance
+CARD_PAYMENT,Current,2024-01-02 10:00:00,2024-01-02 10:00:00,Albert Heijn,-12.50,0.00,EUR,COMPLETED,87.50
+";
+
+    /// A form part: the field name, the filename of a file part and the content.
+    type Part<'a> = (&'a str, Option<&'a str>, &'a [u8]);
+
+    fn multipart_body(parts: &[Part<'_>]) -> Vec<u8> {
+        let mut body: Vec<u8> = vec![];
+        for (name, file_name, content) in parts {
+            body.extend(format!("--{}\r\n", BOUNDARY).into_bytes());
+            let disposition: String = match file_name {
+                Some(file_name) => format!("form-data; name=\"{}\"; filename=\"{}\"", name, file_name),
+                None => format!("form-data; name=\"{}\"", name),
+            };
+            body.extend(format!("Content-Disposition: {}\r\n\r\n", disposition).into_bytes());
+            body.extend_from_slice(content);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend(format!("--{}--\r\n", BOUNDARY).into_bytes());
+        body
+    }
+
+    async fn post(uri: &str, parts: &[Part<'_>]) -> ServiceResponse {
+        post_with_limit(uri, parts, None).await
+    }
+
+    /// Posts the parts with `AURORA_MAX_UPLOAD_BYTES` set to `limit`, or unset.
+    async fn post_with_limit(uri: &str, parts: &[Part<'_>], limit: Option<usize>) -> ServiceResponse {
+        let _env = ENV.lock().await;
+        match limit {
+            Some(limit) => std::env::set_var("AURORA_MAX_UPLOAD_BYTES", limit.to_string()),
+            None => std::env::remove_var("AURORA_MAX_UPLOAD_BYTES"),
+        }
+
+        let app = init_service(App::new().service(upload_file).service(upload_batch)).await;
+        let request = TestRequest::post()
+            .uri(uri)
+            .insert_header((
+                "content-type",
+                format!("multipart/form-data; boundary={}", BOUNDARY),
+            ))
+            .set_payload(multipart_body(parts))
+            .to_request();
+        let response: ServiceResponse = call_service(&app, request).await;
+
+        std::env::remove_var("AURORA_MAX_UPLOAD_BYTES");
+        response
+    }
+
+    #[actix_web::test]
+    async fn a_single_file_is_parsed() {
+        let response: ServiceResponse =
+            post("/upload", &[("file", Some("revolut.csv"), REVOLUT_CSV.as_bytes())]).await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = read_body_json(response).await;
+        assert_eq!(body["detection"]["document_provider"], "revolut_csv");
+        assert_eq!(body["data"][0]["amount"], "-12.50");
+    }
+
+    #[actix_web::test]
+    async fn a_file_over_the_limit_is_rejected_with_the_error_envelope() {
+        let response: ServiceResponse = post_with_limit(
+            "/upload",
+            &[("file", Some("revolut.csv"), REVOLUT_CSV.as_bytes())],
+            Some(64),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let body: Value = read_body_json(response).await;
+        assert_eq!(body["status"], "error");
+        assert_eq!(body["code"], "payload_too_large");
+        assert_eq!(body["details"]["limit"], 64);
+    }
+
+    #[actix_web::test]
+    async fn a_failing_file_of_a_batch_gets_its_own_error() {
+        let response: ServiceResponse = post(
+            "/upload/batch",
+            &[
+                ("file", Some("revolut.csv"), REVOLUT_CSV.as_bytes()),
+                ("file", Some("unknown.bin"), &[0, 159, 146, 150]),
+                ("file", Some("again.csv"), REVOLUT_CSV.as_bytes()),
+            ],
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = read_body_json(response).await;
+        let results: &Vec<Value> = body.as_array().unwrap();
+        assert_eq!(results.len(), 3);
+
+        assert_eq!(results[0]["status"], 200);
+        assert_eq!(results[0]["result"]["detection"]["document_provider"], "revolut_csv");
+        assert!(results[0].get("error").is_none());
+
+        assert_eq!(results[1]["file_name"], "unknown.bin");
+        assert_eq!(results[1]["status"], 415);
+        assert_eq!(results[1]["error"]["code"], "unsupported_format");
+        assert!(results[1].get("result").is_none());
+
+        assert_eq!(results[2]["status"], 200);
+    }
+
+    #[actix_web::test]
+    async fn a_form_without_a_file_is_a_bad_request() {
+        for uri in ["/upload", "/upload/batch"] {
+            let response: ServiceResponse = post(uri, &[("note", None, b"no file here")]).await;
+
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            let body: Value = read_body_json(response).await;
+            assert_eq!(body["code"], "bad_request");
+            assert_eq!(body["message"], "No file found in multipart payload");
+        }
+    }
+
+    #[actix_web::test]
+    async fn options_are_read_from_a_json_field() {
+        let options: &[u8] = br#"{"money_format": "minor_units", "ocr": {"languages": ["nld"], "dpi": 400}}"#;
+        let response: ServiceResponse = post(
+            "/upload",
+            &[("options", None, options), ("file", Some("revolut.csv"), REVOLUT_CSV.as_bytes())],
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Value = read_body_json(response).await;
+        assert_eq!(body["data"][0]["amount"], -1250);
+    }
+
+    #[actix_web::test]
+    async fn an_invalid_options_field_is_a_bad_request() {
+        let response: ServiceResponse = post(
+            "/upload",
+            &[("options", None, b"{\"ocr\": 3}"), ("file", Some("revolut.csv"), REVOLUT_CSV.as_bytes())],
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: Value = read_body_json(response).await;
+        assert_eq!(body["code"], "bad_request");
+    }
+}
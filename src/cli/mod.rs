@@ -0,0 +1,549 @@
+//! ## Command-line interface
+//!
+//! Runs the detection and casting pipeline on local files and directories
+//! without the HTTP API:
+//!
+//! ```bash
+//! aurora parse statements/ --output-format ndjson --output transactions.ndjson
+//! aurora detect account-statement.csv
+//! aurora providers list
+//! aurora serve
+//! ```
+//!
+//! Running `aurora` without a subcommand starts the web server, as before.
+
+use anyhow::{bail, Context, Result};
+use bytes::Bytes;
+use clap::{Args, Parser as ClapParser, Subcommand, ValueEnum};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::fs::{read_dir, File};
+use std::io::{stdout, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+// crate imports
+use crate::api::server::api;
+use crate::error::{AuroraError, ErrorBody};
+use crate::ocr::preprocess::{PreprocessKind, PreprocessOptions};
+use crate::ocr::{OcrOptions, DEFAULT_OCR_DPI};
+use crate::parser::builder::read_document;
+use crate::parser::caster::caster_registry::{load_configured_providers, registry, CasterRegistry};
+use crate::parser::caster::diagnostic::Diagnostic;
+use crate::parser::document::{DocumentMetadata, ParsedDocument};
+use crate::parser::format::{detect_format, DocumentFormat};
+use crate::parser::options::{DateOutput, MoneyFormat, OutputShape, ParseOptions, PdfExtraction};
+use crate::parser::schema::detection::Detection;
+use crate::parser::schema::determine_document_provider;
+use crate::{parse_file, Parser};
+
+/// The arguments of the `aurora` binary.
+#[derive(ClapParser, Debug)]
+#[command(name = "aurora", version, about = "Parse bank statements and financial documents into structured data")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// The subcommands of the `aurora` binary.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Parses files, or every file in a directory, into transactions.
+    Parse(ParseArgs),
+    /// Detects the format and provider of files without printing their data.
+    Detect(DetectArgs),
+    /// Manages the providers Aurora knows.
+    Providers {
+        #[command(subcommand)]
+        command: ProvidersCommand,
+    },
+    /// Starts the HTTP API.
+    Serve,
+}
+
+/// The subcommands of `aurora providers`.
+#[derive(Subcommand, Debug)]
+pub enum ProvidersCommand {
+    /// Lists every registered provider, including the ones of `config.yaml`.
+    List,
+}
+
+/// The arguments of `aurora parse`.
+#[derive(Args, Debug)]
+pub struct ParseArgs {
+    /// The files or directories to parse, directories are walked recursively.
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+    /// How the result is written.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
+    pub output_format: OutputFormat,
+    /// Writes the result to this file instead of stdout.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Skips detection and casts every file as this provider, e.g. `revolut_csv`.
+    #[arg(long)]
+    pub provider: Option<String>,
+    /// The shape transactions are returned in: `canonical` or `provider`.
+    #[arg(long, default_value = "canonical", value_parser = parse_option::<OutputShape>)]
+    pub shape: OutputShape,
+    /// How amounts are written: `string` or `minor_units`.
+    #[arg(long, default_value = "string", value_parser = parse_option::<MoneyFormat>)]
+    pub money_format: MoneyFormat,
+    /// How dates are written: `unix_seconds`, `iso8601` or `date_only`.
+    #[arg(long, default_value = "unix_seconds", value_parser = parse_option::<DateOutput>)]
+    pub date_format: DateOutput,
+    /// Fails a file when any of its rows can't be cast.
+    #[arg(long)]
+    pub strict: bool,
+    /// How text is extracted from PDF documents: `text`, `layout` or `table`.
+    #[arg(long, default_value = "text", value_parser = parse_option::<PdfExtraction>)]
+    pub pdf_extraction: PdfExtraction,
+    /// The Tesseract languages images and scans are recognised in, e.g. `nld,eng`.
+    #[arg(long, value_delimiter = ',', default_value = "nld,eng")]
+    pub ocr_languages: Vec<String>,
+    /// The resolution of images and scans in dots per inch.
+    #[arg(long, default_value_t = DEFAULT_OCR_DPI)]
+    pub ocr_dpi: u32,
+    /// The preprocessing steps to skip before OCR, e.g. `binarize,crop_borders`.
+    #[arg(long, value_delimiter = ',', value_parser = parse_option::<PreprocessKind>)]
+    pub ocr_skip: Vec<PreprocessKind>,
+}
+
+/// The arguments of `aurora detect`.
+#[derive(Args, Debug)]
+pub struct DetectArgs {
+    /// The files or directories to inspect, directories are walked recursively.
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+    /// Writes the result to this file instead of stdout.
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+}
+
+/// How `aurora parse` writes its result.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// A single JSON document, an array of documents for several files.
+    Json,
+    /// A JSON object per record and line.
+    Ndjson,
+    /// A CSV row per record, nested objects are written as JSON.
+    Csv,
+}
+
+/// The outcome of parsing a single file.
+#[derive(Serialize, Debug)]
+struct FileResult {
+    file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    document: Option<ParsedDocument>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorBody>,
+}
+
+/// Runs the `aurora` binary.
+///
+/// # Arguments
+///
+/// * `cli` - The parsed command-line arguments.
+///
+/// # Returns
+///
+/// A `Result` that is an error when the command failed, or when any file couldn't be parsed.
+pub async fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        None | Some(Command::Serve) => api().await.context("Failed to start server"),
+        Some(Command::Parse(args)) => {
+            load_providers();
+            parse(args).await
+        }
+        Some(Command::Detect(args)) => {
+            load_providers();
+            detect(args).await
+        }
+        Some(Command::Providers {
+            command: ProvidersCommand::List,
+        }) => {
+            load_providers();
+            for caster in registry().casters() {
+                println!("{}", caster.name());
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Loads the provider definitions of `config.yaml`, a broken file only costs its own providers.
+fn load_providers() {
+    if let Err(e) = load_configured_providers() {
+        eprintln!("warning: failed to load provider definitions: {:#}", e);
+    }
+}
+
+async fn parse(args: ParseArgs) -> Result<()> {
+    let options: ParseOptions = ParseOptions {
+        output_shape: args.shape,
+        money_format: args.money_format,
+        date_format: args.date_format,
+        provider: args.provider.clone(),
+        strict: args.strict,
+        pdf_extraction: args.pdf_extraction,
+        ocr: OcrOptions {
+            languages: args.ocr_languages.clone(),
+            dpi: args.ocr_dpi,
+            preprocess: PreprocessOptions::default().without(&args.ocr_skip),
+        },
+    };
+    let files: Vec<PathBuf> = collect_files(&args.paths)?;
+    let results: Vec<FileResult> = parse_files(&files, &options).await;
+
+    let mut writer: Box<dyn Write> = open_output(args.output.as_deref())?;
+    match args.output_format {
+        OutputFormat::Json => write_json(&mut writer, &results)?,
+        OutputFormat::Ndjson => write_ndjson(&mut writer, &results)?,
+        OutputFormat::Csv => write_csv(&mut writer, &results)?,
+    }
+    writer.flush()?;
+
+    report_failures(&results)
+}
+
+async fn detect(args: DetectArgs) -> Result<()> {
+    let files: Vec<PathBuf> = collect_files(&args.paths)?;
+
+    let mut writer: Box<dyn Write> = open_output(args.output.as_deref())?;
+    let mut failed: usize = 0;
+    for file in &files {
+        let file_name: String = file.display().to_string();
+        let line: Value = match detect_file(&file_name).await {
+            Ok((document_format, detection)) => json!({
+                "file": file_name,
+                "document_format": document_format,
+                "detection": detection,
+            }),
+            Err(e) => {
+                eprintln!("error: {}: {}", file_name, e);
+                failed += 1;
+                json!({ "file": file_name, "error": e.to_body() })
+            }
+        };
+        writeln!(writer, "{}", line)?;
+    }
+    writer.flush()?;
+
+    if failed > 0 {
+        bail!("{} of {} files couldn't be detected", failed, files.len());
+    }
+    Ok(())
+}
+
+/// Detects the format and provider of a file, its rows are read but not cast.
+async fn detect_file(file_name: &str) -> Result<(DocumentFormat, Detection), AuroraError> {
+    let content: Bytes = std::fs::read(file_name)
+        .map(Bytes::from)
+        .map_err(|e| AuroraError::Storage(format!("Error reading {}: {}", file_name, e)))?;
+    let parser: Parser = Parser::new(file_name.to_string());
+    let registry: Arc<CasterRegistry> = registry();
+
+    let document_format: DocumentFormat = detect_format(&content, &parser);
+    let (rows, _, _): (Value, DocumentMetadata, Vec<Diagnostic>) =
+        read_document(&content, &parser, document_format, &ParseOptions::default(), &registry).await?;
+    let (_, detection): (Value, Detection) =
+        determine_document_provider(&rows, Some(file_name), None, &registry)
+            .map_err(AuroraError::Detection)?;
+    Ok((document_format, detection))
+}
+
+/// Parses every file independently, a failing file doesn't stop the others.
+async fn parse_files(files: &[PathBuf], options: &ParseOptions) -> Vec<FileResult> {
+    let mut results: Vec<FileResult> = vec![];
+    for file in files {
+        let file_name: String = file.display().to_string();
+        let result: FileResult = match parse_file(&file_name, options).await {
+            Ok(document) => FileResult {
+                file: file_name,
+                document: Some(document),
+                error: None,
+            },
+            Err(e) => FileResult {
+                file: file_name,
+                document: None,
+                error: Some(e.to_body()),
+            },
+        };
+        results.push(result);
+    }
+    results
+}
+
+/// Prints every failed file and every row that couldn't be cast to stderr.
+fn report_failures(results: &[FileResult]) -> Result<()> {
+    for result in results {
+        let Some(document) = &result.document else {
+            continue;
+        };
+        for diagnostic in &document.errors {
+            match diagnostic.row {
+                Some(row) => eprintln!("warning: {}: row {}: {}", result.file, row, diagnostic.error),
+                None => eprintln!("warning: {}: {}", result.file, diagnostic.error),
+            }
+        }
+    }
+
+    let failed: Vec<&FileResult> = results.iter().filter(|result| result.error.is_some()).collect();
+    for result in &failed {
+        if let Some(error) = &result.error {
+            eprintln!("error: {}: {}", result.file, error.message);
+        }
+    }
+    if !failed.is_empty() {
+        bail!("{} of {} files couldn't be parsed", failed.len(), results.len());
+    }
+    Ok(())
+}
+
+/// Expands directories into the files they hold, recursively and in a stable order.
+///
+/// Hidden files and directories are skipped.
+fn collect_files(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = vec![];
+    for path in paths {
+        if path.is_dir() {
+            let mut entries: Vec<PathBuf> = read_dir(path)
+                .with_context(|| format!("Failed to read directory {}", path.display()))?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|entry| {
+                    !entry
+                        .file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with('.'))
+                })
+                .collect();
+            entries.sort();
+            files.extend(collect_files(&entries)?);
+        } else if path.is_file() {
+            files.push(path.clone());
+        } else {
+            bail!("No such file or directory: {}", path.display());
+        }
+    }
+    Ok(files)
+}
+
+fn open_output(output: Option<&Path>) -> Result<Box<dyn Write>> {
+    match output {
+        Some(path) => {
+            let file: File = File::create(path)
+                .with_context(|| format!("Failed to create {}", path.display()))?;
+            Ok(Box::new(BufWriter::new(file)))
+        }
+        None => Ok(Box::new(BufWriter::new(stdout()))),
+    }
+}
+
+/// Writes a single document as is, several files as an array of results.
+fn write_json(writer: &mut dyn Write, results: &[FileResult]) -> Result<()> {
+    match results {
+        [FileResult {
+            document: Some(document),
+            ..
+        }] => serde_json::to_writer_pretty(&mut *writer, document)?,
+        _ => serde_json::to_writer_pretty(&mut *writer, results)?,
+    }
+    writeln!(writer)?;
+    Ok(())
+}
+
+fn write_ndjson(writer: &mut dyn Write, results: &[FileResult]) -> Result<()> {
+    for record in records(results)? {
+        writeln!(writer, "{}", Value::Object(record))?;
+    }
+    Ok(())
+}
+
+/// Writes every record as a CSV row, the header is the union of every key in order of appearance.
+fn write_csv(writer: &mut dyn Write, results: &[FileResult]) -> Result<()> {
+    let records: Vec<Map<String, Value>> = records(results)?;
+    let mut header: Vec<String> = vec!["file".to_string()];
+    for record in &records {
+        for key in record.keys() {
+            if !header.contains(key) {
+                header.push(key.clone());
+            }
+        }
+    }
+
+    let mut csv_writer: csv::Writer<&mut dyn Write> = csv::Writer::from_writer(writer);
+    csv_writer.write_record(&header)?;
+    for record in &records {
+        let row: Vec<String> = header
+            .iter()
+            .map(|key| match record.get(key) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(value)) => value.clone(),
+                Some(value) => value.to_string(),
+            })
+            .collect();
+        csv_writer.write_record(&row)?;
+    }
+    csv_writer.flush()?;
+    Ok(())
+}
+
+/// Flattens the records of every parsed file, each prefixed with the `file` it came from.
+fn records(results: &[FileResult]) -> Result<Vec<Map<String, Value>>> {
+    let mut records: Vec<Map<String, Value>> = vec![];
+    for result in results {
+        let Some(document) = &result.document else {
+            continue;
+        };
+        for record in &document.data {
+            let mut row: Map<String, Value> = Map::new();
+            row.insert("file".to_string(), Value::String(result.file.clone()));
+            if let Value::Object(fields) = serde_json::to_value(record)? {
+                row.extend(fields);
+            }
+            records.push(row);
+        }
+    }
+    Ok(records)
+}
+
+/// Parses an option value the way the HTTP API does, e.g. `minor_units`.
+fn parse_option<T: DeserializeOwned>(value: &str) -> Result<T, String> {
+    serde_json::from_value(Value::String(value.to_string())).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::document::Record;
+
+    const REVOLUT_CSV: &str = "Type,Product,Started Date,Completed Date,Description,Amount,Fee,Currency,State,Balance
+CARD_PAYMENT,Current,2024-01-02 10:00:00,2024-01-02 10:00:00,Albert Heijn,-12.50,0.00,EUR,COMPLETED,87.50
+";
+
+    /// Creates an empty directory in the system temp directory.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir: PathBuf = std::env::temp_dir().join(format!("aurora-cli-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn document(rows: Vec<Value>) -> ParsedDocument {
+        ParsedDocument {
+            document_format: DocumentFormat::Csv,
+            detection: Detection {
+                document_provider: "unknown".to_string(),
+                forced: false,
+                candidates: vec![],
+            },
+            data: rows
+                .into_iter()
+                .map(|row| match row {
+                    Value::Object(fields) => Record::Row(fields),
+                    _ => unreachable!(),
+                })
+                .collect(),
+            warnings: vec![],
+            errors: vec![],
+            metadata: DocumentMetadata::default(),
+        }
+    }
+
+    #[test]
+    fn directories_are_walked_in_order_without_hidden_entries() {
+        let dir: PathBuf = temp_dir("collect");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::create_dir_all(dir.join(".git")).unwrap();
+        for file in ["b.csv", "a.csv", ".DS_Store", "nested/c.csv", ".git/config"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+
+        let files: Vec<PathBuf> = collect_files(&[dir.clone()]).unwrap();
+        assert_eq!(files, vec![dir.join("a.csv"), dir.join("b.csv"), dir.join("nested/c.csv")]);
+        assert!(collect_files(&[dir.join("missing.csv")]).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn the_csv_header_is_the_union_of_every_record() {
+        let results: Vec<FileResult> = vec![
+            FileResult {
+                file: "first.csv".to_string(),
+                document: Some(document(vec![json!({"date": "2024-01-02", "amount": "-12.50"})])),
+                error: None,
+            },
+            FileResult {
+                file: "broken.pdf".to_string(),
+                document: None,
+                error: Some(AuroraError::BadRequest("broken".to_string()).to_body()),
+            },
+            FileResult {
+                file: "second.csv".to_string(),
+                document: Some(document(vec![json!({"amount": "3.00", "note": {"tag": "x"}})])),
+                error: None,
+            },
+        ];
+
+        let mut output: Vec<u8> = vec![];
+        write_csv(&mut output, &results).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "file,date,amount,note\nfirst.csv,2024-01-02,-12.50,\nsecond.csv,,3.00,\"{\"\"tag\"\":\"\"x\"\"}\"\n"
+        );
+    }
+
+    #[actix_web::test]
+    async fn a_failing_file_fails_the_run_but_not_the_others() {
+        let dir: PathBuf = temp_dir("failing");
+        std::fs::write(dir.join("revolut.csv"), REVOLUT_CSV).unwrap();
+        std::fs::write(dir.join("unknown.bin"), [0u8, 159, 146, 150]).unwrap();
+        let output: PathBuf = dir.join(".output.ndjson");
+
+        let cli: Cli = Cli::try_parse_from([
+            "aurora".as_ref(),
+            "parse".as_ref(),
+            dir.as_os_str(),
+            "--output-format".as_ref(),
+            "ndjson".as_ref(),
+            "--output".as_ref(),
+            output.as_os_str(),
+        ])
+        .unwrap();
+        let error: anyhow::Error = run(cli).await.unwrap_err();
+        assert_eq!(error.to_string(), "1 of 2 files couldn't be parsed");
+
+        let lines: Vec<Value> = std::fs::read_to_string(&output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["file"], dir.join("revolut.csv").display().to_string());
+
+        let cli: Cli = Cli::try_parse_from([
+            "aurora".as_ref(),
+            "detect".as_ref(),
+            dir.as_os_str(),
+            "--output".as_ref(),
+            output.as_os_str(),
+        ])
+        .unwrap();
+        let error: anyhow::Error = run(cli).await.unwrap_err();
+        assert_eq!(error.to_string(), "1 of 2 files couldn't be detected");
+
+        let lines: Vec<Value> = std::fs::read_to_string(&output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines[0]["detection"]["document_provider"], "revolut_csv");
+        assert_eq!(lines[1]["error"]["code"], "unsupported_format");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -6,6 +6,7 @@
 //! to retrieve the API port from an environment variable.
 
 use std::env;
+use std::path::PathBuf;
 use tracing::{error, info, warn};
 
 /// Constructs the full path to a script by appending the script name to the
@@ -50,3 +51,69 @@ pub fn get_api_port() -> u16 {
         .parse()
         .unwrap_or(7777)
 }
+
+/// Retrieves the maximum size of a single uploaded file from the
+/// `AURORA_MAX_UPLOAD_BYTES` environment variable.
+///
+/// # Returns
+///
+/// A `usize` representing the per-file upload limit in bytes. Defaults to
+/// 25 MiB if the environment variable is not set or cannot be parsed.
+pub fn get_max_upload_size() -> usize {
+    env::var("AURORA_MAX_UPLOAD_BYTES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(25 * 1024 * 1024)
+}
+
+/// Retrieves the path of the provider definitions from the
+/// `AURORA_CONFIG_PATH` environment variable.
+///
+/// # Returns
+///
+/// A `PathBuf` to the YAML file. Defaults to `config.yaml` in the working
+/// directory if the environment variable is not set.
+pub fn get_config_path() -> PathBuf {
+    env::var("AURORA_CONFIG_PATH")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("config.yaml"))
+}
+
+/// Retrieves the token guarding the `/admin` endpoints from the
+/// `AURORA_ADMIN_TOKEN` environment variable.
+///
+/// # Returns
+///
+/// The token, or `None` if the environment variable is not set or empty, in
+/// which case the `/admin` endpoints are disabled.
+pub fn get_admin_token() -> Option<String> {
+    env::var("AURORA_ADMIN_TOKEN")
+        .ok()
+        .filter(|token| !token.trim().is_empty())
+}
+
+/// Retrieves the directory holding the Tesseract trained data from the
+/// `AURORA_TESSDATA_PATH` environment variable.
+///
+/// # Returns
+///
+/// The directory, or `None` if the environment variable is not set or empty,
+/// in which case Tesseract falls back to `TESSDATA_PREFIX`.
+pub fn get_tessdata_path() -> Option<String> {
+    env::var("AURORA_TESSDATA_PATH")
+        .ok()
+        .filter(|path| !path.trim().is_empty())
+}
+
+/// Retrieves the directory holding the pdfium library from the
+/// `AURORA_PDFIUM_PATH` environment variable.
+///
+/// # Returns
+///
+/// The directory, or `None` if the environment variable is not set or empty,
+/// in which case pdfium is looked up on the system library path.
+pub fn get_pdfium_path() -> Option<String> {
+    env::var("AURORA_PDFIUM_PATH")
+        .ok()
+        .filter(|path| !path.trim().is_empty())
+}
@@ -0,0 +1,285 @@
+//! ## Errors
+//!
+//! Every failure Aurora reports to a client is an [`AuroraError`]. Each variant
+//! maps to a single HTTP status and a stable, machine readable `code`, and is
+//! rendered as the same JSON envelope by every endpoint:
+//!
+//! ```json
+//! {
+//!   "status": "error",
+//!   "code": "parse_failed",
+//!   "message": "Error parsing mt940 document: missing :20: transaction reference",
+//!   "details": { "format": "mt940" }
+//! }
+//! ```
+
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use thiserror::Error;
+
+// crate imports
+use crate::parser::caster::diagnostic::Diagnostic;
+use crate::parser::format::DocumentFormat;
+
+/// Everything that can go wrong while fetching, parsing and casting a document.
+#[derive(Debug, Clone, Error)]
+pub enum AuroraError {
+    /// The request itself is malformed, e.g. an invalid multipart payload.
+    #[error("{0}")]
+    BadRequest(String),
+    /// The document exceeds the configured upload size.
+    #[error("File exceeds the maximum upload size of {limit} bytes")]
+    PayloadTooLarge { limit: usize },
+    /// The document format couldn't be detected or has no parsing engine.
+    #[error("{message}")]
+    UnsupportedFormat { format: DocumentFormat, message: String },
+    /// The document couldn't be downloaded from its URL.
+    #[error("Failed to fetch {url}: {message}")]
+    Fetch { url: String, message: String },
+    /// The provider of the document couldn't be determined, e.g. an unknown forced provider.
+    #[error("{0}")]
+    Detection(String),
+    /// The parsing engine rejected the document.
+    #[error("Error parsing {format} document: {message}")]
+    Parse { format: String, message: String },
+    /// Rows couldn't be cast in strict mode.
+    #[error("{} rows couldn't be cast in strict mode", .0.len())]
+    Cast(Vec<Diagnostic>),
+    /// Text couldn't be recognised in a scanned document.
+    #[error("OCR failed: {0}")]
+    Ocr(String),
+    /// A file couldn't be read from or written to disk.
+    #[error("Storage error: {0}")]
+    Storage(String),
+    /// The requested resource doesn't exist.
+    #[error("{0}")]
+    NotFound(String),
+    /// The request lacks valid credentials.
+    #[error("{0}")]
+    Unauthorized(String),
+    /// The endpoint is disabled on this server.
+    #[error("{0}")]
+    Forbidden(String),
+    /// The provider definitions of `config.yaml` are invalid.
+    #[error("{0}")]
+    Config(String),
+}
+
+/// The JSON envelope every error is rendered as.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ErrorBody {
+    /// Always `error`.
+    pub status: String,
+    /// The stable, machine readable code of the `AuroraError`, e.g. `parse_failed`.
+    pub code: String,
+    /// A human readable message.
+    pub message: String,
+    /// Structured details, `null` when the error has none.
+    pub details: Option<Value>,
+}
+
+impl AuroraError {
+    /// Returns the stable, machine readable code of the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AuroraError::BadRequest(_) => "bad_request",
+            AuroraError::PayloadTooLarge { .. } => "payload_too_large",
+            AuroraError::UnsupportedFormat { .. } => "unsupported_format",
+            AuroraError::Fetch { .. } => "fetch_failed",
+            AuroraError::Detection(_) => "detection_failed",
+            AuroraError::Parse { .. } => "parse_failed",
+            AuroraError::Cast(_) => "cast_failed",
+            AuroraError::Ocr(_) => "ocr_failed",
+            AuroraError::Storage(_) => "storage_failed",
+            AuroraError::NotFound(_) => "not_found",
+            AuroraError::Unauthorized(_) => "unauthorized",
+            AuroraError::Forbidden(_) => "forbidden",
+            AuroraError::Config(_) => "invalid_config",
+        }
+    }
+
+    /// Returns the structured details of the error, if any.
+    pub fn details(&self) -> Option<Value> {
+        match self {
+            AuroraError::PayloadTooLarge { limit } => Some(json!({ "limit": limit })),
+            AuroraError::UnsupportedFormat { format, .. } => Some(json!({ "format": format })),
+            AuroraError::Fetch { url, .. } => Some(json!({ "url": url })),
+            AuroraError::Parse { format, .. } => Some(json!({ "format": format })),
+            AuroraError::Cast(errors) => Some(json!({ "errors": errors })),
+            _ => None,
+        }
+    }
+
+    /// Renders the error as its JSON envelope.
+    pub fn to_body(&self) -> ErrorBody {
+        ErrorBody {
+            status: "error".to_string(),
+            code: self.code().to_string(),
+            message: self.to_string(),
+            details: self.details(),
+        }
+    }
+}
+
+impl ResponseError for AuroraError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AuroraError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AuroraError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            AuroraError::UnsupportedFormat { .. } => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AuroraError::Fetch { .. } => StatusCode::BAD_GATEWAY,
+            AuroraError::Detection(_)
+            | AuroraError::Parse { .. }
+            | AuroraError::Cast(_)
+            | AuroraError::Ocr(_)
+            | AuroraError::Config(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AuroraError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AuroraError::NotFound(_) => StatusCode::NOT_FOUND,
+            AuroraError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AuroraError::Forbidden(_) => StatusCode::FORBIDDEN,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(self.to_body())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::caster::diagnostic::CastError;
+    use actix_web::body::to_bytes;
+
+    #[test]
+    fn every_variant_has_its_code_and_status() {
+        let cases: Vec<(AuroraError, &str, StatusCode)> = vec![
+            (AuroraError::BadRequest("no file".to_string()), "bad_request", StatusCode::BAD_REQUEST),
+            (
+                AuroraError::PayloadTooLarge { limit: 10 },
+                "payload_too_large",
+                StatusCode::PAYLOAD_TOO_LARGE,
+            ),
+            (
+                AuroraError::UnsupportedFormat {
+                    format: DocumentFormat::Xml,
+                    message: "XML isn't supported".to_string(),
+                },
+                "unsupported_format",
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ),
+            (
+                AuroraError::Fetch {
+                    url: "https://example.com/statement.csv".to_string(),
+                    message: "404 Not Found".to_string(),
+                },
+                "fetch_failed",
+                StatusCode::BAD_GATEWAY,
+            ),
+            (
+                AuroraError::Detection("unknown provider".to_string()),
+                "detection_failed",
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                AuroraError::Parse {
+                    format: "mt940".to_string(),
+                    message: "missing :20:".to_string(),
+                },
+                "parse_failed",
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (AuroraError::Cast(vec![]), "cast_failed", StatusCode::UNPROCESSABLE_ENTITY),
+            (AuroraError::Ocr("no text".to_string()), "ocr_failed", StatusCode::UNPROCESSABLE_ENTITY),
+            (
+                AuroraError::Storage("disk full".to_string()),
+                "storage_failed",
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (AuroraError::NotFound("gone".to_string()), "not_found", StatusCode::NOT_FOUND),
+            (
+                AuroraError::Unauthorized("bad token".to_string()),
+                "unauthorized",
+                StatusCode::UNAUTHORIZED,
+            ),
+            (AuroraError::Forbidden("disabled".to_string()), "forbidden", StatusCode::FORBIDDEN),
+            (
+                AuroraError::Config("invalid yaml".to_string()),
+                "invalid_config",
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+        ];
+
+        for (error, code, status) in cases {
+            assert_eq!(error.code(), code, "{:?}", error);
+            assert_eq!(error.status_code(), status, "{:?}", error);
+        }
+    }
+
+    #[test]
+    fn details_carry_the_structured_fields() {
+        assert_eq!(
+            AuroraError::PayloadTooLarge { limit: 10 }.details(),
+            Some(json!({ "limit": 10 }))
+        );
+        assert_eq!(
+            AuroraError::UnsupportedFormat {
+                format: DocumentFormat::Xml,
+                message: "XML isn't supported".to_string(),
+            }
+            .details(),
+            Some(json!({ "format": "xml" }))
+        );
+        assert_eq!(
+            AuroraError::Fetch {
+                url: "https://example.com/a.csv".to_string(),
+                message: "404 Not Found".to_string(),
+            }
+            .details(),
+            Some(json!({ "url": "https://example.com/a.csv" }))
+        );
+        assert_eq!(
+            AuroraError::Parse {
+                format: "mt940".to_string(),
+                message: "missing :20:".to_string(),
+            }
+            .details(),
+            Some(json!({ "format": "mt940" }))
+        );
+
+        let errors: Vec<Diagnostic> = vec![Diagnostic::row(1, CastError::field("amount", "n/a", "not a number"))];
+        let details: Value = AuroraError::Cast(errors).details().unwrap();
+        assert_eq!(details["errors"][0]["row"], 1);
+        assert_eq!(details["errors"][0]["field"], "amount");
+
+        assert_eq!(AuroraError::Ocr("no text".to_string()).details(), None);
+        assert_eq!(AuroraError::BadRequest("no file".to_string()).details(), None);
+    }
+
+    #[tokio::test]
+    async fn renders_the_json_envelope() {
+        let error: AuroraError = AuroraError::Parse {
+            format: "mt940".to_string(),
+            message: "missing :20: transaction reference".to_string(),
+        };
+        let response: HttpResponse = error.error_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+        let body: ErrorBody = serde_json::from_slice(&to_bytes(response.into_body()).await.unwrap()).unwrap();
+        assert_eq!(
+            body,
+            ErrorBody {
+                status: "error".to_string(),
+                code: "parse_failed".to_string(),
+                message: "Error parsing mt940 document: missing :20: transaction reference".to_string(),
+                details: Some(json!({ "format": "mt940" })),
+            }
+        );
+
+        // errors without details render them as null
+        let body: Value = serde_json::to_value(AuroraError::Forbidden("disabled".to_string()).to_body()).unwrap();
+        assert_eq!(body, json!({ "status": "error", "code": "forbidden", "message": "disabled", "details": null }));
+    }
+}
@@ -18,56 +18,145 @@
 //! }
 //! ```
 //!
+//! Files that can't be exposed through a URL can be sent as `multipart/form-data`,
+//! either one at a time or as a batch with a result per file.
+//! ```bash
+//! curl -F "file=@statement.csv" http://localhost:7777/upload
+//! curl -F "file=@january.csv" -F "file=@february.pdf" http://localhost:7777/upload/batch
+//! ```
+//!
+//! Uploads take their options as a JSON `options` field before the files, or
+//! flat ones such as `?strict=true` from the query string.
+//! ```bash
+//! curl -F 'options={"ocr": {"languages": ["nld"]}}' -F "file=@scan.png" http://localhost:7777/upload
+//! ```
+//!
+//! ## Formats
+//!
+//! - **CSV** in any encoding, with its delimiter and quote character sniffed.
+//!   Account details above the header are returned under `metadata.preamble`,
+//!   totals below the data are skipped, see [`parser::csv`].
+//! - **Spreadsheets** (`.xlsx`, `.xls`, `.ods`): the first worksheet holding
+//!   data is read like a CSV export, see [`parser::spreadsheet`].
+//! - **MT940** statements, see [`parser::mt940`].
+//! - **PDF** statements, see [PDF modes](#pdf-modes).
+//! - **Images** (PNG, JPEG, TIFF) are read with OCR, see [OCR](#ocr).
+//!
+//! Anything else, XML included, fails with `unsupported_format`.
+//!
+//! ## Options
+//!
+//! Every option goes in the request body next to `file_url`, see [`ParseOptions`].
+//!
+//! - `output_shape` - `canonical` (default) returns the provider-agnostic
+//!   `Transaction`, `provider` the provider-specific fields.
+//! - `money_format` - exact decimal strings by default, `minor_units` for
+//!   integers in the minor units of the currency (`5000` for `50.00 EUR`).
+//! - `date_format` - Unix time seconds by default, `iso8601` or `date_only` in
+//!   the timezone of the provider, e.g. `Europe/Amsterdam` for MT940.
+//! - `provider` - skips detection, e.g. `"provider": "revolut_csv"`.
+//! - `strict` - fails the whole document with `422 Unprocessable Entity` when
+//!   any row can't be cast.
+//! - `pdf_extraction` - `text` (default), `layout` or `table`.
+//! - `ocr` - the languages, resolution and preprocessing of OCR.
+//!
+//! ## Diagnostics
+//!
+//! - `detection` lists every candidate provider with its confidence.
+//! - Every transaction keeps the columns of its source row, as parsed, under `raw`.
+//! - `errors` lists the rows that couldn't be cast and were left out of `data`,
+//!   with their row index, field, raw value and reason.
+//! - `warnings` lists what didn't fail the document but is worth knowing, e.g.
+//!   an MT940 closing balance that doesn't match its transactions, or PDF pages
+//!   that couldn't be read with OCR.
+//! - `metadata` says how the document was read: its `encoding`, `dialect`,
+//!   `preamble`, `ocr` and `table`.
+//!
+//! ## OCR
+//!
+//! Images and PDF pages without a text layer are recognised with Tesseract, in
+//! Dutch and English by default, send `"ocr": { "languages": ["deu", "fra"] }`
+//! for others. Images are preprocessed first, steps can be turned off per
+//! request, e.g. `"ocr": { "preprocess": { "binarize": false } }`. The engine,
+//! confidence and preprocessing steps are returned under `metadata.ocr`, see
+//! [`ocr`] and [`ocr::preprocess`].
+//!
+//! ## PDF modes
+//!
+//! - `text` - the text of every page, with where it came from (`text_layer` or `ocr`).
+//! - `layout` - also every text run with its bounding box, font name and size,
+//!   see [`parser::pdf::layout`]. Without pdfium the pages keep their text and
+//!   the document gets a warning.
+//! - `table` - the transaction table rebuilt from the runs, every row keyed by
+//!   its column header like a CSV row, with the columns under `metadata.table`,
+//!   see [`parser::pdf::table`].
+//!
 //! ## Example response (`Revolut personal CSV`)
-//! This is with the `f64` casting
 //! ```json
-//! [
-//!   {
-//!     "data": {
-//!         "type": "TRANSFER",
-//!         "balance": 100,
-//!         "amount": 50,
-//!         "completed_date": "2024-01-01 01:24:30",
-//!         "currency": "EUR",
-//!         "fee": 0.0,
-//!         "description": "To EUR Pocket",
-//!         "state": "COMPLETED",
-//!         "started_date": "2024-01-01 01:24:30",
-//!         "product": "Savings"
-//!     },
-//!     "document_provider": "Revolut"
+//! {
+//!   "document_format": "csv",
+//!   "detection": {
+//!     "document_provider": "revolut_csv",
+//!     "forced": false,
+//!     "candidates": [
+//!       { "document_provider": "revolut_csv", "confidence": 1.0 },
+//!       { "document_provider": "abn_amro_mt940", "confidence": 0.28 }
+//!     ]
 //!   },
-//!   {
-//!     "data": {
+//!   "data": [
+//!     {
+//!       "document_provider": "revolut_csv",
+//!       "booking_date": 1704072270,
+//!       "value_date": 1704072270,
+//!       "amount": "50.00",
+//!       "currency": "EUR",
+//!       "counterparty_name": null,
+//!       "counterparty_iban": null,
+//!       "description": "To EUR Pocket",
+//!       "reference": null,
+//!       "balance_after": "100.00",
+//!       "fee": "0.00",
+//!       "status": "completed",
+//!       "provider_fields": {
 //!         "type": "TRANSFER",
 //!         "product": "Savings",
-//!         "currency": "EUR",
-//!         "state": "COMPLETED",
-//!         "amount": 50,
-//!         "started_date": "2024-01-01 09:22:37",
-//!         "description": "To EUR Pocket",
-//!         "balance": 500,
-//!         "fee": 0.0,
-//!         "completed_date": "2024-01-01 09:22:37"
-//!     },
-//!     "document_provider": "Revolut"
-//!   },
-//!   {
-//!     "data": {
-//!         "balance": 1000,
-//!         "product": "Savings",
+//!         "state": "COMPLETED"
+//!       },
+//!       "raw": {
 //!         "type": "TRANSFER",
+//!         "product": "Savings",
+//!         "started_date": "2024-01-01 01:24:30",
+//!         "completed_date": "2024-01-01 01:24:30",
 //!         "description": "To EUR Pocket",
-//!         "started_date": "2024-01-04 13:48:07",
-//!         "fee": 0.0,
+//!         "amount": "50.00",
+//!         "fee": "0.00",
 //!         "currency": "EUR",
 //!         "state": "COMPLETED",
-//!         "amount": 1.0,
-//!         "completed_date": "2024-01-04 13:48:07"
-//!     },
-//!     "document_provider": "Revolut"
+//!         "balance": "100.00"
+//!       }
+//!     }
+//!   ],
+//!   "warnings": [],
+//!   "errors": [
+//!     { "row": 3, "field": "amount", "raw_value": "n/a", "reason": "Failed to cast amount to Decimal" }
+//!   ],
+//!   "metadata": {
+//!     "encoding": { "name": "utf-8", "source": "detected", "confident": true, "had_errors": false },
+//!     "dialect": { "delimiter": ",", "quote": "\"", "header_line": 0, "footer_lines": 0 }
 //!   }
-//! ]
+//! }
+//! ```
+//!
+//! ## Errors
+//! Every error is returned as the same JSON envelope with a stable `code`, see
+//! [`error::AuroraError`] for every code and its HTTP status.
+//! ```json
+//! {
+//!   "status": "error",
+//!   "code": "unsupported_format",
+//!   "message": "Unable to determine the document format",
+//!   "details": null
+//! }
 //! ```
 //!
 //! ## Document Foundry
@@ -80,12 +169,12 @@
 //! |-------------------------|-----------|-----------------------|----------------|
 //! | **ABN AMRO**            | Business  | CSV                   | ❌               |
 //! | **ABN AMRO**            | Business  | PDF                   | ❌               |
-//! | **ABN AMRO**            | Business  | MT940                 | ❌               |
+//! | **ABN AMRO**            | Business  | MT940                 | ✅               |
 //! | **ABN AMRO**            | Business  | TXT250                | ❌               |
 //! | **ABN AMRO**            | Business  | XLX250                | ❌               |
 //! | **ABN AMRO**            | Personal  | CSV                   | ❌               |
 //! | **ABN AMRO**            | Personal  | PDF                   | ❌               |
-//! | **ABN AMRO**            | Personal  | MT940                 | ❌               |
+//! | **ABN AMRO**            | Personal  | MT940                 | ✅               |
 //! | **ABN AMRO**            | Personal  | TXT250                | ❌               |
 //! | **ABN AMRO**            | Personal  | XLX250                | ❌               |
 //! | **Bunq**                | Business  | PDF                   | ❌               |
@@ -122,6 +211,15 @@
 //!
 //! Aurora can be integrated with your existing infrastructure to automatically parse and transform documents. Detailed API documentation and SDK support are available for seamless integration.
 //!
+//! Providers Aurora doesn't ship with can be added without forking it: implement
+//! `ProviderCaster` and register it at startup, before the first document is parsed.
+//! ```ignore
+//! use aurora::parser::caster::caster_registry::register_caster;
+//! use std::sync::Arc;
+//!
+//! register_caster(Arc::new(MyBankCsvCaster));
+//! ```
+//!
 //! ## Environment variables
 //!
 //! To run this project, you will need to add the following environment variables to your .env file
@@ -129,12 +227,20 @@
 //! - `AURORA_SCRIPT_DIR` - Optional, if you are extending Aurora with custom implementations but don't want to alter the source code
 //! - `POSTGRES_CONNECTION_STRING` - any postgres string will work
 //! - `AURORA_API_PORT` - Defaults to 7777, exposes the `Actix-Web` Rest api
+//! - `AURORA_MAX_UPLOAD_BYTES` - Defaults to 26214400 (25 MiB), the maximum size of a single file sent to `/upload` or `/upload/batch`
+//! - `AURORA_CONFIG_PATH` - Defaults to `config.yaml`, the file CSV providers can be declared in without a Rust release (see the example in `config.yaml`)
+//! - `AURORA_ADMIN_TOKEN` - Optional, enables `POST /admin/providers/reload` for requests sending `Authorization: Bearer <token>`, which reloads `config.yaml` without a restart
+//! - `AURORA_TESSDATA_PATH` - Optional, the directory holding the Tesseract trained data (`nld.traineddata`, `eng.traineddata`, ...), defaults to Tesseract's own `TESSDATA_PREFIX`
+//! - `AURORA_PDFIUM_PATH` - Optional, the directory holding the pdfium library (`libpdfium.so`) used to render scanned PDF pages for OCR, defaults to the system library path
 //!
 //! ## Build Aurora (Ubuntu 24.xx)
 //!
 //! ### Leptonica and Tesseract dependencies
 //! On Ubuntu and derivatives the additional dependencies can be installed by running:
 //! ```sudo apt-get install libleptonica-dev libtesseract-dev clang```
+//! 
+//! The trained data of the OCR languages is installed with:
+//! ```sudo apt-get install tesseract-ocr-nld tesseract-ocr-eng tesseract-ocr-deu tesseract-ocr-fra```
 //!
 //! On Fedora 30 the additional dependencies can be installed by running:
 //! ```sudo dnf install leptonica-devel tesseract-devel clang```
@@ -150,7 +256,7 @@
 //! Please install `vcpkg` and set up user wide integration or `vcpkg` crate won't be able to find a library.
 //!
 //! To install tesseract
-//! ```
+//! ```text
 //! REM from the vcpkg directory
 //!
 //! REM 32 bit
@@ -163,24 +269,24 @@
 //! `vcpkg` allows building either dynamically or statically linked application
 //!
 //! if you prefer dynamic linking
-//! ```
+//! ```text
 //! SET VCPKGRS_DYNAMIC=true
 //! ```
 //!
 //! for statically linked libraries
 //!
-//! ```
+//! ```text
 //! SET RUSTFLAGS=-Ctarget-feature=+crt-static
 //! ```
 //! To run the tests please download the English trained data to this directory and set
 //!
-//! ```
+//! ```text
 //! SET TESSDATA_PREFIX=.
 //! ```
 //! If you prefer to compile tesseract yourself (Because, for example, you could not get vcpkg to build using clang-cl.exe), you can set these environment variables: TESSERACT_INCLUDE_PATHS, TESSERACT_LINK_PATHS and TESSERACT_LINK_LIBS.
 //!
 //! For example:
-//! ```
+//! ```text
 //! set TESSERACT_INCLUDE_PATHS=D:\tesseract\build\include
 //! set TESSERACT_LINK_PATHS=D:\tesseract\build\lib
 //! set TESSERACT_LINK_LIBS=tesseract41
@@ -215,22 +321,31 @@
 //! For support, email floris@xylex.ai or submit an issue here on github
 
 pub mod api;
+pub mod cli;
 pub mod config;
 pub mod db;
+pub mod error;
 pub mod foundry;
 pub mod llm;
 pub mod ocr;
 pub mod parser;
 pub mod utils;
 
+use bytes::Bytes;
 use mime_guess::from_path;
 use serde::Deserialize;
 use std::path::Path;
 use tracing::{error, info};
 
 // crate imports
+use crate::parser::builder::process_bytestream;
+use crate::utils::bytestream_helper::read_file_to_bytestream;
 use crate::utils::file_extension::get_file_extension;
 
+pub use crate::error::AuroraError;
+pub use crate::parser::document::{ParsedDocument, Record};
+pub use crate::parser::options::ParseOptions;
+
 #[derive(Deserialize, Debug, Clone)]
 pub struct Parser {
     pub file_url: String,
@@ -260,4 +375,113 @@ impl Parser {
             file_mime,
         }
     }
+
+    /// Creates a `Parser` without a file name or URL, the format and provider are
+    /// then detected from the content alone.
+    pub fn without_hint() -> Self {
+        Parser {
+            file_url: String::new(),
+            file_extension: String::new(),
+            file_mime: "application/octet-stream".to_string(),
+        }
+    }
+
+    /// Parses a document, using the file name or URL of the `Parser` as a format and provider hint.
+    ///
+    /// # Arguments
+    ///
+    /// * `content` - The bytes of the document.
+    /// * `options` - The `ParseOptions` selecting the output shape, formats and provider.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `ParsedDocument`, or the `AuroraError` describing why it couldn't be parsed.
+    pub async fn parse(
+        &self,
+        content: impl Into<Bytes>,
+        options: &ParseOptions,
+    ) -> Result<ParsedDocument, AuroraError> {
+        let content: Bytes = content.into();
+        process_bytestream(&content, self, options).await
+    }
+}
+
+/// Parses a document without a file name, its format is detected from the content alone.
+///
+/// ```no_run
+/// # async fn run() -> Result<(), aurora::AuroraError> {
+/// let csv: Vec<u8> = std::fs::read("statement.csv").unwrap();
+/// let document: aurora::ParsedDocument = aurora::parse(csv, &aurora::ParseOptions::default()).await?;
+/// for transaction in document.transactions() {
+///     println!("{} {}", transaction.amount, transaction.currency);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Arguments
+///
+/// * `content` - The bytes of the document.
+/// * `options` - The `ParseOptions` selecting the output shape, formats and provider.
+///
+/// # Returns
+///
+/// A `Result` containing the `ParsedDocument`, or the `AuroraError` describing why it couldn't be parsed.
+pub async fn parse(
+    content: impl Into<Bytes>,
+    options: &ParseOptions,
+) -> Result<ParsedDocument, AuroraError> {
+    Parser::without_hint().parse(content, options).await
+}
+
+/// Reads a document from a local path or an HTTP(S) URL and parses it.
+///
+/// # Arguments
+///
+/// * `file_path` - The local path or URL of the document, also used as a format and provider hint.
+/// * `options` - The `ParseOptions` selecting the output shape, formats and provider.
+///
+/// # Returns
+///
+/// A `Result` containing the `ParsedDocument`, or the `AuroraError` describing why it couldn't be read or parsed.
+pub async fn parse_file(file_path: &str, options: &ParseOptions) -> Result<ParsedDocument, AuroraError> {
+    let content: Vec<u8> = read_file_to_bytestream(file_path).await.map_err(|e| {
+        if file_path.starts_with("http://") || file_path.starts_with("https://") {
+            AuroraError::Fetch {
+                url: file_path.to_string(),
+                message: e.to_string(),
+            }
+        } else {
+            AuroraError::Storage(format!("Error reading {}: {:#}", file_path, e))
+        }
+    })?;
+    Parser::new(file_path.to_string()).parse(content, options).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const REVOLUT_CSV: &str = "Type,Product,Started Date,Completed Date,Description,Amount,Fee,Currency,State,Balance
+CARD_PAYMENT,Current,2024-01-02 10:00:00,2024-01-02 10:00:00,Albert Heijn,-12.50,0.00,EUR,COMPLETED,87.50
+";
+
+    #[tokio::test]
+    async fn parses_content_without_a_file_name() {
+        let document: ParsedDocument = parse(REVOLUT_CSV.as_bytes().to_vec(), &ParseOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(document.detection.document_provider, "revolut_csv");
+        assert_eq!(document.transactions().count(), 1);
+        assert!(matches!(document.data[0], Record::Transaction(_)));
+    }
+
+    #[test]
+    fn a_parser_without_hint_has_no_extension() {
+        let parser: Parser = Parser::without_hint();
+        assert!(parser.file_url.is_empty());
+        assert!(parser.file_extension.is_empty());
+        assert_eq!(parser.file_mime, "application/octet-stream");
+    }
 }
@@ -4,30 +4,47 @@ use tracing_subscriber::EnvFilter;
 use tokio::runtime::Builder;
 use tokio::task;
 
-use aurora::api::server::api;
+use aurora::cli::{run, Cli, Command};
+use clap::Parser;
+use std::process::exit;
 
 #[tokio::main]
 async fn main() {
-    init_tracing();
-    println!("Hello, world!");
+    let cli: Cli = Cli::parse();
+    let serving: bool = matches!(cli.command, None | Some(Command::Serve));
+    init_tracing(serving);
+    if serving {
+        println!("Hello, world!");
+    }
 
-    if let Err(e) = api().await {
-        eprintln!("Failed to start server: {}", e);
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {:#}", e);
+        exit(1);
     }
 }
 
 /// ## Initialize Tracing
 ///
 /// This function sets up the tracing subscriber for logging and monitoring.
+/// The server logs `info` to stdout, the other commands keep stdout for their
+/// output and only log warnings, to stderr.
 ///
 /// ### Example
 ///
 /// ```
-/// init_tracing();
+/// init_tracing(true);
 /// ```
-fn init_tracing() {
+fn init_tracing(serving: bool) {
+    let default_level: &str = if serving { "info" } else { "warn" };
     let filter: EnvFilter =
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(default_level));
 
-    tracing_subscriber::fmt().with_env_filter(filter).init()
+    if serving {
+        tracing_subscriber::fmt().with_env_filter(filter).init()
+    } else {
+        tracing_subscriber::fmt()
+            .with_env_filter(filter)
+            .with_writer(std::io::stderr)
+            .init()
+    }
 }
@@ -0,0 +1,329 @@
+//! ## OCR
+//!
+//! Photographed receipts and scanned statements carry no text of their own.
+//! This module runs them through Tesseract and returns the text of every page,
+//! along with every word, its bounding box in pixels and its confidence, so the
+//! text can go through provider detection like the text layer of a PDF.
+//!
+//! Images are decoded from PNG, JPEG or TIFF (the first page of a TIFF), and
+//! rasterized PDF pages can be passed in directly. The trained data of the
+//! requested languages has to be installed, see `install_tesseract_ubuntu.sh`;
+//! it is looked up in `AURORA_TESSDATA_PATH`, or Tesseract's `TESSDATA_PREFIX`.
+//!
+//! Images are run through [`preprocess`](mod@preprocess) first, see its documentation for the
+//! steps and how to turn them off.
+//!
+//! ```json
+//! { "ocr": { "languages": ["nld", "eng"], "dpi": 300 } }
+//! ```
+
+pub mod preprocess;
+pub mod tesseract;
+
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+// crate imports
+use crate::config::get_tessdata_path;
+use crate::error::AuroraError;
+use crate::ocr::preprocess::{preprocess, PreprocessOptions, PreprocessStep, Preprocessed};
+use crate::ocr::tesseract::{Recognition, Tesseract};
+
+/// The resolution images are assumed to have, and PDF pages are rendered at.
+pub const DEFAULT_OCR_DPI: u32 = 300;
+
+/// How documents are run through OCR.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct OcrOptions {
+    /// The Tesseract language codes to recognise, e.g. `nld`, `eng`, `deu` or `fra`.
+    #[serde(default = "default_languages")]
+    pub languages: Vec<String>,
+    /// The resolution of the images in dots per inch.
+    #[serde(default = "default_dpi")]
+    pub dpi: u32,
+    /// Which preprocessing steps run before recognition.
+    #[serde(default)]
+    pub preprocess: PreprocessOptions,
+}
+
+impl Default for OcrOptions {
+    fn default() -> OcrOptions {
+        OcrOptions {
+            languages: default_languages(),
+            dpi: default_dpi(),
+            preprocess: PreprocessOptions::default(),
+        }
+    }
+}
+
+impl OcrOptions {
+    /// Checks the languages and the resolutions.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is an `AuroraError::BadRequest` when there are no languages,
+    /// one isn't a language code or a resolution is out of range.
+    pub fn validate(&self) -> Result<(), AuroraError> {
+        if self.languages.is_empty() {
+            return Err(AuroraError::BadRequest("ocr.languages must not be empty".to_string()));
+        }
+        if let Some(language) = self
+            .languages
+            .iter()
+            .find(|language| {
+                language.is_empty() || !language.chars().all(|c| c.is_ascii_lowercase() || c == '_')
+            })
+        {
+            return Err(AuroraError::BadRequest(format!(
+                "'{}' is not a Tesseract language code, e.g. nld, eng, deu or fra",
+                language
+            )));
+        }
+        if !(70..=1200).contains(&self.dpi) {
+            return Err(AuroraError::BadRequest(format!(
+                "ocr.dpi must be between 70 and 1200, got {}",
+                self.dpi
+            )));
+        }
+        self.preprocess.validate()
+    }
+
+    /// Joins the languages the way Tesseract expects them, e.g. `nld+eng`.
+    pub fn tesseract_languages(&self) -> String {
+        self.languages.join("+")
+    }
+}
+
+fn default_languages() -> Vec<String> {
+    vec!["nld".to_string(), "eng".to_string()]
+}
+
+fn default_dpi() -> u32 {
+    DEFAULT_OCR_DPI
+}
+
+/// A rectangle on a page, from its top left to its bottom right corner.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+/// A single recognised word.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OcrWord {
+    /// The text of the word.
+    pub text: String,
+    /// How sure Tesseract is about the word, between `0` and `100`.
+    pub confidence: f32,
+    /// The zero-based text line of the page the word is on.
+    pub line: u32,
+    /// Where the word is on the page, in pixels.
+    pub bbox: BoundingBox,
+}
+
+/// The recognised text of a single image or page.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OcrPage {
+    /// The 1-based page number.
+    pub page_number: u32,
+    /// The recognised text, a line per text line.
+    pub text: String,
+    /// The width of the preprocessed image in pixels.
+    pub width: u32,
+    /// The height of the preprocessed image in pixels.
+    pub height: u32,
+    /// The resolution of the preprocessed image.
+    pub dpi: u32,
+    /// The mean confidence of the words, between `0` and `100`.
+    pub confidence: f32,
+    /// Every recognised word, in reading order, positioned on the preprocessed image.
+    pub words: Vec<OcrWord>,
+    /// The preprocessing steps that changed the image.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub preprocessing: Vec<PreprocessStep>,
+}
+
+/// The preprocessing steps that changed the image of a page.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PagePreprocessing {
+    /// The 1-based page number.
+    pub page_number: u32,
+    /// The steps, in the order they ran.
+    pub steps: Vec<PreprocessStep>,
+}
+
+/// How a document was run through OCR.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct OcrMetadata {
+    /// The engine and its version, e.g. `tesseract 5.3.0`.
+    pub engine: String,
+    /// The languages the pages were recognised in.
+    pub languages: Vec<String>,
+    /// The resolution the pages were recognised at.
+    pub dpi: u32,
+    /// The mean confidence over every page, between `0` and `100`.
+    pub confidence: f32,
+    /// The preprocessing steps that changed the image of every page, pages left as they were are omitted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub preprocessing: Vec<PagePreprocessing>,
+}
+
+impl OcrMetadata {
+    /// Summarises the OCR of some pages.
+    pub fn new(pages: &[OcrPage], options: &OcrOptions) -> OcrMetadata {
+        let confidence: f32 = if pages.is_empty() {
+            0.0
+        } else {
+            pages.iter().map(|page| page.confidence).sum::<f32>() / pages.len() as f32
+        };
+        OcrMetadata {
+            engine: format!("tesseract {}", Tesseract::version()),
+            languages: options.languages.clone(),
+            dpi: options.dpi,
+            confidence,
+            preprocessing: pages
+                .iter()
+                .filter(|page| !page.preprocessing.is_empty())
+                .map(|page| PagePreprocessing {
+                    page_number: page.page_number,
+                    steps: page.preprocessing.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Recognises the text on a PNG, JPEG or TIFF image.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the image.
+/// * `options` - The `OcrOptions` of the request.
+///
+/// # Returns
+///
+/// A `Result` containing the `OcrPage` of the image, or an `AuroraError` when
+/// the image can't be decoded or recognised.
+pub async fn ocr_image(content: &[u8], options: &OcrOptions) -> Result<OcrPage, AuroraError> {
+    let image: DynamicImage = image::load_from_memory(content)
+        .map_err(|e| AuroraError::Ocr(format!("Failed to decode image: {}", e)))?;
+    let mut pages: Vec<OcrPage> = ocr_pages(vec![(1, image)], options).await?;
+    pages
+        .pop()
+        .ok_or_else(|| AuroraError::Ocr("Tesseract returned no page".to_string()))
+}
+
+/// Recognises the text on rendered pages, e.g. the rasterized pages of a scanned PDF.
+///
+/// Recognition is CPU bound and runs on the blocking thread pool, a single
+/// Tesseract engine recognises every page in turn.
+///
+/// # Arguments
+///
+/// * `pages` - The 1-based page numbers and their images.
+/// * `options` - The `OcrOptions` of the request.
+///
+/// # Returns
+///
+/// A `Result` containing an `OcrPage` per image in the same order, or the
+/// `AuroraError` of the first page that couldn't be recognised.
+pub async fn ocr_pages(
+    pages: Vec<(u32, DynamicImage)>,
+    options: &OcrOptions,
+) -> Result<Vec<OcrPage>, AuroraError> {
+    options.validate()?;
+    let languages: String = options.tesseract_languages();
+    let dpi: u32 = options.dpi;
+    let preprocess_options: PreprocessOptions = options.preprocess.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let mut tesseract: Tesseract = Tesseract::new(&languages, get_tessdata_path().as_deref())?;
+        pages
+            .into_iter()
+            .map(|(page_number, image)| {
+                recognize(&mut tesseract, page_number, image, dpi, &preprocess_options)
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| AuroraError::Ocr(format!("OCR task failed: {}", e)))?
+}
+
+/// Preprocesses and recognises a single page with an initialised engine.
+fn recognize(
+    tesseract: &mut Tesseract,
+    page_number: u32,
+    image: DynamicImage,
+    dpi: u32,
+    options: &PreprocessOptions,
+) -> Result<OcrPage, AuroraError> {
+    let preprocessed: Preprocessed = preprocess(image, dpi, options);
+    if !preprocessed.steps.is_empty() {
+        info!("Preprocessed page {}: {:?}", page_number, preprocessed.steps);
+    }
+    let recognition: Recognition = tesseract.recognize(&preprocessed.image, preprocessed.dpi)?;
+    info!(
+        "Recognised {} words on page {} with a mean confidence of {}",
+        recognition.words.len(),
+        page_number,
+        recognition.confidence
+    );
+
+    Ok(OcrPage {
+        page_number,
+        text: recognition.text,
+        width: preprocessed.image.width(),
+        height: preprocessed.image.height(),
+        dpi: preprocessed.dpi,
+        confidence: recognition.confidence,
+        words: recognition.words,
+        preprocessing: preprocessed.steps,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(languages: &[&str], dpi: u32) -> OcrOptions {
+        OcrOptions {
+            languages: languages.iter().map(|language| language.to_string()).collect(),
+            dpi,
+            ..OcrOptions::default()
+        }
+    }
+
+    #[test]
+    fn validates_languages_and_resolution() {
+        assert!(OcrOptions::default().validate().is_ok());
+        assert!(options(&["chi_sim"], 600).validate().is_ok());
+        assert!(matches!(options(&[], 300).validate(), Err(AuroraError::BadRequest(_))));
+        assert!(matches!(options(&["nld+eng"], 300).validate(), Err(AuroraError::BadRequest(_))));
+        assert!(matches!(options(&["../eng"], 300).validate(), Err(AuroraError::BadRequest(_))));
+        assert!(matches!(options(&["eng"], 50).validate(), Err(AuroraError::BadRequest(_))));
+    }
+
+    #[test]
+    fn joins_languages_for_tesseract() {
+        assert_eq!(OcrOptions::default().tesseract_languages(), "nld+eng");
+        assert_eq!(options(&["deu"], 300).tesseract_languages(), "deu");
+    }
+
+    #[test]
+    fn deserializes_partial_options() {
+        let parsed: OcrOptions = serde_json::from_str(r#"{ "dpi": 200 }"#).unwrap();
+        assert_eq!(parsed.languages, vec!["nld", "eng"]);
+        assert_eq!(parsed.dpi, 200);
+    }
+
+    #[tokio::test]
+    async fn content_that_isnt_an_image_is_an_ocr_error() {
+        let result: Result<OcrPage, AuroraError> =
+            ocr_image(b"Date,Amount\n2024-01-31,12.50\n", &OcrOptions::default()).await;
+        assert!(matches!(result, Err(AuroraError::Ocr(_))));
+    }
+}
@@ -0,0 +1,872 @@
+//! ## Image preprocessing
+//!
+//! Phone photos of receipts and skewed scans give Tesseract a hard time. Before
+//! an image is recognised it runs through these steps, in order:
+//!
+//! 1. `grayscale` - drops colour, Tesseract only looks at luminance.
+//! 2. `normalize_dpi` - rescales the image from its `dpi` to the `target_dpi`.
+//! 3. `rotate` - turns pages lying on their side or upside down upright.
+//! 4. `deskew` - straightens pages tilted by up to 10 degrees.
+//! 5. `binarize` - adaptive thresholding against shadows and uneven lighting.
+//! 6. `crop_borders` - removes the dark borders of scanners and blank margins.
+//!
+//! Every step is on by default and can be turned off per request. The steps
+//! that changed the image are reported in the `preprocessing` of the OCR
+//! metadata, so word bounding boxes can be related to the original image.
+//!
+//! ```json
+//! { "ocr": { "preprocess": { "binarize": false, "target_dpi": 300 } } }
+//! ```
+
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GrayImage, ImageBuffer, Luma, Pixel, Rgb};
+use serde::{Deserialize, Serialize};
+
+// crate imports
+use crate::error::AuroraError;
+use crate::ocr::DEFAULT_OCR_DPI;
+
+/// The longest side of the downscaled copy rotation and skew are detected on.
+const ANALYSIS_SIZE: u32 = 1200;
+/// The largest skew `deskew` corrects, in degrees.
+const MAX_SKEW_DEGREES: f32 = 10.0;
+/// Skews below this many degrees are left alone.
+const MIN_SKEW_DEGREES: f32 = 0.1;
+/// The longest side an image is scaled up to by `normalize_dpi`.
+const MAX_NORMALIZED_SIZE: u32 = 10_000;
+
+/// A single preprocessing step, used to turn steps off, e.g. `--ocr-skip deskew`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PreprocessKind {
+    Grayscale,
+    NormalizeDpi,
+    Rotate,
+    Deskew,
+    Binarize,
+    CropBorders,
+}
+
+/// Which preprocessing steps run before OCR.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PreprocessOptions {
+    /// Converts the image to grayscale, colour images are recognised as they are otherwise.
+    #[serde(default = "enabled")]
+    pub grayscale: bool,
+    /// Rescales the image from the `dpi` of the request to `target_dpi`.
+    #[serde(default = "enabled")]
+    pub normalize_dpi: bool,
+    /// Detects pages turned by 90, 180 or 270 degrees and turns them upright.
+    #[serde(default = "enabled")]
+    pub rotate: bool,
+    /// Detects and corrects a skew of up to 10 degrees.
+    #[serde(default = "enabled")]
+    pub deskew: bool,
+    /// Thresholds every pixel against its neighbourhood, this implies grayscale.
+    #[serde(default = "enabled")]
+    pub binarize: bool,
+    /// Crops dark scanner borders and blank margins.
+    #[serde(default = "enabled")]
+    pub crop_borders: bool,
+    /// The resolution `normalize_dpi` rescales to.
+    #[serde(default = "default_target_dpi")]
+    pub target_dpi: u32,
+}
+
+impl Default for PreprocessOptions {
+    fn default() -> PreprocessOptions {
+        PreprocessOptions {
+            grayscale: true,
+            normalize_dpi: true,
+            rotate: true,
+            deskew: true,
+            binarize: true,
+            crop_borders: true,
+            target_dpi: default_target_dpi(),
+        }
+    }
+}
+
+impl PreprocessOptions {
+    /// Returns these options with some steps turned off.
+    ///
+    /// # Arguments
+    ///
+    /// * `kinds` - The steps to turn off.
+    ///
+    /// # Returns
+    ///
+    /// The `PreprocessOptions` without the given steps.
+    pub fn without(mut self, kinds: &[PreprocessKind]) -> PreprocessOptions {
+        for kind in kinds {
+            match kind {
+                PreprocessKind::Grayscale => self.grayscale = false,
+                PreprocessKind::NormalizeDpi => self.normalize_dpi = false,
+                PreprocessKind::Rotate => self.rotate = false,
+                PreprocessKind::Deskew => self.deskew = false,
+                PreprocessKind::Binarize => self.binarize = false,
+                PreprocessKind::CropBorders => self.crop_borders = false,
+            }
+        }
+        self
+    }
+
+    /// Checks the target resolution.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is an `AuroraError::BadRequest` when the target resolution is out of range.
+    pub fn validate(&self) -> Result<(), AuroraError> {
+        if !(70..=1200).contains(&self.target_dpi) {
+            return Err(AuroraError::BadRequest(format!(
+                "ocr.preprocess.target_dpi must be between 70 and 1200, got {}",
+                self.target_dpi
+            )));
+        }
+        Ok(())
+    }
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn default_target_dpi() -> u32 {
+    DEFAULT_OCR_DPI
+}
+
+/// A preprocessing step that changed the image.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum PreprocessStep {
+    /// The colour image was converted to grayscale.
+    Grayscale,
+    /// The image was rescaled from one resolution to another.
+    NormalizeDpi { from: u32, to: u32 },
+    /// The image was turned clockwise by 90, 180 or 270 degrees.
+    Rotate { degrees: u32 },
+    /// The image was turned back by its skew, in degrees, positive when the page was tilted clockwise.
+    Deskew { angle: f32 },
+    /// The image was thresholded against the mean of a window of pixels around every pixel.
+    Binarize { window: u32 },
+    /// The image was cropped to this rectangle, in pixels of the image before cropping.
+    CropBorders {
+        left: u32,
+        top: u32,
+        width: u32,
+        height: u32,
+    },
+}
+
+/// An image ready for OCR.
+#[derive(Debug, Clone)]
+pub struct Preprocessed {
+    /// The preprocessed image, grayscale unless `grayscale` and `binarize` were turned off.
+    pub image: DynamicImage,
+    /// The resolution of the preprocessed image.
+    pub dpi: u32,
+    /// The steps that changed the image, in the order they ran.
+    pub steps: Vec<PreprocessStep>,
+}
+
+/// Runs an image through the enabled preprocessing steps.
+///
+/// # Arguments
+///
+/// * `image` - The decoded image or rendered page.
+/// * `dpi` - The resolution of the image.
+/// * `options` - The `PreprocessOptions` of the request.
+///
+/// # Returns
+///
+/// The `Preprocessed` image, its resolution and the steps that changed it.
+pub fn preprocess(image: DynamicImage, dpi: u32, options: &PreprocessOptions) -> Preprocessed {
+    let mut steps: Vec<PreprocessStep> = vec![];
+    let mut image: DynamicImage = match image {
+        DynamicImage::ImageLuma8(_) => image,
+        image if options.grayscale || options.binarize => {
+            steps.push(PreprocessStep::Grayscale);
+            DynamicImage::ImageLuma8(image.to_luma8())
+        }
+        image => DynamicImage::ImageRgb8(image.to_rgb8()),
+    };
+    let mut dpi: u32 = dpi;
+
+    if options.normalize_dpi && dpi != options.target_dpi {
+        if let Some((resized, resized_dpi)) = normalize_dpi(&image, dpi, options.target_dpi) {
+            steps.push(PreprocessStep::NormalizeDpi {
+                from: dpi,
+                to: resized_dpi,
+            });
+            image = resized;
+            dpi = resized_dpi;
+        }
+    }
+
+    if options.rotate {
+        let degrees: u32 = detect_rotation(&Mask::of(&image));
+        if degrees != 0 {
+            image = match degrees {
+                90 => image.rotate90(),
+                180 => image.rotate180(),
+                _ => image.rotate270(),
+            };
+            steps.push(PreprocessStep::Rotate { degrees });
+        }
+    }
+
+    if options.deskew {
+        if let Some(angle) = detect_skew(&Mask::of(&image)) {
+            image = rotate_about_center(&image, -angle);
+            steps.push(PreprocessStep::Deskew { angle });
+        }
+    }
+
+    if options.binarize {
+        let gray: GrayImage = image.to_luma8();
+        let window: u32 = (gray.width().max(gray.height()) / 16).max(15) | 1;
+        image = DynamicImage::ImageLuma8(binarize(&gray, window));
+        steps.push(PreprocessStep::Binarize { window });
+    }
+
+    if options.crop_borders {
+        if let Some((left, top, width, height)) = content_bounds(&image.to_luma8(), options.binarize) {
+            image = image.crop_imm(left, top, width, height);
+            steps.push(PreprocessStep::CropBorders {
+                left,
+                top,
+                width,
+                height,
+            });
+        }
+    }
+
+    Preprocessed { image, dpi, steps }
+}
+
+/// Rescales an image from one resolution to another.
+///
+/// # Returns
+///
+/// The rescaled image and its resolution, or `None` when the image is within
+/// 10% of the target resolution already.
+fn normalize_dpi(image: &DynamicImage, dpi: u32, target_dpi: u32) -> Option<(DynamicImage, u32)> {
+    let longest: u32 = image.width().max(image.height());
+    let scale: f32 = (target_dpi as f32 / dpi.max(1) as f32).min(MAX_NORMALIZED_SIZE as f32 / longest.max(1) as f32);
+    if (scale - 1.0).abs() < 0.1 {
+        return None;
+    }
+    let width: u32 = ((image.width() as f32 * scale).round() as u32).max(1);
+    let height: u32 = ((image.height() as f32 * scale).round() as u32).max(1);
+    let filter: FilterType = if scale > 1.0 {
+        FilterType::CatmullRom
+    } else {
+        FilterType::Triangle
+    };
+    let resized_dpi: u32 = (dpi as f32 * scale).round() as u32;
+    Some((image.resize_exact(width, height, filter), resized_dpi))
+}
+
+/// The dark pixels of a downscaled copy of an image, used to analyse its layout.
+struct Mask {
+    width: u32,
+    height: u32,
+    dark: Vec<bool>,
+}
+
+impl Mask {
+    /// Downscales an image and separates dark from light pixels with Otsu's threshold.
+    ///
+    /// Rows and columns that are mostly dark, scanner borders and ruling lines,
+    /// are cleared, they'd outweigh the text.
+    fn of(image: &DynamicImage) -> Mask {
+        let gray: GrayImage = image.to_luma8();
+        let longest: u32 = gray.width().max(gray.height()).max(1);
+        let gray: GrayImage = if longest > ANALYSIS_SIZE {
+            let width: u32 = (gray.width() as u64 * ANALYSIS_SIZE as u64 / longest as u64).max(1) as u32;
+            let height: u32 = (gray.height() as u64 * ANALYSIS_SIZE as u64 / longest as u64).max(1) as u32;
+            imageops::resize(&gray, width, height, FilterType::Triangle)
+        } else {
+            gray
+        };
+        let threshold: u8 = otsu_threshold(&gray);
+        let mut mask: Mask = Mask {
+            width: gray.width(),
+            height: gray.height(),
+            dark: gray.pixels().map(|pixel| pixel.0[0] <= threshold).collect(),
+        };
+
+        let border_rows: Vec<bool> = mask
+            .row_profile()
+            .iter()
+            .map(|&count| count as f32 > mask.width as f32 * 0.6)
+            .collect();
+        let border_columns: Vec<bool> = mask
+            .column_profile()
+            .iter()
+            .map(|&count| count as f32 > mask.height as f32 * 0.6)
+            .collect();
+        for y in 0..mask.height {
+            for x in 0..mask.width {
+                if border_rows[y as usize] || border_columns[x as usize] {
+                    mask.dark[(y * mask.width + x) as usize] = false;
+                }
+            }
+        }
+        mask
+    }
+
+    fn is_dark(&self, x: u32, y: u32) -> bool {
+        self.dark[(y * self.width + x) as usize]
+    }
+
+    /// The mask turned clockwise by 90 degrees, like `DynamicImage::rotate90`.
+    fn rotate90(&self) -> Mask {
+        let mut dark: Vec<bool> = Vec::with_capacity(self.dark.len());
+        for y in 0..self.width {
+            for x in 0..self.height {
+                dark.push(self.is_dark(y, self.height - 1 - x));
+            }
+        }
+        Mask {
+            width: self.height,
+            height: self.width,
+            dark,
+        }
+    }
+
+    /// The number of dark pixels on every row.
+    fn row_profile(&self) -> Vec<u32> {
+        (0..self.height)
+            .map(|y| (0..self.width).filter(|&x| self.is_dark(x, y)).count() as u32)
+            .collect()
+    }
+
+    /// The number of dark pixels on every column.
+    fn column_profile(&self) -> Vec<u32> {
+        (0..self.width)
+            .map(|x| (0..self.height).filter(|&y| self.is_dark(x, y)).count() as u32)
+            .collect()
+    }
+
+    /// The best angle to project the dark pixels onto rows at, see [`Projection`].
+    fn projection(&self) -> Option<Projection> {
+        let mut points: Vec<(f32, f32)> = vec![];
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.is_dark(x, y) {
+                    points.push((x as f32, y as f32));
+                }
+            }
+        }
+        if points.len() < 500 {
+            return None;
+        }
+        let stride: usize = (points.len() / 100_000).max(1);
+        let points: Vec<(f32, f32)> = points.into_iter().step_by(stride).collect();
+        let diagonal: f32 = (self.width as f32).hypot(self.height as f32);
+
+        let project = |degrees: f32| -> Projection {
+            let (sin, cos): (f32, f32) = degrees.to_radians().sin_cos();
+            let mut rows: Vec<u32> = vec![0; (diagonal * 2.0) as usize + 2];
+            for &(x, y) in &points {
+                let row: f32 = y * cos - x * sin + diagonal;
+                rows[row.max(0.0) as usize] += 1;
+            }
+            let energy: f64 = rows.iter().map(|&count| (count as f64).powi(2)).sum();
+            Projection { angle: degrees, energy, rows }
+        };
+        let best = |candidates: Vec<f32>| -> Projection {
+            candidates
+                .into_iter()
+                .map(project)
+                .reduce(|best, candidate| if candidate.energy > best.energy { candidate } else { best })
+                .unwrap_or_else(|| project(0.0))
+        };
+
+        let coarse: Projection = best((-40..=40).map(|step| step as f32 * MAX_SKEW_DEGREES / 40.0).collect());
+        Some(best((-5..=5).map(|step| coarse.angle + step as f32 * 0.05).collect()))
+    }
+}
+
+/// The dark pixels of a page projected onto rows along its text lines.
+///
+/// Projected at the angle the lines run at, the rows hold the most ink, i.e.
+/// the sum of squares of the row counts, the `energy`, is the largest.
+struct Projection {
+    /// The angle of the text lines in degrees, positive when the page is tilted clockwise.
+    angle: f32,
+    energy: f64,
+    rows: Vec<u32>,
+}
+
+/// The threshold separating dark from light pixels that maximises the variance between both.
+fn otsu_threshold(image: &GrayImage) -> u8 {
+    let mut histogram: [u64; 256] = [0; 256];
+    for pixel in image.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+    let total: u64 = histogram.iter().sum();
+    let weighted_total: u64 = histogram.iter().enumerate().map(|(value, count)| value as u64 * count).sum();
+
+    let (mut best, mut best_variance): (u8, f64) = (127, 0.0);
+    let (mut dark_count, mut dark_sum): (u64, u64) = (0, 0);
+    for (value, count) in histogram.iter().enumerate() {
+        dark_count += count;
+        dark_sum += value as u64 * count;
+        let light_count: u64 = total - dark_count;
+        if dark_count == 0 || light_count == 0 {
+            continue;
+        }
+        let dark_mean: f64 = dark_sum as f64 / dark_count as f64;
+        let light_mean: f64 = (weighted_total - dark_sum) as f64 / light_count as f64;
+        let variance: f64 = dark_count as f64 * light_count as f64 * (dark_mean - light_mean).powi(2);
+        if variance > best_variance {
+            best = value as u8;
+            best_variance = variance;
+        }
+    }
+    best
+}
+
+/// Detects by how many degrees a page has to be turned clockwise to be upright.
+///
+/// Text lines concentrate the ink of an upright page on few rows, so the page
+/// lies on its side when its columns do so more. Upside down pages are told
+/// apart by their ascenders, Latin script, digits and capitals reach far more
+/// often above the x-height of a line than below it.
+fn detect_rotation(mask: &Mask) -> u32 {
+    let turned: Mask = mask.rotate90();
+    let (upright, sideways): (Projection, Projection) = match (mask.projection(), turned.projection()) {
+        (Some(upright), Some(sideways)) => (upright, sideways),
+        _ => return 0,
+    };
+
+    if sideways.energy > upright.energy * 1.1 {
+        match is_upside_down(&sideways.rows) {
+            Some(true) => 270,
+            _ => 90,
+        }
+    } else {
+        match is_upside_down(&upright.rows) {
+            Some(true) => 180,
+            _ => 0,
+        }
+    }
+}
+
+/// Compares the ink above and below the x-height of every text line.
+///
+/// # Arguments
+///
+/// * `profile` - The number of dark pixels on every row, projected along the text lines.
+///
+/// # Returns
+///
+/// Whether the page is upside down, or `None` when there's too little text to tell.
+fn is_upside_down(profile: &[u32]) -> Option<bool> {
+    let peak: u32 = profile.iter().copied().max().unwrap_or(0);
+    if peak == 0 {
+        return None;
+    }
+
+    let (mut above, mut below): (u64, u64) = (0, 0);
+    let mut y: usize = 0;
+    while y < profile.len() {
+        if profile[y] * 20 < peak {
+            y += 1;
+            continue;
+        }
+        let start: usize = y;
+        while y < profile.len() && profile[y] * 20 >= peak {
+            y += 1;
+        }
+        let line: &[u32] = &profile[start..y];
+        if line.len() < 5 {
+            continue;
+        }
+        let line_peak: u32 = line.iter().copied().max().unwrap_or(0);
+        let core: Vec<usize> = (0..line.len()).filter(|&i| line[i] * 2 >= line_peak).collect();
+        if let (Some(&core_top), Some(&core_bottom)) = (core.first(), core.last()) {
+            above += line[..core_top].iter().map(|&count| count as u64).sum::<u64>();
+            below += line[core_bottom + 1..].iter().map(|&count| count as u64).sum::<u64>();
+        }
+    }
+
+    if above + below < 50 {
+        return None;
+    }
+    Some(below as f64 > above as f64 * 1.3)
+}
+
+/// Detects the skew of the text lines of an upright page.
+///
+/// # Returns
+///
+/// The skew in degrees, positive when the page is tilted clockwise, or `None`
+/// when it's negligible or there's too little text.
+fn detect_skew(mask: &Mask) -> Option<f32> {
+    let angle: f32 = mask.projection()?.angle;
+    if angle.abs() < MIN_SKEW_DEGREES || angle.abs() > MAX_SKEW_DEGREES {
+        return None;
+    }
+    Some((angle * 100.0).round() / 100.0)
+}
+
+/// Turns an image about its center, uncovered corners are filled with white.
+///
+/// # Arguments
+///
+/// * `image` - The grayscale or RGB image.
+/// * `degrees` - The angle to turn by, clockwise when positive.
+fn rotate_about_center(image: &DynamicImage, degrees: f32) -> DynamicImage {
+    match image {
+        DynamicImage::ImageLuma8(gray) => DynamicImage::ImageLuma8(rotate_buffer(gray, degrees, Luma([255]))),
+        image => DynamicImage::ImageRgb8(rotate_buffer(&image.to_rgb8(), degrees, Rgb([255, 255, 255]))),
+    }
+}
+
+/// Turns an image buffer about its center with bilinear interpolation.
+fn rotate_buffer<P: Pixel<Subpixel = u8>>(
+    image: &ImageBuffer<P, Vec<u8>>,
+    degrees: f32,
+    fill: P,
+) -> ImageBuffer<P, Vec<u8>> {
+    let (width, height): (u32, u32) = image.dimensions();
+    let (sin, cos): (f32, f32) = degrees.to_radians().sin_cos();
+    let (center_x, center_y): (f32, f32) = (width as f32 / 2.0, height as f32 / 2.0);
+    let channels: usize = P::CHANNEL_COUNT as usize;
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let (dx, dy): (f32, f32) = (x as f32 + 0.5 - center_x, y as f32 + 0.5 - center_y);
+        let source_x: f32 = cos * dx + sin * dy + center_x - 0.5;
+        let source_y: f32 = -sin * dx + cos * dy + center_y - 0.5;
+        if source_x < 0.0 || source_y < 0.0 || source_x > (width - 1) as f32 || source_y > (height - 1) as f32 {
+            return fill;
+        }
+
+        let (x0, y0): (u32, u32) = (source_x as u32, source_y as u32);
+        let (x1, y1): (u32, u32) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+        let (fx, fy): (f32, f32) = (source_x - x0 as f32, source_y - y0 as f32);
+        let corners: [&[u8]; 4] = [
+            image.get_pixel(x0, y0).channels(),
+            image.get_pixel(x1, y0).channels(),
+            image.get_pixel(x0, y1).channels(),
+            image.get_pixel(x1, y1).channels(),
+        ];
+
+        let mut pixel: P = fill;
+        for (channel, value) in pixel.channels_mut().iter_mut().enumerate().take(channels) {
+            let top: f32 = corners[0][channel] as f32 * (1.0 - fx) + corners[1][channel] as f32 * fx;
+            let bottom: f32 = corners[2][channel] as f32 * (1.0 - fx) + corners[3][channel] as f32 * fx;
+            *value = (top * (1.0 - fy) + bottom * fy).round() as u8;
+        }
+        pixel
+    })
+}
+
+/// Thresholds every pixel against the mean of the window around it (Bradley-Roth).
+///
+/// # Arguments
+///
+/// * `image` - The grayscale image.
+/// * `window` - The width and height of the window in pixels.
+///
+/// # Returns
+///
+/// A black and white image, pixels more than 15% darker than their window are black.
+fn binarize(image: &GrayImage, window: u32) -> GrayImage {
+    let (width, height): (u32, u32) = image.dimensions();
+    let stride: usize = width as usize + 1;
+    let mut integral: Vec<u64> = vec![0; stride * (height as usize + 1)];
+    for y in 0..height as usize {
+        let mut row_sum: u64 = 0;
+        for x in 0..width as usize {
+            row_sum += image.get_pixel(x as u32, y as u32).0[0] as u64;
+            integral[(y + 1) * stride + x + 1] = integral[y * stride + x + 1] + row_sum;
+        }
+    }
+
+    let half: u32 = window / 2;
+    GrayImage::from_fn(width, height, |x, y| {
+        let (x0, y0): (usize, usize) = (x.saturating_sub(half) as usize, y.saturating_sub(half) as usize);
+        let (x1, y1): (usize, usize) = ((x + half + 1).min(width) as usize, (y + half + 1).min(height) as usize);
+        let count: u64 = ((x1 - x0) * (y1 - y0)) as u64;
+        let sum: u64 = integral[y1 * stride + x1] + integral[y0 * stride + x0]
+            - integral[y0 * stride + x1]
+            - integral[y1 * stride + x0];
+        let value: u64 = image.get_pixel(x, y).0[0] as u64;
+        if value * count * 100 <= sum * 85 {
+            Luma([0])
+        } else {
+            Luma([255])
+        }
+    })
+}
+
+/// Finds the part of an image within its dark borders and blank margins.
+///
+/// Rows and columns are trimmed from every edge while they are mostly dark, a
+/// scanner border, or hold no ink at all, a blank margin. A little of the
+/// margin is kept, Tesseract recognises text touching the edge poorly.
+///
+/// # Arguments
+///
+/// * `image` - The grayscale image.
+/// * `binarized` - Whether the image is black and white already.
+///
+/// # Returns
+///
+/// The left, top, width and height of the content, or `None` when there's
+/// nothing to crop or the content would be implausibly small.
+fn content_bounds(image: &GrayImage, binarized: bool) -> Option<(u32, u32, u32, u32)> {
+    let (width, height): (u32, u32) = image.dimensions();
+    if width < 20 || height < 20 {
+        return None;
+    }
+    let threshold: u8 = if binarized { 127 } else { otsu_threshold(image) };
+    let is_dark = |x: u32, y: u32| image.get_pixel(x, y).0[0] <= threshold;
+
+    let rows: Vec<f32> = (0..height)
+        .map(|y| (0..width).filter(|&x| is_dark(x, y)).count() as f32 / width as f32)
+        .collect();
+    let columns: Vec<f32> = (0..width)
+        .map(|x| (0..height).filter(|&y| is_dark(x, y)).count() as f32 / height as f32)
+        .collect();
+    let padding: u32 = (width.min(height) / 100).max(10);
+
+    let (top, bottom): (u32, u32) = trim_edges(&rows, padding)?;
+    let (left, right): (u32, u32) = trim_edges(&columns, padding)?;
+    let (crop_width, crop_height): (u32, u32) = (right - left, bottom - top);
+    if crop_width == width && crop_height == height {
+        return None;
+    }
+    if crop_width * 10 < width || crop_height * 10 < height {
+        return None;
+    }
+    Some((left, top, crop_width, crop_height))
+}
+
+/// Trims border and blank lines from both ends of a profile of dark fractions.
+///
+/// # Returns
+///
+/// The first and one past the last line to keep, or `None` when no line holds content.
+fn trim_edges(profile: &[f32], padding: u32) -> Option<(u32, u32)> {
+    let is_border = |fraction: f32| fraction > 0.6;
+    let is_content = |fraction: f32| !is_border(fraction) && fraction > 0.002;
+
+    let first: usize = profile.iter().position(|&fraction| is_content(fraction))?;
+    let last: usize = profile.iter().rposition(|&fraction| is_content(fraction))?;
+    let start_limit: usize = profile[..first]
+        .iter()
+        .rposition(|&fraction| is_border(fraction))
+        .map_or(0, |border| border + 1);
+    let end_limit: usize = profile[last + 1..]
+        .iter()
+        .position(|&fraction| is_border(fraction))
+        .map_or(profile.len(), |border| last + 1 + border);
+
+    let start: usize = first.saturating_sub(padding as usize).max(start_limit);
+    let end: usize = (last + 1 + padding as usize).min(end_limit);
+    Some((start as u32, end as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Draws a page of text lines: words with an x-height of 12 pixels and an
+    /// ascender every 10 pixels, like Latin script.
+    fn text_page(width: u32, height: u32) -> GrayImage {
+        let mut page: GrayImage = GrayImage::from_pixel(width, height, Luma([255]));
+        let mut baseline: u32 = 80;
+        while baseline + 20 < height - 60 {
+            let mut x: u32 = 60;
+            let mut word: u32 = 0;
+            while x + 80 < width - 60 {
+                let word_width: u32 = 30 + (word * 17) % 50;
+                for dx in 0..word_width {
+                    for dy in 0..12 {
+                        page.put_pixel(x + dx, baseline - dy, Luma([20]));
+                    }
+                    if dx % 10 < 2 {
+                        for dy in 12..20 {
+                            page.put_pixel(x + dx, baseline - dy, Luma([20]));
+                        }
+                    }
+                }
+                x += word_width + 15;
+                word += 1;
+            }
+            baseline += 40;
+        }
+        page
+    }
+
+    fn only(step: PreprocessKind) -> PreprocessOptions {
+        let all: [PreprocessKind; 6] = [
+            PreprocessKind::Grayscale,
+            PreprocessKind::NormalizeDpi,
+            PreprocessKind::Rotate,
+            PreprocessKind::Deskew,
+            PreprocessKind::Binarize,
+            PreprocessKind::CropBorders,
+        ];
+        let others: Vec<PreprocessKind> = all.into_iter().filter(|kind| *kind != step).collect();
+        PreprocessOptions::default().without(&others)
+    }
+
+    #[test]
+    fn reads_partial_options() {
+        let options: PreprocessOptions = serde_json::from_str(r#"{ "binarize": false, "target_dpi": 400 }"#).unwrap();
+        assert!(!options.binarize);
+        assert!(options.deskew);
+        assert_eq!(options.target_dpi, 400);
+        assert!(options.validate().is_ok());
+
+        let options: PreprocessOptions = PreprocessOptions {
+            target_dpi: 2400,
+            ..PreprocessOptions::default()
+        };
+        assert!(matches!(options.validate(), Err(AuroraError::BadRequest(_))));
+    }
+
+    #[test]
+    fn every_step_can_be_turned_off() {
+        let options: PreprocessOptions = only(PreprocessKind::Grayscale).without(&[PreprocessKind::Grayscale]);
+        let image: DynamicImage = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(50, 40, Rgb([200, 10, 10])));
+        let preprocessed: Preprocessed = preprocess(image, 150, &options);
+
+        assert!(preprocessed.steps.is_empty());
+        assert_eq!(preprocessed.dpi, 150);
+        assert!(matches!(preprocessed.image, DynamicImage::ImageRgb8(_)));
+    }
+
+    #[test]
+    fn colour_images_are_converted_to_grayscale() {
+        let image: DynamicImage = DynamicImage::ImageRgb8(ImageBuffer::from_pixel(50, 40, Rgb([200, 10, 10])));
+        let preprocessed: Preprocessed = preprocess(image, 300, &only(PreprocessKind::Grayscale));
+        assert_eq!(preprocessed.steps, vec![PreprocessStep::Grayscale]);
+        assert!(matches!(preprocessed.image, DynamicImage::ImageLuma8(_)));
+
+        let image: DynamicImage = DynamicImage::ImageLuma8(GrayImage::new(50, 40));
+        assert!(preprocess(image, 300, &only(PreprocessKind::Grayscale)).steps.is_empty());
+    }
+
+    #[test]
+    fn rescales_to_the_target_resolution() {
+        let image: DynamicImage = DynamicImage::ImageLuma8(GrayImage::new(200, 100));
+        let preprocessed: Preprocessed = preprocess(image, 150, &only(PreprocessKind::NormalizeDpi));
+        assert_eq!(preprocessed.steps, vec![PreprocessStep::NormalizeDpi { from: 150, to: 300 }]);
+        assert_eq!(preprocessed.dpi, 300);
+        assert_eq!((preprocessed.image.width(), preprocessed.image.height()), (400, 200));
+
+        let image: DynamicImage = DynamicImage::ImageLuma8(GrayImage::new(200, 100));
+        assert!(normalize_dpi(&image, 290, 300).is_none());
+        let (huge, dpi) = normalize_dpi(&DynamicImage::ImageLuma8(GrayImage::new(5000, 100)), 72, 300).unwrap();
+        assert_eq!(huge.width(), MAX_NORMALIZED_SIZE);
+        assert_eq!(dpi, 144);
+    }
+
+    #[test]
+    fn otsu_separates_ink_from_paper() {
+        let mut image: GrayImage = GrayImage::from_pixel(20, 20, Luma([230]));
+        for x in 0..20 {
+            for y in 0..5 {
+                image.put_pixel(x, y, Luma([30]));
+            }
+        }
+        let threshold: u8 = otsu_threshold(&image);
+        assert!((30..230).contains(&threshold));
+    }
+
+    #[test]
+    fn binarizes_text_on_a_shadowed_page() {
+        // paper going from white to a dark shadow, with ink a little darker than the paper around it
+        let mut image: GrayImage = GrayImage::from_fn(200, 60, |x, _| Luma([250 - (x as u8 / 2)]));
+        for x in (10..190).step_by(20) {
+            for y in 25..35 {
+                let paper: u8 = image.get_pixel(x, y).0[0];
+                image.put_pixel(x, y, Luma([paper / 2]));
+            }
+        }
+        let binarized: GrayImage = binarize(&image, 15);
+
+        assert_eq!(binarized.get_pixel(10, 30).0[0], 0);
+        assert_eq!(binarized.get_pixel(170, 30).0[0], 0);
+        assert_eq!(binarized.get_pixel(15, 30).0[0], 255);
+        assert_eq!(binarized.get_pixel(175, 30).0[0], 255);
+    }
+
+    #[test]
+    fn crops_blank_margins() {
+        let page: GrayImage = text_page(600, 400);
+        let ink: Vec<(u32, u32)> = page
+            .enumerate_pixels()
+            .filter(|(_, _, pixel)| pixel.0[0] < 128)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        let left: u32 = ink.iter().map(|(x, _)| *x).min().unwrap();
+        let right: u32 = ink.iter().map(|(x, _)| *x).max().unwrap();
+        let top: u32 = ink.iter().map(|(_, y)| *y).min().unwrap();
+        let bottom: u32 = ink.iter().map(|(_, y)| *y).max().unwrap();
+
+        // 10 pixels of margin are kept around the ink
+        assert_eq!(
+            content_bounds(&page, false),
+            Some((left - 10, top - 10, right - left + 21, bottom - top + 21))
+        );
+    }
+
+    #[test]
+    fn crops_scanner_borders() {
+        let mut page: GrayImage = text_page(600, 400);
+        for (x, y) in page.clone().enumerate_pixels().map(|(x, y, _)| (x, y)) {
+            if !(15..585).contains(&x) || !(15..385).contains(&y) {
+                page.put_pixel(x, y, Luma([0]));
+            }
+        }
+        let (left, top, width, height) = content_bounds(&page, false).unwrap();
+
+        assert_eq!((left, top), (15, 15));
+        assert_eq!((left + width, top + height), (585, 385));
+
+        assert_eq!(trim_edges(&[0.0, 0.9, 0.0, 0.1, 0.2, 0.0, 0.0], 1), Some((2, 6)));
+        assert_eq!(trim_edges(&[0.0, 0.0], 1), None);
+    }
+
+    #[test]
+    fn straightens_a_skewed_page() {
+        let page: DynamicImage = DynamicImage::ImageLuma8(text_page(800, 1000));
+        assert_eq!(detect_skew(&Mask::of(&page)), None);
+
+        let tilted: DynamicImage = rotate_about_center(&page, 3.0);
+        let angle: f32 = detect_skew(&Mask::of(&tilted)).unwrap();
+        assert!((angle - 3.0).abs() < 0.3, "angle {}", angle);
+
+        let preprocessed: Preprocessed = preprocess(tilted, 300, &only(PreprocessKind::Deskew));
+        assert!(matches!(preprocessed.steps[..], [PreprocessStep::Deskew { .. }]));
+        assert_eq!(detect_skew(&Mask::of(&preprocessed.image)), None);
+    }
+
+    #[test]
+    fn turns_pages_upright() {
+        let page: DynamicImage = DynamicImage::ImageLuma8(text_page(800, 1000));
+        assert_eq!(detect_rotation(&Mask::of(&page)), 0);
+        assert_eq!(detect_rotation(&Mask::of(&page.rotate180())), 180);
+        // a page turned counterclockwise has to be turned clockwise by 90 degrees
+        assert_eq!(detect_rotation(&Mask::of(&page.rotate270())), 90);
+        assert_eq!(detect_rotation(&Mask::of(&page.rotate90())), 270);
+
+        let preprocessed: Preprocessed = preprocess(page.rotate180(), 300, &only(PreprocessKind::Rotate));
+        assert_eq!(preprocessed.steps, vec![PreprocessStep::Rotate { degrees: 180 }]);
+    }
+
+    #[test]
+    fn blank_pages_are_left_alone() {
+        let page: DynamicImage = DynamicImage::ImageLuma8(GrayImage::from_pixel(400, 300, Luma([255])));
+        let options: PreprocessOptions = PreprocessOptions::default().without(&[PreprocessKind::Binarize]);
+        assert!(preprocess(page, 300, &options).steps.is_empty());
+    }
+}
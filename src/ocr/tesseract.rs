@@ -0,0 +1,256 @@
+//! ## Tesseract bindings
+//!
+//! A small safe wrapper around the C API of `tesseract-sys`. A [`Tesseract`]
+//! owns a single `TessBaseAPI` and is meant to live on one thread: create it
+//! inside `spawn_blocking` and reuse it for every page of that task, as
+//! `ocr_pages` does, loading the trained data once per document.
+
+use image::DynamicImage;
+use std::borrow::Cow;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+use tesseract_sys::{
+    TessBaseAPI, TessBaseAPICreate, TessBaseAPIDelete, TessBaseAPIEnd, TessBaseAPIGetIterator,
+    TessBaseAPIGetUTF8Text, TessBaseAPIInit3, TessBaseAPIMeanTextConf, TessBaseAPIRecognize,
+    TessBaseAPISetImage, TessBaseAPISetPageSegMode, TessBaseAPISetSourceResolution,
+    TessDeleteText, TessPageIterator, TessPageIteratorBoundingBox, TessPageIteratorIsAtBeginningOf,
+    TessPageIteratorLevel_RIL_TEXTLINE, TessPageIteratorLevel_RIL_WORD, TessPageSegMode_PSM_AUTO,
+    TessResultIterator, TessResultIteratorConfidence, TessResultIteratorDelete,
+    TessResultIteratorGetPageIteratorConst, TessResultIteratorGetUTF8Text, TessResultIteratorNext,
+    TessVersion,
+};
+
+// crate imports
+use crate::error::AuroraError;
+use crate::ocr::{BoundingBox, OcrWord};
+
+/// The text, words and mean confidence Tesseract recognised on an image.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recognition {
+    /// The text of the image, a line per recognised text line.
+    pub text: String,
+    /// Every recognised word, in reading order.
+    pub words: Vec<OcrWord>,
+    /// The mean confidence of the words between `0` and `100`.
+    pub confidence: f32,
+}
+
+/// An initialised Tesseract engine.
+pub struct Tesseract {
+    handle: *mut TessBaseAPI,
+}
+
+impl Tesseract {
+    /// Initialises Tesseract with the trained data of some languages.
+    ///
+    /// # Arguments
+    ///
+    /// * `languages` - The Tesseract language codes joined by `+`, e.g. `nld+eng`.
+    /// * `datapath` - The directory holding the `tessdata`, `TESSDATA_PREFIX` when `None`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the engine, or an `AuroraError::Ocr` when the trained data can't be loaded.
+    pub fn new(languages: &str, datapath: Option<&str>) -> Result<Tesseract, AuroraError> {
+        let c_languages: CString = CString::new(languages)
+            .map_err(|_| AuroraError::Ocr(format!("Invalid OCR languages '{}'", languages)))?;
+        let c_datapath: Option<CString> = datapath
+            .map(CString::new)
+            .transpose()
+            .map_err(|_| AuroraError::Ocr("Invalid tessdata path".to_string()))?;
+
+        // SAFETY: the handle is checked for null and owned by the returned value
+        let handle: *mut TessBaseAPI = unsafe { TessBaseAPICreate() };
+        if handle.is_null() {
+            return Err(AuroraError::Ocr("Failed to create Tesseract engine".to_string()));
+        }
+        let tesseract: Tesseract = Tesseract { handle };
+
+        let datapath_ptr: *const c_char = c_datapath.as_ref().map_or(ptr::null(), |path| path.as_ptr());
+        // SAFETY: both strings outlive the call, Tesseract copies what it keeps
+        let status: c_int =
+            unsafe { TessBaseAPIInit3(tesseract.handle, datapath_ptr, c_languages.as_ptr()) };
+        if status != 0 {
+            return Err(AuroraError::Ocr(format!(
+                "Failed to load the Tesseract trained data for '{}', is it installed?",
+                languages
+            )));
+        }
+        // SAFETY: the handle is initialised
+        unsafe { TessBaseAPISetPageSegMode(tesseract.handle, TessPageSegMode_PSM_AUTO) };
+        Ok(tesseract)
+    }
+
+    /// Returns the version of the linked Tesseract library, e.g. `5.3.0`.
+    pub fn version() -> String {
+        // SAFETY: Tesseract returns a static string
+        unsafe {
+            let version: *const c_char = TessVersion();
+            if version.is_null() {
+                return String::new();
+            }
+            CStr::from_ptr(version).to_string_lossy().into_owned()
+        }
+    }
+
+    /// Recognises the text on an image.
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - The image, 8-bit grayscale images are passed as they are, others as 8-bit RGB.
+    /// * `dpi` - The resolution of the image, Tesseract sizes its expectations of glyphs on it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the `Recognition`, or an `AuroraError::Ocr` when recognition fails.
+    pub fn recognize(&mut self, image: &DynamicImage, dpi: u32) -> Result<Recognition, AuroraError> {
+        let (width, height): (u32, u32) = (image.width(), image.height());
+        if width == 0 || height == 0 {
+            return Ok(Recognition::default());
+        }
+        let (pixels, bytes_per_pixel): (Cow<[u8]>, u32) = match image {
+            DynamicImage::ImageLuma8(gray) => (Cow::Borrowed(gray.as_raw().as_slice()), 1),
+            image => (Cow::Owned(image.to_rgb8().into_raw()), 3),
+        };
+        let bytes_per_line: c_int = to_int(row_stride(width, bytes_per_pixel)?)?;
+
+        // SAFETY: the buffer holds `width * height * bytes_per_pixel` bytes, and Tesseract copies it
+        unsafe {
+            TessBaseAPISetImage(
+                self.handle,
+                pixels.as_ptr(),
+                to_int(width)?,
+                to_int(height)?,
+                to_int(bytes_per_pixel)?,
+                bytes_per_line,
+            );
+            TessBaseAPISetSourceResolution(self.handle, to_int(dpi)?);
+            if TessBaseAPIRecognize(self.handle, ptr::null_mut()) != 0 {
+                return Err(AuroraError::Ocr("Tesseract failed to recognise the image".to_string()));
+            }
+        }
+
+        // SAFETY: recognition succeeded, so the results can be read
+        let text: String = unsafe { take_text(TessBaseAPIGetUTF8Text(self.handle)) }.unwrap_or_default();
+        let confidence: f32 = unsafe { TessBaseAPIMeanTextConf(self.handle) } as f32;
+        let words: Vec<OcrWord> = unsafe { self.words() };
+
+        Ok(Recognition {
+            text,
+            words,
+            confidence,
+        })
+    }
+
+    /// Walks the words of the last recognition.
+    ///
+    /// # Safety
+    ///
+    /// `recognize` must have succeeded on this engine.
+    unsafe fn words(&mut self) -> Vec<OcrWord> {
+        let mut words: Vec<OcrWord> = vec![];
+        let iterator: *mut TessResultIterator = TessBaseAPIGetIterator(self.handle);
+        if iterator.is_null() {
+            return words;
+        }
+        let page_iterator: *const TessPageIterator = TessResultIteratorGetPageIteratorConst(iterator);
+
+        let mut line: u32 = 0;
+        loop {
+            if !words.is_empty()
+                && TessPageIteratorIsAtBeginningOf(page_iterator, TessPageIteratorLevel_RIL_TEXTLINE) != 0
+            {
+                line += 1;
+            }
+
+            let text: Option<String> =
+                take_text(TessResultIteratorGetUTF8Text(iterator, TessPageIteratorLevel_RIL_WORD));
+            let (mut left, mut top, mut right, mut bottom): (c_int, c_int, c_int, c_int) = (0, 0, 0, 0);
+            let has_box: bool = TessPageIteratorBoundingBox(
+                page_iterator,
+                TessPageIteratorLevel_RIL_WORD,
+                &mut left,
+                &mut top,
+                &mut right,
+                &mut bottom,
+            ) != 0;
+
+            if let (Some(text), true) = (text, has_box) {
+                if !text.trim().is_empty() {
+                    words.push(OcrWord {
+                        text: text.trim().to_string(),
+                        confidence: TessResultIteratorConfidence(iterator, TessPageIteratorLevel_RIL_WORD),
+                        line,
+                        bbox: BoundingBox {
+                            left: left as f32,
+                            top: top as f32,
+                            right: right as f32,
+                            bottom: bottom as f32,
+                        },
+                    });
+                }
+            }
+
+            if TessResultIteratorNext(iterator, TessPageIteratorLevel_RIL_WORD) == 0 {
+                break;
+            }
+        }
+
+        TessResultIteratorDelete(iterator);
+        words
+    }
+}
+
+impl Drop for Tesseract {
+    fn drop(&mut self) {
+        // SAFETY: the handle was created by `TessBaseAPICreate` and is dropped once
+        unsafe {
+            TessBaseAPIEnd(self.handle);
+            TessBaseAPIDelete(self.handle);
+        }
+    }
+}
+
+/// Converts a dimension of an image to the `int` Tesseract takes.
+fn to_int(value: u32) -> Result<c_int, AuroraError> {
+    c_int::try_from(value).map_err(|_| AuroraError::Ocr("Image is too large for OCR".to_string()))
+}
+
+/// Returns the number of bytes in a line of pixels.
+///
+/// # Returns
+///
+/// A `Result` containing the stride, or an `AuroraError::Ocr` when it doesn't fit in a `u32`.
+fn row_stride(width: u32, bytes_per_pixel: u32) -> Result<u32, AuroraError> {
+    width
+        .checked_mul(bytes_per_pixel)
+        .ok_or_else(|| AuroraError::Ocr("Image is too wide for OCR".to_string()))
+}
+
+/// Copies a string returned by Tesseract and frees it.
+///
+/// # Safety
+///
+/// `text` must be null or a string allocated by Tesseract that isn't used afterwards.
+unsafe fn take_text(text: *mut c_char) -> Option<String> {
+    if text.is_null() {
+        return None;
+    }
+    let owned: String = CStr::from_ptr(text).to_string_lossy().into_owned();
+    TessDeleteText(text);
+    Some(owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_stride_of_an_image_that_is_too_wide_is_an_error() {
+        assert_eq!(row_stride(2480, 3).unwrap(), 7440);
+        assert_eq!(row_stride(2480, 1).unwrap(), 2480);
+        assert!(matches!(row_stride(u32::MAX / 2, 3), Err(AuroraError::Ocr(_))));
+        assert!(matches!(to_int(u32::MAX), Err(AuroraError::Ocr(_))));
+    }
+}
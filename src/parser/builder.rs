@@ -2,20 +2,33 @@
 //! The parser will determine the correct parsing engine based on EXIF and MIME types.
 //!
 
-use actix_web::{web::Bytes, HttpResponse};
-use serde_json::Value;
-use std::io::Cursor;
+use actix_web::{web::Bytes, HttpResponse, ResponseError};
+use serde_json::{json, Value};
+use std::borrow::Cow;
+use std::sync::Arc;
 use tracing::info;
 
 // crate imports
-use crate::parser::caster::caster_registry::cast_transactions;
-use crate::parser::csv::convert_csv_reader_to_json;
+use crate::error::AuroraError;
+use crate::ocr::{ocr_image, OcrMetadata, OcrPage};
+use crate::parser::format::{detect_format, DocumentFormat};
+use crate::parser::document::{DocumentMetadata, ParsedDocument};
+use crate::parser::options::{ParseOptions, PdfExtraction};
+use crate::{parse_file, Parser};
+use crate::parser::caster::caster_registry::{cast_transactions, registry, CasterRegistry};
+use crate::parser::caster::diagnostic::{Diagnostic, Diagnostics};
+use crate::parser::csv::encoding::{decode_text, TextEncoding};
+use crate::parser::csv::{convert_csv_with_dialect, CsvDialect, CsvMetadata};
+use crate::parser::schema::detection::Detection;
 use crate::parser::schema::determine_document_provider;
-use crate::parser::schema::RevolutPersonalSchema;
-use crate::utils::bytestream_helper::read_file_to_bytestream;
+use crate::parser::spreadsheet::read_spreadsheet;
 
 // pdf 
-use crate::parser::pdf::output_doc;
+use crate::parser::pdf::table::{extract_table, Table};
+use crate::parser::pdf::{read_pdf, PdfText};
+
+// mt940
+use crate::parser::mt940::{parse_mt940, statements_to_json, Mt940Statement};
 
 
 /// Processes a JSON value by first determining its document provider and then passing it to the appropriate handler.
@@ -23,103 +36,381 @@ use crate::parser::pdf::output_doc;
 /// # Arguments
 ///
 /// * `json_value` - A mutable reference to a `serde_json::Value` which is expected to be a JSON object.
-/// * `schemas` - A slice of `RevolutPersonalSchema` representing the available struct schemas to match against.
+/// * `file_name` - The file name or URL of the document, used as a detection hint.
+/// * `options` - The `ParseOptions` of the request.
+/// * `registry` - The `CasterRegistry` snapshot the document is parsed with.
 ///
 /// # Returns
 ///
-/// A `Value` representing the processed JSON object.
+/// A `Result` containing the processed JSON object, the `Detection` it is based on and the
+/// `Diagnostics` collected while casting, or an `AuroraError::Detection` when the requested
+/// provider doesn't exist.
 pub async fn process_json_value(
     json_value: &mut Value,
-    schemas: &[RevolutPersonalSchema],
-) -> Value {
+    file_name: Option<&str>,
+    options: &ParseOptions,
+    registry: &CasterRegistry,
+) -> Result<(Value, Detection, Diagnostics), AuroraError> {
     info!("Processing JSON value: {:#?}", json_value);
     // First, determine the document provider
-    let mut processed_value: Value = determine_document_provider(json_value, schemas);
+    let (mut processed_value, detection): (Value, Detection) =
+        determine_document_provider(json_value, file_name, options.provider.as_deref(), registry)
+            .map_err(AuroraError::Detection)?;
 
     info!("processed_values; {:#?}", processed_value);
     // here we wanna enter the pipeline for the casting registry
 
-    let casted_data: Result<Value, String> = cast_transactions(&mut processed_value).await;
+    let (final_value, diagnostics): (Value, Diagnostics) =
+        cast_transactions(&mut processed_value, options, registry).await;
+    info!(
+        "Casting finished with {} warnings and {} errors",
+        diagnostics.warnings.len(),
+        diagnostics.errors.len()
+    );
 
-    // handle
-    let final_value: Value = match casted_data {
-        Ok(value) => value,
-        Err(e) => {
-            // Log the error and return the original processed value
-            info!("Error during casting transactions: {}", e);
-            processed_value
-        }
-    };
-
-    // Here you can add additional processing logic if needed
-    // For now, we simply return the processed value
-    final_value
+    Ok((final_value, detection, diagnostics))
 }
 
-/// Handles the processing of a file given its path.
+/// Handles the processing of a file given its path, the HTTP adapter of [`crate::parse_file`].
 ///
 /// # Arguments
 ///
-/// * `file_path` - A string slice that holds the path to the file.
+/// * `file_path` - A string slice that holds the path or URL of the file.
+/// * `options` - The `ParseOptions` of the request.
 ///
 /// # Returns
 ///
 /// An `HttpResponse` indicating the result of the operation.
-pub async fn handle_file_path(file_path: &str) -> HttpResponse {
-    match read_file_to_bytestream(file_path).await {
-        Ok(bytestream) => {
-            let reader: Cursor<Vec<u8>> = Cursor::new(bytestream);
-
-            match convert_csv_reader_to_json(reader).await {
-                Ok(json_result) => HttpResponse::Ok().json(json_result),
-                Err(e) => HttpResponse::InternalServerError()
-                    .body(format!("Error converting CSV to JSON: {}", e)),
-            }
-        }
-        Err(e) => HttpResponse::InternalServerError()
-            .body(format!("Error reading file to bytestream: {}", e)),
+pub async fn handle_file_path(file_path: &str, options: &ParseOptions) -> HttpResponse {
+    match parse_file(file_path, options).await {
+        Ok(document) => HttpResponse::Ok().json(document),
+        Err(e) => e.error_response(),
     }
 }
 
-use std::io::Read; // Import the Read trait for read_exact method
-
-/// Handles the processing of a bytestream.
+/// Handles the processing of a bytestream, the HTTP adapter of [`Parser::parse`].
 ///
 /// # Arguments
 ///
 /// * `content` - A reference to the bytes of the content.
+/// * `parser` - The `Parser` carrying the file extension and MIME type hints.
+/// * `options` - The `ParseOptions` of the request.
 ///
 /// # Returns
 ///
 /// An `HttpResponse` indicating the result of the operation.
-pub async fn handle_bytestream(content: &Bytes) -> HttpResponse {
-    let mut reader = Cursor::new(content.clone());
+pub async fn handle_bytestream(
+    content: &Bytes,
+    parser: &Parser,
+    options: &ParseOptions,
+) -> HttpResponse {
+    match parser.parse(content.clone(), options).await {
+        Ok(document) => HttpResponse::Ok().json(document),
+        Err(e) => e.error_response(),
+    }
+}
+
+/// Processes a bytestream into a `ParsedDocument`.
+///
+/// The format of the content is detected first and the bytes are then
+/// dispatched to the matching parsing engine. The detected format, the
+/// provider `Detection` and the casting `warnings` and `errors` are returned
+/// alongside the parsed data. In strict mode any casting error fails the
+/// document with `AuroraError::Cast`.
+///
+/// # Arguments
+///
+/// * `content` - A reference to the bytes of the content.
+/// * `parser` - The `Parser` carrying the file extension and MIME type hints.
+/// * `options` - The `ParseOptions` of the request.
+///
+/// # Returns
+///
+/// A `Result` containing the `ParsedDocument`, or the `AuroraError` describing why it couldn't be parsed.
+pub async fn process_bytestream(
+    content: &Bytes,
+    parser: &Parser,
+    options: &ParseOptions,
+) -> Result<ParsedDocument, AuroraError> {
+    // a single snapshot, so a reload can't change the providers halfway through a document
+    let registry: Arc<CasterRegistry> = registry();
+    let document_format: DocumentFormat = detect_format(content, parser);
+    info!("Handling bytestream as {}", document_format);
+    let (mut json_result, metadata, engine_warnings): (Value, DocumentMetadata, Vec<Diagnostic>) =
+        read_document(content, parser, document_format, options, &registry).await?;
 
-    // Determine if the content is a CSV by checking the first few bytes for a CSV header
-    // let is_csv: bool = {
-    //     let mut buf: [u8; 4] = [0; 4];
-    //     reader.read_exact(&mut buf).is_ok() && buf.starts_with(b"\"") // Simple check for CSV
-    // };
+    let (processed_value, detection, mut diagnostics): (Value, Detection, Diagnostics) =
+        process_json_value(&mut json_result, Some(&parser.file_url), options, &registry).await?;
+    diagnostics.warnings.extend(engine_warnings);
 
-    let is_csv: bool = true; // For testing purposes, we assume it's a CSV
+    if options.strict && !diagnostics.errors.is_empty() {
+        return Err(AuroraError::Cast(diagnostics.errors));
+    }
+
+    Ok(ParsedDocument::new(
+        document_format,
+        detection,
+        processed_value,
+        diagnostics,
+        metadata,
+        options,
+    ))
+}
 
-    if is_csv {
-        match convert_csv_reader_to_json(reader).await {
-            Ok(mut json_result) => {
-                // Assuming `schemas` is available in the context or passed as an argument
-                let schemas: Vec<RevolutPersonalSchema> = vec![]; // Replace with actual schemas
-                let processed_value: Value = process_json_value(&mut json_result, &schemas).await;
+/// Reads a document with the parsing engine of its format, without casting it.
+///
+/// # Arguments
+///
+/// * `content` - A reference to the bytes of the content.
+/// * `parser` - The `Parser` carrying the file name used as a detection hint.
+/// * `document_format` - The `DocumentFormat` the content was detected as.
+/// * `options` - The `ParseOptions` of the request.
+/// * `registry` - The `CasterRegistry` snapshot the document is parsed with.
+///
+/// # Returns
+///
+/// A `Result` containing the parsed rows, the `DocumentMetadata` of the engine
+/// and its warnings, or the `AuroraError` describing why the document couldn't be read.
+pub async fn read_document(
+    content: &Bytes,
+    parser: &Parser,
+    document_format: DocumentFormat,
+    options: &ParseOptions,
+    registry: &CasterRegistry,
+) -> Result<(Value, DocumentMetadata, Vec<Diagnostic>), AuroraError> {
+    let mut metadata: DocumentMetadata = DocumentMetadata::default();
+    let mut engine_warnings: Vec<Diagnostic> = vec![];
 
-                HttpResponse::Ok().json(processed_value)
+    let rows: Value = match document_format {
+        DocumentFormat::Csv => {
+            let (rows, csv_metadata): (Value, CsvMetadata) =
+                parse_csv(content, parser, options, registry).await?;
+            metadata.encoding = Some(csv_metadata.encoding);
+            metadata.dialect = Some(csv_metadata.layout);
+            metadata.preamble = csv_metadata.preamble;
+            rows
+        }
+        DocumentFormat::Pdf => {
+            let pdf: PdfText = read_pdf(content, options.pdf_extraction, &options.ocr).await?;
+            metadata.ocr = pdf.ocr;
+            engine_warnings = pdf.warnings.into_iter().map(Diagnostic::document).collect();
+            if options.pdf_extraction != PdfExtraction::Table {
+                json!(pdf.pages)
+            } else {
+                let table: Option<Table> = extract_table(&pdf.pages);
+                match table {
+                    Some(table) => {
+                        metadata.table = Some(table.metadata);
+                        json!(table.rows)
+                    }
+                    None => {
+                        engine_warnings.push(Diagnostic::document(
+                            "No transaction table found, the pages are returned as text",
+                        ));
+                        json!(pdf.pages)
+                    }
+                }
             }
-            Err(e) => {
-                HttpResponse::InternalServerError().body(format!("Error converting CSV to JSON: {}", e))
+        }
+        format if format.is_image() => {
+            let page: OcrPage = ocr_image(content, &options.ocr).await?;
+            let pages: Vec<OcrPage> = vec![page];
+            metadata.ocr = Some(OcrMetadata::new(&pages, &options.ocr));
+            json!(pages)
+        }
+        DocumentFormat::Mt940 => {
+            // banks still export MT940 in Windows-1252 or ISO-8859-1, not UTF-8
+            let to_error = |e: anyhow::Error| AuroraError::Parse {
+                format: document_format.to_string(),
+                message: format!("{:#}", e),
+            };
+            let (text, encoding): (Cow<str>, TextEncoding) =
+                decode_text(content, None).map_err(to_error)?;
+            metadata.encoding = Some(encoding);
+            let statements: Vec<Mt940Statement> =
+                parse_mt940(&text).map_err(|e| AuroraError::Parse {
+                    format: document_format.to_string(),
+                    message: e.to_string(),
+                })?;
+            let (rows, warnings): (Value, Vec<Diagnostic>) = statements_to_json(&statements);
+            engine_warnings = warnings;
+            rows
+        }
+        format if format.is_spreadsheet() => {
+            read_spreadsheet(content).map_err(|e| AuroraError::Parse {
+                format: document_format.to_string(),
+                message: format!("{:#}", e),
+            })?
+        }
+        DocumentFormat::Xml => {
+            return Err(AuroraError::UnsupportedFormat {
+                format: document_format,
+                message: "XML documents, e.g. CAMT.053 statements, aren't supported".to_string(),
+            })
+        }
+        _ => {
+            return Err(AuroraError::UnsupportedFormat {
+                format: document_format,
+                message: "Unable to determine the document format".to_string(),
+            })
+        }
+    };
+    Ok((rows, metadata, engine_warnings))
+}
+
+/// Parses a CSV document with the dialect of its provider.
+///
+/// A forced provider's dialect is used straight away. Otherwise the document
+/// is read with a sniffed delimiter as UTF-8 first, and read again when the
+/// detected provider declares a different dialect, e.g. a Windows-1252 encoding.
+///
+/// # Arguments
+///
+/// * `content` - A reference to the bytes of the content.
+/// * `parser` - The `Parser` carrying the file name used as a detection hint.
+/// * `options` - The `ParseOptions` of the request.
+/// * `registry` - The `CasterRegistry` snapshot the document is parsed with.
+///
+/// # Returns
+///
+/// A `Result` containing the parsed rows and the `CsvMetadata` they were read with,
+/// or the `AuroraError` describing why they couldn't be read.
+async fn parse_csv(
+    content: &Bytes,
+    parser: &Parser,
+    options: &ParseOptions,
+    registry: &CasterRegistry,
+) -> Result<(Value, CsvMetadata), AuroraError> {
+    let to_error = |e: anyhow::Error| AuroraError::Parse {
+        format: DocumentFormat::Csv.to_string(),
+        message: e.to_string(),
+    };
+
+    if let Some(dialect) = options
+        .provider
+        .as_deref()
+        .and_then(|provider| registry.get(provider))
+        .and_then(|caster| caster.csv_dialect())
+    {
+        return convert_csv_with_dialect(content, &dialect).await.map_err(to_error);
+    }
+
+    let sniffed: CsvDialect = CsvDialect::default();
+    let (rows, csv_metadata): (Value, CsvMetadata) =
+        convert_csv_with_dialect(content, &sniffed).await.map_err(to_error)?;
+
+    let (_, detection): (Value, Detection) =
+        determine_document_provider(&rows, Some(&parser.file_url), None, registry)
+            .map_err(AuroraError::Detection)?;
+    match registry
+        .get(&detection.document_provider)
+        .and_then(|caster| caster.csv_dialect())
+    {
+        Some(dialect) if dialect != sniffed => {
+            info!(
+                "Reading CSV again with the dialect of {}: {:?}",
+                detection.document_provider, dialect
+            );
+            convert_csv_with_dialect(content, &dialect).await.map_err(to_error)
+        }
+        _ => Ok((rows, csv_metadata)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use crate::parser::caster::transaction::Transaction;
+    use actix_web::ResponseError;
+
+    const REVOLUT_CSV: &str = "Type,Product,Started Date,Completed Date,Description,Amount,Fee,Currency,State,Balance
+TRANSFER,Savings,2024-01-01 01:24:30,2024-01-01 01:24:30,To EUR Pocket,50.00,0.00,EUR,COMPLETED,100.00
+TRANSFER,Savings,2024-01-01 09:22:37,2024-01-01 09:22:37,To EUR Pocket,n/a,0.00,EUR,COMPLETED,500.00
+";
+
+    fn parser() -> Parser {
+        Parser {
+            file_url: "revolut.csv".to_string(),
+            file_extension: "csv".to_string(),
+            file_mime: "text/csv".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn rows_that_cant_be_cast_are_errors_of_the_document() {
+        let content: Bytes = Bytes::from_static(REVOLUT_CSV.as_bytes());
+        let document: ParsedDocument = process_bytestream(&content, &parser(), &ParseOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(document.document_format, DocumentFormat::Csv);
+        assert_eq!(document.detection.document_provider, "revolut_csv");
+        assert_eq!(document.transactions().count(), 1);
+        assert_eq!(document.errors.len(), 1);
+        assert_eq!(document.errors[0].row, Some(1));
+    }
+
+    #[tokio::test]
+    async fn strict_mode_fails_the_document() {
+        let content: Bytes = Bytes::from_static(REVOLUT_CSV.as_bytes());
+        let options: ParseOptions = ParseOptions {
+            strict: true,
+            ..ParseOptions::default()
+        };
+
+        match process_bytestream(&content, &parser(), &options).await {
+            Err(AuroraError::Cast(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].error.raw_value.as_deref(), Some("n/a"));
             }
+            other => panic!("expected a cast error, got {:?}", other.map(|_| ())),
         }
-    } else {
-        match output_doc("./cache/Financieel_Overzicht_2024.pdf").await {
-            Ok(pages_text) => HttpResponse::Ok().json(pages_text),
-            Err(e) => HttpResponse::InternalServerError().body(format!("Error processing PDF: {}", e)),
+    }
+
+    #[tokio::test]
+    async fn xml_documents_are_rejected_even_when_named_csv() {
+        let content: Bytes = Bytes::from_static(
+            b"<?xml version=\"1.0\"?>\n<Document><Amt Ccy=\"EUR\">1,00</Amt>\n<Amt>2,00</Amt></Document>",
+        );
+
+        match process_bytestream(&content, &parser(), &ParseOptions::default()).await {
+            Err(error @ AuroraError::UnsupportedFormat { .. }) => {
+                assert_eq!(error.status_code(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+                assert_eq!(error.details(), Some(json!({ "format": "xml" })));
+            }
+            other => panic!("expected an unsupported format, got {:?}", other.map(|_| ())),
         }
     }
+
+    #[tokio::test]
+    async fn mt940_statements_are_decoded_with_their_encoding() {
+        let statement: &str = ":20:ABN AMRO BANK NV\r
+:25:123456789\r
+:28:24001/1\r
+:60F:C240130EUR1000,00\r
+:61:2402010201C250,00N541NONREF\r
+:86:SEPA Overboeking                 IBAN: NL91RABO0315273637\r
+BIC: RABONL2U                    Naam: Caf\u{e9} Noir\r
+Omschrijving: Terugbetaling cr\u{e8}me br\u{fb}l\u{e9}e\r
+:62F:C240201EUR1250,00\r
+-\r
+";
+        let (windows_1252, _, _) = encoding_rs::WINDOWS_1252.encode(statement);
+        let content: Bytes = Bytes::from(windows_1252.into_owned());
+        let parser: Parser = Parser {
+            file_url: "statement.sta".to_string(),
+            file_extension: "sta".to_string(),
+            file_mime: "application/octet-stream".to_string(),
+        };
+        let document: ParsedDocument = process_bytestream(&content, &parser, &ParseOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(document.document_format, DocumentFormat::Mt940);
+        assert_eq!(document.metadata.encoding.as_ref().unwrap().name, "windows-1252");
+        let transaction: &Transaction = document.transactions().next().unwrap();
+        assert_eq!(transaction.counterparty_name.as_deref(), Some("Caf\u{e9} Noir"));
+        assert!(transaction.description.contains("cr\u{e8}me br\u{fb}l\u{e9}e"));
+    }
 }
@@ -0,0 +1,313 @@
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::{from_value, Map, Value};
+
+use crate::parser::caster::caster_registry::provider::{to_object, OutputSchema, ProviderCaster};
+use crate::parser::caster::diagnostic::CastError;
+use crate::parser::caster::number::locale::DecimalSeparator;
+use crate::parser::caster::number::{try_cast_to_decimal, NumberFormat};
+use crate::parser::caster::time::{try_cast_to_unix, DateFormat};
+use crate::parser::caster::transaction::{non_empty, Transaction, TransactionStatus};
+use crate::parser::options::OutputShape;
+use crate::parser::schema::detection::{
+    header_overlap, value_match_ratio, weighted_score, DetectionContext, ValuePattern,
+};
+use crate::parser::schema::SchemaKeys;
+
+/// Represents a transaction from an ABN AMRO MT940 statement as produced by
+/// `parser::mt940::statements_to_json`.
+///
+/// # Fields
+///
+/// * `account` - A `String` representing the account (IBAN) of the statement.
+/// * `value_date` - A `String` representing the value date as RFC 3339 once normalized, to be casted to `i64`.
+/// * `entry_date` - A `String` representing the booking date as RFC 3339 once normalized, to be casted to `i64`.
+/// * `amount` - A `String` representing the signed amount of the transaction, to be casted to `Decimal`.
+/// * `balance` - A `String` representing the balance after the transaction, to be casted to `Decimal`.
+/// * `currency` - A `String` representing the currency of the statement.
+/// * `transaction_type` - A `String` representing the `/TRTP/` of the transaction.
+/// * `counterparty_iban` - A `String` representing the IBAN of the counterparty.
+/// * `counterparty_name` - A `String` representing the name of the counterparty.
+/// * `end_to_end_reference` - A `String` representing the `/EREF/` of the transaction.
+/// * `description` - A `String` providing a description of the transaction.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AbnAmroMt940TransactionOld {
+    pub document_provider: String,
+    pub account: String,
+    pub value_date: String, // To be casted to i64
+    pub entry_date: String, // To be casted to i64
+    pub amount: String,     // To be casted to Decimal
+    pub balance: String,    // To be casted to Decimal
+    pub currency: String,
+    pub transaction_type: String,
+    pub counterparty_iban: String,
+    pub counterparty_name: String,
+    pub end_to_end_reference: String,
+    pub description: String,
+}
+
+impl AbnAmroMt940TransactionOld {
+    /// Converts an instance of `AbnAmroMt940TransactionOld` to `AbnAmroMt940TransactionTarget`.
+    ///
+    /// The target mirrors `RevolutTransactionTarget` so statements from both
+    /// providers come out in the same shape. The booking date maps to
+    /// `started_date` and the value date to `completed_date`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is:
+    /// - `Ok(AbnAmroMt940TransactionTarget)` if all fields are successfully casted.
+    /// - `Err(CastError)` naming the first field that fails to cast.
+    pub fn to_target(&self) -> Result<AbnAmroMt940TransactionTarget, CastError> {
+        let amount: Decimal =
+            try_cast_to_decimal(&self.amount).ok_or_else(|| {
+            CastError::field("amount", &self.amount, "Failed to cast amount to Decimal")
+        })?;
+
+        let balance: Decimal =
+            try_cast_to_decimal(&self.balance).ok_or_else(|| {
+            CastError::field("balance", &self.balance, "Failed to cast balance to Decimal")
+        })?;
+
+        let mut entry_date_value: Value = Value::String(self.entry_date.clone());
+        try_cast_to_unix(&mut entry_date_value);
+        let started_date: i64 = entry_date_value
+            .as_i64()
+            .ok_or_else(|| {
+                CastError::field("entry_date", &self.entry_date, "Failed to cast entry_date to i64")
+            })?;
+
+        let mut value_date_value: Value = Value::String(self.value_date.clone());
+        try_cast_to_unix(&mut value_date_value);
+        let completed_date: i64 = value_date_value
+            .as_i64()
+            .ok_or_else(|| {
+                CastError::field("value_date", &self.value_date, "Failed to cast value_date to i64")
+            })?;
+
+        Ok(AbnAmroMt940TransactionTarget {
+            document_provider: self.document_provider.clone(),
+            amount,
+            balance,
+            product: self.account.clone(),
+            state: "COMPLETED".to_string(),
+            started_date,
+            currency: self.currency.clone(),
+            completed_date,
+            fee: Decimal::ZERO,
+            description: self.description.clone(),
+            transaction_type: self.transaction_type.clone(),
+            counterparty_iban: self.counterparty_iban.clone(),
+            counterparty_name: self.counterparty_name.clone(),
+            reference: self.end_to_end_reference.clone(),
+        })
+    }
+}
+
+/// Represents a transaction from an ABN AMRO MT940 statement with fields casted to appropriate types.
+///
+/// The first fields match `RevolutTransactionTarget`, the counterparty fields
+/// decoded from the `:86:` tag are added on top.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct AbnAmroMt940TransactionTarget {
+    /// A `String` representing the document provider.
+    pub document_provider: String,
+    /// A `Decimal` representing the signed amount of the transaction.
+    pub amount: Decimal,
+    /// A `Decimal` representing the balance after the transaction.
+    pub balance: Decimal,
+    /// A `String` representing the account (IBAN) the statement belongs to.
+    pub product: String,
+    /// A `String` representing the state of the transaction, booked MT940 lines are always `COMPLETED`.
+    pub state: String,
+    /// An `i64` representing the booking date of the transaction.
+    pub started_date: i64,
+    /// A `String` representing the currency used in the transaction.
+    pub currency: String,
+    /// An `i64` representing the value date of the transaction.
+    pub completed_date: i64,
+    /// A `Decimal` representing the fee associated with the transaction, MT940 books fees as separate lines.
+    pub fee: Decimal,
+    /// A `String` providing a description of the transaction.
+    pub description: String,
+    /// A `String` representing the transaction type, e.g. `SEPA OVERBOEKING`.
+    pub transaction_type: String,
+    /// A `String` representing the IBAN of the counterparty.
+    pub counterparty_iban: String,
+    /// A `String` representing the name of the counterparty.
+    pub counterparty_name: String,
+    /// A `String` representing the end-to-end reference of the transaction.
+    pub reference: String,
+}
+
+impl AbnAmroMt940TransactionTarget {
+    /// The fields holding amounts, formatted per the requested `MoneyFormat`.
+    pub const MONEY_FIELDS: [&'static str; 3] = ["amount", "balance", "fee"];
+    /// The fields holding dates, formatted per the requested `DateOutput`.
+    pub const DATE_FIELDS: [&'static str; 2] = ["started_date", "completed_date"];
+
+    /// Maps the MT940 transaction into the canonical `Transaction`.
+    ///
+    /// The account and the `/TRTP/` transaction type have no canonical
+    /// counterpart and are kept in `provider_fields`.
+    pub fn to_transaction(&self) -> Transaction {
+        let mut provider_fields: Map<String, Value> = Map::new();
+        provider_fields.insert("account".to_string(), Value::from(self.product.clone()));
+        provider_fields.insert(
+            "transaction_type".to_string(),
+            Value::from(self.transaction_type.clone()),
+        );
+
+        Transaction {
+            document_provider: self.document_provider.clone(),
+            booking_date: self.started_date,
+            value_date: Some(self.completed_date),
+            amount: self.amount,
+            currency: self.currency.clone(),
+            counterparty_name: non_empty(&self.counterparty_name),
+            counterparty_iban: non_empty(&self.counterparty_iban),
+            description: self.description.clone(),
+            reference: non_empty(&self.reference),
+            balance_after: Some(self.balance),
+            fee: None,
+            status: TransactionStatus::Completed,
+            provider_fields,
+            raw: Map::new(),
+        }
+    }
+}
+
+/// The `ProviderCaster` of ABN AMRO MT940 statements.
+pub struct AbnAmroMt940Caster;
+
+impl ProviderCaster for AbnAmroMt940Caster {
+    fn name(&self) -> &str {
+        "abn_amro_mt940"
+    }
+
+    fn detect(&self, context: &DetectionContext) -> f32 {
+        let header: f32 = header_overlap(context, &SchemaKeys::AbnAmroMt940.keys());
+        if header == 0.0 {
+            return 0.0;
+        }
+        let values: f32 = weighted_score(&[
+            (value_match_ratio(context, "account", ValuePattern::Iban), 1.0),
+            (value_match_ratio(context, "amount", ValuePattern::Amount), 1.0),
+        ]);
+        let score: f32 = weighted_score(&[(header, 0.7), (values, 0.3)]);
+        (score + 0.1 * context.file_name_score(&["mt940", "abn"])).min(1.0)
+    }
+
+    // the MT940 parser already emits `.` decimals
+    fn number_format(&self) -> NumberFormat {
+        NumberFormat {
+            decimal_separator: Some(DecimalSeparator::Dot),
+            amount_columns: vec!["amount".to_string(), "balance".to_string()],
+            indicator_column: None,
+        }
+    }
+
+    // MT940 dates are local to the bank
+    fn date_format(&self) -> DateFormat {
+        DateFormat {
+            formats: vec!["%y%m%d".to_string()],
+            timezone: Some("Europe/Amsterdam".to_string()),
+            date_columns: vec!["value_date".to_string(), "entry_date".to_string()],
+        }
+    }
+
+    fn cast_row(&self, row: &Map<String, Value>, shape: OutputShape) -> Result<Map<String, Value>, CastError> {
+        let abn_amro_transaction_old: AbnAmroMt940TransactionOld =
+            from_value(Value::Object(row.clone())).map_err(|e| {
+                CastError::new(format!("Failed to deserialize AbnAmroMt940TransactionOld: {}", e))
+            })?;
+
+        let abn_amro_transaction_target: AbnAmroMt940TransactionTarget =
+            abn_amro_transaction_old.to_target()?;
+
+        match shape {
+            OutputShape::Canonical => to_object(&abn_amro_transaction_target.to_transaction()),
+            OutputShape::Provider => to_object(&abn_amro_transaction_target),
+        }
+        .map_err(CastError::from)
+    }
+
+    fn output_schema(&self, shape: OutputShape) -> OutputSchema {
+        match shape {
+            OutputShape::Canonical => OutputSchema::canonical(),
+            OutputShape::Provider => OutputSchema::new(
+                &AbnAmroMt940TransactionTarget::MONEY_FIELDS,
+                &AbnAmroMt940TransactionTarget::DATE_FIELDS,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A normalized transaction of an ABN AMRO statement, as it reaches the caster.
+    fn row(value_date: &str) -> Map<String, Value> {
+        json!({
+            "document_provider": "abn_amro_mt940",
+            "account": "NL91ABNA0417164300",
+            "value_date": value_date,
+            "entry_date": "2024-01-31T00:00:00+01:00",
+            "amount": "-23.45",
+            "balance": "976.55",
+            "currency": "EUR",
+            "transaction_type": "SEPA OVERBOEKING",
+            "counterparty_iban": "NL20INGB0001234567",
+            "counterparty_name": "J. Jansen",
+            "end_to_end_reference": "",
+            "description": "Huur januari"
+        })
+        .as_object()
+        .cloned()
+        .unwrap()
+    }
+
+    #[test]
+    fn casts_statement_lines_into_canonical_transactions() {
+        let casted: Map<String, Value> = AbnAmroMt940Caster
+            .cast_row(&row("2024-02-01T00:00:00+01:00"), OutputShape::Canonical)
+            .unwrap();
+        let transaction: Transaction = from_value(Value::Object(casted)).unwrap();
+
+        assert_eq!(transaction.booking_date, 1706655600);
+        assert_eq!(transaction.value_date, Some(1706742000));
+        assert_eq!(transaction.amount, Decimal::new(-2345, 2));
+        assert_eq!(transaction.balance_after, Some(Decimal::new(97655, 2)));
+        assert_eq!(transaction.fee, None);
+        assert_eq!(transaction.counterparty_name.as_deref(), Some("J. Jansen"));
+        assert_eq!(transaction.counterparty_iban.as_deref(), Some("NL20INGB0001234567"));
+        assert_eq!(transaction.reference, None);
+        assert_eq!(transaction.status, TransactionStatus::Completed);
+        assert_eq!(transaction.provider_fields["account"], "NL91ABNA0417164300");
+        assert_eq!(transaction.provider_fields["transaction_type"], "SEPA OVERBOEKING");
+    }
+
+    #[test]
+    fn casts_statement_lines_into_the_provider_shape() {
+        let casted: Map<String, Value> = AbnAmroMt940Caster
+            .cast_row(&row("2024-02-01T00:00:00+01:00"), OutputShape::Provider)
+            .unwrap();
+        let target: AbnAmroMt940TransactionTarget = from_value(Value::Object(casted)).unwrap();
+
+        assert_eq!(target.product, "NL91ABNA0417164300");
+        assert_eq!(target.state, "COMPLETED");
+        assert_eq!(target.fee, Decimal::ZERO);
+        assert_eq!(target.reference, "");
+    }
+
+    #[test]
+    fn names_the_date_that_cant_be_cast() {
+        let error: CastError = AbnAmroMt940Caster
+            .cast_row(&row("240231"), OutputShape::Canonical)
+            .unwrap_err();
+        assert_eq!(error.field.as_deref(), Some("value_date"));
+        assert_eq!(error.raw_value.as_deref(), Some("240231"));
+    }
+}
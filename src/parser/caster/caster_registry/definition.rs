@@ -0,0 +1,792 @@
+//! ## Provider definitions
+//!
+//! CSV providers can be declared in `config.yaml` instead of in Rust. Each
+//! [`ProviderDefinition`] describes how to recognise an export and how its
+//! columns map onto the canonical [`Transaction`], and is registered as a
+//! [`DefinitionCaster`] next to the built-in casters.
+//!
+//! ```yaml
+//! providers:
+//!   - name: ing_csv
+//!     detection:
+//!       headers: [Datum, Naam / Omschrijving, Rekening, Tegenrekening, Code, Af Bij, Bedrag (EUR), Mutatiesoort, Mededelingen]
+//!       file_name_markers: [ing]
+//!     delimiter: ";"
+//!     encoding: windows-1252
+//!     currency: EUR
+//!     decimal_separator: comma
+//!     date_formats: ["%Y%m%d"]
+//!     timezone: Europe/Amsterdam
+//!     sign:
+//!       convention: indicator
+//!       column: Af Bij
+//!     columns:
+//!       booking_date: Datum
+//!       amount: Bedrag (EUR)
+//!     aliases:
+//!       reference: [Transactiereferentie]
+//! ```
+//!
+//! Columns that aren't mapped are found through their [`HeaderAliases`]: the
+//! built-in ones, which already know `Naam / Omschrijving`, `Tegenrekening` and
+//! `Mededelingen`, and the `aliases` of the provider.
+
+use anyhow::{bail, Context, Result};
+use chrono_tz::Tz;
+use encoding_rs::Encoding;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use tracing::info;
+
+// crate imports
+use crate::parser::caster::caster_registry::provider::{to_object, OutputSchema, ProviderCaster};
+use crate::parser::caster::diagnostic::CastError;
+use crate::parser::caster::number::locale::DecimalSeparator;
+use crate::parser::caster::number::{try_cast_to_decimal, IndicatorColumn, NumberFormat};
+use crate::parser::caster::time::{try_cast_to_unix, DateFormat};
+use crate::parser::caster::transaction::{non_empty, Transaction, TransactionStatus};
+use crate::parser::csv::headers::{builtin_aliases, normalize_header, HeaderAliases};
+use crate::parser::csv::CsvDialect;
+use crate::parser::options::OutputShape;
+use crate::parser::schema::detection::{
+    header_overlap_with, value_match_ratio, weighted_score, DetectionContext, ValuePattern,
+};
+
+/// The top level of `config.yaml`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ProvidersConfig {
+    /// The declared providers.
+    #[serde(default)]
+    pub providers: Vec<ProviderDefinition>,
+}
+
+/// How a provider is recognised.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct DetectionDefinition {
+    /// The header row of the export, as written in the file.
+    pub headers: Vec<String>,
+    /// Words of the file name that hint at the provider, e.g. `ing`.
+    #[serde(default)]
+    pub file_name_markers: Vec<String>,
+}
+
+/// Which source column feeds which canonical `Transaction` field.
+///
+/// Columns are written as in the header row of the export. A field that isn't
+/// mapped is read from the column known by one of its aliases.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ColumnMapping {
+    #[serde(default)]
+    pub booking_date: Option<String>,
+    #[serde(default)]
+    pub value_date: Option<String>,
+    #[serde(default)]
+    pub amount: Option<String>,
+    #[serde(default)]
+    pub currency: Option<String>,
+    #[serde(default)]
+    pub counterparty_name: Option<String>,
+    #[serde(default)]
+    pub counterparty_iban: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub reference: Option<String>,
+    #[serde(default)]
+    pub balance_after: Option<String>,
+    #[serde(default)]
+    pub fee: Option<String>,
+    #[serde(default)]
+    pub status: Option<String>,
+}
+
+impl ColumnMapping {
+    /// The canonical fields a column can be mapped to.
+    pub const FIELDS: [&'static str; 11] = [
+        "booking_date",
+        "value_date",
+        "amount",
+        "currency",
+        "counterparty_name",
+        "counterparty_iban",
+        "description",
+        "reference",
+        "balance_after",
+        "fee",
+        "status",
+    ];
+
+    /// Returns the column mapped to a canonical field.
+    pub fn get(&self, field: &str) -> Option<&String> {
+        match field {
+            "booking_date" => self.booking_date.as_ref(),
+            "value_date" => self.value_date.as_ref(),
+            "amount" => self.amount.as_ref(),
+            "currency" => self.currency.as_ref(),
+            "counterparty_name" => self.counterparty_name.as_ref(),
+            "counterparty_iban" => self.counterparty_iban.as_ref(),
+            "description" => self.description.as_ref(),
+            "reference" => self.reference.as_ref(),
+            "balance_after" => self.balance_after.as_ref(),
+            "fee" => self.fee.as_ref(),
+            "status" => self.status.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// How the sign of an amount is written.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(tag = "convention", rename_all = "snake_case")]
+pub enum SignConvention {
+    /// Amounts carry their own sign, money leaving the account is negative.
+    #[default]
+    Signed,
+    /// Money leaving the account is positive, e.g. credit card exports.
+    Inverted,
+    /// Amounts are unsigned and a separate column tells debit from credit.
+    Indicator { column: String },
+}
+
+/// A CSV provider declared in `config.yaml`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProviderDefinition {
+    /// The identifier stamped as `document_provider`, e.g. `ing_csv`.
+    pub name: String,
+    pub detection: DetectionDefinition,
+    /// The field delimiter, sniffed when unset.
+    #[serde(default)]
+    pub delimiter: Option<char>,
+    /// The WHATWG label of the text encoding, e.g. `windows-1252`. UTF-8 when unset.
+    #[serde(default)]
+    pub encoding: Option<String>,
+    #[serde(default)]
+    pub columns: ColumnMapping,
+    /// Headers of the export known as a canonical field, on top of the built-in aliases.
+    #[serde(default)]
+    pub aliases: BTreeMap<String, Vec<String>>,
+    /// The currency of every row when the export has no currency column.
+    #[serde(default)]
+    pub currency: Option<String>,
+    /// The decimal separator of the amounts, inferred per column when unset.
+    #[serde(default)]
+    pub decimal_separator: Option<DecimalSeparator>,
+    /// The `chrono` formats of the dates, inferred per column when empty.
+    #[serde(default)]
+    pub date_formats: Vec<String>,
+    /// The IANA timezone of the dates, UTC when unset.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub sign: SignConvention,
+}
+
+impl ProviderDefinition {
+    /// Checks that the definition is complete and consistent.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` which is `Err` listing every problem found.
+    pub fn validate(&self) -> Result<()> {
+        let mut problems: Vec<String> = vec![];
+
+        if self.name.trim().is_empty()
+            || !self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            problems.push(format!(
+                "name '{}' must be non-empty snake_case, e.g. `ing_csv`",
+                self.name
+            ));
+        }
+        if self.detection.headers.is_empty() {
+            problems.push("detection.headers must list the header row of the export".to_string());
+        }
+
+        let headers: HashSet<String> = self.headers();
+        for column in self.mapped_columns() {
+            if !headers.contains(&normalize_header(column)) {
+                problems.push(format!("column '{}' is not in detection.headers", column));
+            }
+        }
+        for field in self.aliases.keys() {
+            if !ColumnMapping::FIELDS.contains(&field.as_str()) {
+                problems.push(format!(
+                    "aliases.{} is not a field, expected one of {}",
+                    field,
+                    ColumnMapping::FIELDS.join(", ")
+                ));
+            }
+        }
+        let source_columns: HashMap<&'static str, String> = self.source_columns();
+        for field in ["booking_date", "amount"] {
+            if !source_columns.contains_key(field) {
+                problems.push(format!(
+                    "no header in detection.headers is known as {}, map columns.{} or add an alias",
+                    field, field
+                ));
+            }
+        }
+        if !source_columns.contains_key("currency") && self.currency.is_none() {
+            problems.push("either a currency column or currency must be set".to_string());
+        }
+        if let Some(timezone) = &self.timezone {
+            if timezone.parse::<Tz>().is_err() {
+                problems.push(format!("timezone '{}' is not an IANA timezone", timezone));
+            }
+        }
+        if let Some(delimiter) = self.delimiter {
+            if !delimiter.is_ascii() {
+                problems.push(format!("delimiter '{}' must be a single ASCII character", delimiter));
+            }
+        }
+        if let Some(encoding) = &self.encoding {
+            if Encoding::for_label(encoding.as_bytes()).is_none() {
+                problems.push(format!("encoding '{}' is unknown", encoding));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            bail!("provider '{}' is invalid:\n- {}", self.name, problems.join("\n- "))
+        }
+    }
+
+    /// The built-in header aliases, extended with the `aliases` of the provider.
+    pub fn header_aliases(&self) -> HeaderAliases {
+        let mut aliases: HeaderAliases = builtin_aliases().clone();
+        for (field, headers) in &self.aliases {
+            aliases.extend(field, headers.iter().map(String::as_str));
+        }
+        aliases
+    }
+
+    /// The normalized header row of the export.
+    fn headers(&self) -> HashSet<String> {
+        self.detection
+            .headers
+            .iter()
+            .map(|header| normalize_header(header))
+            .collect()
+    }
+
+    /// Every source column the definition maps explicitly.
+    fn mapped_columns(&self) -> Vec<&String> {
+        let mut mapped: Vec<&String> = ColumnMapping::FIELDS
+            .iter()
+            .filter_map(|field| self.columns.get(field))
+            .collect();
+        if let SignConvention::Indicator { column } = &self.sign {
+            mapped.push(column);
+        }
+        mapped
+    }
+
+    /// The normalized source column of every canonical field the export holds.
+    ///
+    /// Mapped columns come first, the remaining fields are looked up among the
+    /// headers that aren't mapped yet, each header feeding a single field.
+    fn source_columns(&self) -> HashMap<&'static str, String> {
+        let aliases: HeaderAliases = self.header_aliases();
+        let mut source_columns: HashMap<&'static str, String> = ColumnMapping::FIELDS
+            .iter()
+            .filter_map(|field| {
+                self.columns
+                    .get(field)
+                    .map(|column| (*field, normalize_header(column)))
+            })
+            .collect();
+
+        let mut unmapped: Vec<String> = self
+            .detection
+            .headers
+            .iter()
+            .map(|header| normalize_header(header))
+            .filter(|header| !source_columns.values().any(|column| column == header))
+            .collect();
+        if let SignConvention::Indicator { column } = &self.sign {
+            let indicator: String = normalize_header(column);
+            unmapped.retain(|header| *header != indicator);
+        }
+
+        for field in ColumnMapping::FIELDS {
+            if source_columns.contains_key(field) {
+                continue;
+            }
+            if let Some(header) = aliases.find(field, unmapped.iter().map(String::as_str)) {
+                let header: String = header.to_string();
+                unmapped.retain(|unmapped_header| *unmapped_header != header);
+                source_columns.insert(field, header);
+            }
+        }
+        source_columns
+    }
+}
+
+/// Loads the provider definitions from a YAML file.
+///
+/// A missing or empty file declares no providers. Every definition is
+/// validated, a single invalid definition rejects the whole file.
+///
+/// # Arguments
+///
+/// * `path` - The path of the YAML file, usually `config.yaml`.
+///
+/// # Returns
+///
+/// A `Result` containing the validated definitions.
+pub fn load_definitions(path: &Path) -> Result<Vec<ProviderDefinition>> {
+    if !path.exists() {
+        info!("No provider definitions at {}", path.display());
+        return Ok(vec![]);
+    }
+    let content: String = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    parse_definitions(&content).with_context(|| format!("Invalid provider definitions in {}", path.display()))
+}
+
+/// Parses and validates provider definitions from YAML.
+///
+/// # Arguments
+///
+/// * `content` - The YAML document, with a top-level `providers` list.
+///
+/// # Returns
+///
+/// A `Result` containing the validated definitions.
+pub fn parse_definitions(content: &str) -> Result<Vec<ProviderDefinition>> {
+    if content.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    let config: ProvidersConfig = serde_yaml::from_str(content)?;
+
+    let mut names: HashSet<&str> = HashSet::new();
+    for definition in &config.providers {
+        definition.validate()?;
+        if !names.insert(&definition.name) {
+            bail!("provider '{}' is defined twice", definition.name);
+        }
+    }
+    Ok(config.providers)
+}
+
+/// The `ProviderCaster` of a `ProviderDefinition`.
+pub struct DefinitionCaster {
+    definition: ProviderDefinition,
+    headers: HashSet<String>,
+    aliases: HeaderAliases,
+    source_columns: HashMap<&'static str, String>,
+}
+
+impl DefinitionCaster {
+    /// Wraps a validated definition.
+    pub fn new(definition: ProviderDefinition) -> DefinitionCaster {
+        let headers: HashSet<String> = definition.headers();
+        let aliases: HeaderAliases = definition.header_aliases();
+        let source_columns: HashMap<&'static str, String> = definition.source_columns();
+        DefinitionCaster {
+            definition,
+            headers,
+            aliases,
+            source_columns,
+        }
+    }
+
+    /// Returns the definition the caster was built from.
+    pub fn definition(&self) -> &ProviderDefinition {
+        &self.definition
+    }
+
+    /// Finds the column holding a field among the keys of a document.
+    ///
+    /// The column of the definition comes first, an export naming it differently
+    /// is read through the aliases of the field.
+    fn find_column<'a>(&self, field: &str, keys: &[&'a str]) -> Option<&'a str> {
+        keys.iter()
+            .copied()
+            .find(|key| self.source_columns.get(field).is_some_and(|column| column == key))
+            .or_else(|| self.aliases.find(field, keys.iter().copied()))
+    }
+
+    /// The name of the column holding a field, for diagnostics.
+    fn column_name(&self, field: &str) -> String {
+        self.source_columns
+            .get(field)
+            .cloned()
+            .unwrap_or_else(|| field.to_string())
+    }
+
+    /// The normalized source columns of some fields, and every alias they are known by.
+    fn columns_of(&self, fields: &[&str]) -> Vec<String> {
+        let mut columns: Vec<String> = vec![];
+        for field in fields {
+            let known: Vec<String> = self
+                .source_columns
+                .get(field)
+                .cloned()
+                .into_iter()
+                .chain(self.aliases.aliases_of(field))
+                .collect();
+            for column in known {
+                if !columns.contains(&column) {
+                    columns.push(column);
+                }
+            }
+        }
+        columns
+    }
+
+    /// Reads an optional field from a row.
+    fn column(&self, row: &Map<String, Value>, field: &str) -> Option<(String, String)> {
+        let keys: Vec<&str> = row.keys().map(String::as_str).collect();
+        let column: &str = self.find_column(field, &keys)?;
+        row.get(column)
+            .and_then(|value| value.as_str())
+            .and_then(non_empty)
+            .map(|value| (column.to_string(), value))
+    }
+
+    /// Reads an optional text field from a row.
+    fn text(&self, row: &Map<String, Value>, field: &str) -> Option<String> {
+        self.column(row, field).map(|(_, value)| value)
+    }
+
+    /// Reads and casts an optional amount field from a row.
+    fn amount(&self, row: &Map<String, Value>, field: &str) -> Result<Option<Decimal>, CastError> {
+        match self.column(row, field) {
+            Some((column, value)) => try_cast_to_decimal(&value)
+                .map(Some)
+                .ok_or_else(|| CastError::field(&column, &value, "Failed to cast to Decimal")),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads and casts an optional date field from a row.
+    fn date(&self, row: &Map<String, Value>, field: &str) -> Result<Option<i64>, CastError> {
+        match self.column(row, field) {
+            Some((column, value)) => {
+                let mut date_value: Value = Value::String(value.clone());
+                try_cast_to_unix(&mut date_value);
+                date_value
+                    .as_i64()
+                    .map(Some)
+                    .ok_or_else(|| CastError::field(&column, &value, "Failed to cast to i64"))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Maps a normalized row onto the canonical `Transaction`.
+    fn to_transaction(&self, row: &Map<String, Value>) -> Result<Transaction, CastError> {
+        let definition: &ProviderDefinition = &self.definition;
+
+        let booking_date: i64 = self
+            .date(row, "booking_date")?
+            .ok_or_else(|| CastError::missing(&self.column_name("booking_date")))?;
+        let mut amount: Decimal = self
+            .amount(row, "amount")?
+            .ok_or_else(|| CastError::missing(&self.column_name("amount")))?;
+        if definition.sign == SignConvention::Inverted {
+            amount = -amount;
+        }
+        let currency: String = self
+            .text(row, "currency")
+            .or_else(|| definition.currency.clone())
+            .unwrap_or_default();
+
+        // everything the mapping doesn't use is kept as is
+        let keys: Vec<&str> = row.keys().map(String::as_str).collect();
+        let mut mapped: HashSet<&str> = ColumnMapping::FIELDS
+            .iter()
+            .filter_map(|field| self.find_column(field, &keys))
+            .collect();
+        let indicator: Option<String> = match &definition.sign {
+            SignConvention::Indicator { column } => Some(normalize_header(column)),
+            _ => None,
+        };
+        mapped.extend(indicator.as_deref());
+        let provider_fields: Map<String, Value> = row
+            .iter()
+            .filter(|(key, _)| *key != "document_provider" && !mapped.contains(key.as_str()))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        Ok(Transaction {
+            document_provider: definition.name.clone(),
+            booking_date,
+            value_date: self.date(row, "value_date")?,
+            amount,
+            currency,
+            counterparty_name: self.text(row, "counterparty_name"),
+            counterparty_iban: self.text(row, "counterparty_iban"),
+            description: self.text(row, "description").unwrap_or_default(),
+            reference: self.text(row, "reference"),
+            balance_after: self.amount(row, "balance_after")?,
+            fee: self.amount(row, "fee")?,
+            status: match self.text(row, "status") {
+                Some(state) => TransactionStatus::from_provider_state(&state),
+                None => TransactionStatus::Completed,
+            },
+            provider_fields,
+            raw: Map::new(),
+        })
+    }
+}
+
+impl ProviderCaster for DefinitionCaster {
+    fn name(&self) -> &str {
+        &self.definition.name
+    }
+
+    fn detect(&self, context: &DetectionContext) -> f32 {
+        let expected: HashSet<&str> = self.headers.iter().map(String::as_str).collect();
+        let header: f32 = header_overlap_with(context, &expected, &self.aliases);
+        if header == 0.0 {
+            return 0.0;
+        }
+        let keys: Vec<&str> = context.header().into_iter().collect();
+        let ratio = |field: &str, pattern: ValuePattern| -> f32 {
+            self.find_column(field, &keys)
+                .map(|column| value_match_ratio(context, column, pattern))
+                .unwrap_or_default()
+        };
+        let values: f32 = weighted_score(&[
+            (ratio("booking_date", ValuePattern::Date), 1.0),
+            (ratio("amount", ValuePattern::Amount), 1.0),
+        ]);
+        let markers: Vec<&str> = self
+            .definition
+            .detection
+            .file_name_markers
+            .iter()
+            .map(String::as_str)
+            .collect();
+        let score: f32 = weighted_score(&[(header, 0.7), (values, 0.3)]);
+        (score + 0.1 * context.file_name_score(&markers)).min(1.0)
+    }
+
+    fn csv_dialect(&self) -> Option<CsvDialect> {
+        Some(CsvDialect {
+            delimiter: self.definition.delimiter.map(|delimiter| delimiter as u8),
+            quote: None,
+            encoding: self.definition.encoding.clone(),
+        })
+    }
+
+    fn number_format(&self) -> NumberFormat {
+        NumberFormat {
+            decimal_separator: self.definition.decimal_separator,
+            amount_columns: self.columns_of(&Transaction::MONEY_FIELDS),
+            indicator_column: match &self.definition.sign {
+                SignConvention::Indicator { column } => Some(IndicatorColumn {
+                    indicator: normalize_header(column),
+                    amount: self.column_name("amount"),
+                }),
+                _ => None,
+            },
+        }
+    }
+
+    fn date_format(&self) -> DateFormat {
+        DateFormat {
+            formats: self.definition.date_formats.clone(),
+            timezone: self.definition.timezone.clone(),
+            date_columns: self.columns_of(&Transaction::DATE_FIELDS),
+        }
+    }
+
+    fn cast_row(&self, row: &Map<String, Value>, shape: OutputShape) -> Result<Map<String, Value>, CastError> {
+        match shape {
+            OutputShape::Canonical => Ok(to_object(&self.to_transaction(row)?)?),
+            // a definition has no struct of its own, the normalized source row is its shape
+            OutputShape::Provider => Ok(row.clone()),
+        }
+    }
+
+    fn output_schema(&self, shape: OutputShape) -> OutputSchema {
+        match shape {
+            OutputShape::Canonical => OutputSchema::canonical(),
+            OutputShape::Provider => OutputSchema {
+                money_fields: self.columns_of(&Transaction::MONEY_FIELDS),
+                date_fields: vec![],
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::caster::number::normalize_amount_columns;
+    use crate::parser::caster::time::normalize_date_columns;
+    use serde_json::json;
+
+    const ING: &str = r#"
+providers:
+  - name: ing_csv
+    detection:
+      headers: [Datum, Naam / Omschrijving, Rekening, Tegenrekening, Code, Af Bij, Bedrag (EUR), Mutatiesoort, Mededelingen]
+      file_name_markers: [ing]
+    delimiter: ";"
+    encoding: windows-1252
+    currency: EUR
+    decimal_separator: comma
+    date_formats: ["%Y%m%d"]
+    timezone: Europe/Amsterdam
+    sign:
+      convention: indicator
+      column: Af Bij
+    columns:
+      booking_date: Datum
+      amount: Bedrag (EUR)
+    aliases:
+      reference: [Transactiereferentie]
+"#;
+
+    fn ing() -> DefinitionCaster {
+        DefinitionCaster::new(parse_definitions(ING).unwrap().remove(0))
+    }
+
+    fn ing_rows() -> Value {
+        json!([
+            {
+                "datum": "20240131",
+                "naam_omschrijving": "Albert Heijn 1234",
+                "rekening": "NL20INGB0001234567",
+                "tegenrekening": "",
+                "code": "BA",
+                "af_bij": "Af",
+                "bedrag_eur": "12,50",
+                "mutatiesoort": "Betaalautomaat",
+                "mededelingen": "Pasvolgnr: 001 31-01-2024 12:15"
+            },
+            {
+                "datum": "20240201",
+                "naam_omschrijving": "Acme BV",
+                "rekening": "NL20INGB0001234567",
+                "tegenrekening": "NL91ABNA0417164300",
+                "code": "OV",
+                "af_bij": "Bij",
+                "bedrag_eur": "1.250,00",
+                "mutatiesoort": "Overschrijving",
+                "mededelingen": "Factuur 2024-001"
+            }
+        ])
+    }
+
+    #[test]
+    fn parses_the_documented_example() {
+        let definitions: Vec<ProviderDefinition> = parse_definitions(ING).unwrap();
+        assert_eq!(definitions.len(), 1);
+        assert_eq!(definitions[0].delimiter, Some(';'));
+        assert_eq!(definitions[0].decimal_separator, Some(DecimalSeparator::Comma));
+        assert_eq!(
+            definitions[0].sign,
+            SignConvention::Indicator {
+                column: "Af Bij".to_string()
+            }
+        );
+
+        // the unmapped columns are found through the built-in aliases
+        let source_columns: HashMap<&'static str, String> = definitions[0].source_columns();
+        assert_eq!(source_columns["counterparty_name"], "naam_omschrijving");
+        assert_eq!(source_columns["counterparty_iban"], "tegenrekening");
+        assert_eq!(source_columns["description"], "mededelingen");
+    }
+
+    #[test]
+    fn an_empty_file_declares_no_providers() {
+        assert!(parse_definitions("").unwrap().is_empty());
+        assert!(parse_definitions("providers: []").unwrap().is_empty());
+    }
+
+    #[test]
+    fn lists_every_problem_of_an_invalid_definition() {
+        let invalid: &str = r#"
+providers:
+  - name: ING CSV
+    detection:
+      headers: [Omschrijving]
+    timezone: Europe/Utrecht
+    encoding: klingon
+    columns:
+      amount: Bedrag
+    aliases:
+      iban: [Rekening]
+"#;
+        let message: String = format!("{:#}", parse_definitions(invalid).unwrap_err());
+        for problem in [
+            "name 'ING CSV' must be non-empty snake_case",
+            "column 'Bedrag' is not in detection.headers",
+            "aliases.iban is not a field",
+            "no header in detection.headers is known as booking_date",
+            "either a currency column or currency must be set",
+            "timezone 'Europe/Utrecht' is not an IANA timezone",
+            "encoding 'klingon' is unknown",
+        ] {
+            assert!(message.contains(problem), "{} in {}", problem, message);
+        }
+
+        let twice: String = format!("{}{}", ING, &ING["\nproviders:\n".len()..]);
+        assert!(parse_definitions(&twice).unwrap_err().to_string().contains("defined twice"));
+    }
+
+    #[test]
+    fn detects_its_own_exports() {
+        let rows: Value = ing_rows();
+        let rows: &[Value] = rows.as_array().unwrap();
+        let caster: DefinitionCaster = ing();
+
+        let context: DetectionContext = DetectionContext::new(rows, Some("NL20INGB_01-01-2024.csv"));
+        let score: f32 = caster.detect(&context);
+        assert!(score > 0.9, "{}", score);
+
+        let other: Vec<Value> = vec![json!({ "type": "TRANSFER", "started_date": "2024-01-01" })];
+        assert_eq!(caster.detect(&DetectionContext::new(&other, None)), 0.0);
+    }
+
+    #[test]
+    fn casts_normalized_rows_into_transactions() {
+        let caster: DefinitionCaster = ing();
+        let mut rows: Value = ing_rows();
+        normalize_amount_columns(&mut rows, &caster.number_format());
+        normalize_date_columns(&mut rows, &caster.date_format());
+
+        let rows: &Vec<Value> = rows.as_array().unwrap();
+        let debit: Map<String, Value> = caster
+            .cast_row(rows[0].as_object().unwrap(), OutputShape::Canonical)
+            .unwrap();
+        assert_eq!(debit["document_provider"], "ing_csv");
+        assert_eq!(debit["booking_date"], 1706655600);
+        assert_eq!(debit["amount"], "-12.50");
+        assert_eq!(debit["currency"], "EUR");
+        assert_eq!(debit["counterparty_name"], "Albert Heijn 1234");
+        assert_eq!(debit["counterparty_iban"], Value::Null);
+        assert_eq!(debit["description"], "Pasvolgnr: 001 31-01-2024 12:15");
+        assert_eq!(debit["status"], "completed");
+        assert_eq!(debit["provider_fields"]["mutatiesoort"], "Betaalautomaat");
+        assert!(debit["provider_fields"].get("af_bij").is_none());
+
+        let credit: Map<String, Value> = caster
+            .cast_row(rows[1].as_object().unwrap(), OutputShape::Canonical)
+            .unwrap();
+        assert_eq!(credit["amount"], "1250.00");
+        assert_eq!(credit["counterparty_iban"], "NL91ABNA0417164300");
+    }
+
+    #[test]
+    fn a_row_without_an_amount_is_a_cast_error() {
+        let caster: DefinitionCaster = ing();
+        let row: Value = json!({ "datum": "2024-01-31T00:00:00+01:00", "bedrag_eur": "" });
+        let error: CastError = caster
+            .cast_row(row.as_object().unwrap(), OutputShape::Canonical)
+            .unwrap_err();
+        assert_eq!(error, CastError::missing("bedrag_eur"));
+    }
+}
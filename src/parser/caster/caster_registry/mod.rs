@@ -1,58 +1,411 @@
+//! ## Caster registry
+//!
+//! Every supported document provider registers a [`ProviderCaster`] here.
+//! Detection and casting look providers up in the registry instead of
+//! matching on their names, so adding a provider never means touching the
+//! pipeline. Applications embedding Aurora can register their own casters
+//! at startup with [`register_caster`], operators can declare CSV providers
+//! in `config.yaml` (see [`definition`]).
+
+pub mod abn_amro_mt940;
+pub mod definition;
+pub mod provider;
 pub mod revolut_csv;
-use crate::parser::caster::caster_registry::revolut_csv::{
-    RevolutTransactionOld, RevolutTransactionTarget,
+
+use crate::config::get_config_path;
+use crate::parser::caster::caster_registry::abn_amro_mt940::AbnAmroMt940Caster;
+use crate::parser::caster::caster_registry::definition::{
+    load_definitions, DefinitionCaster, ProviderDefinition,
 };
-use serde_json::{from_value, to_value, Map, Value};
-use tracing::info;
+use crate::parser::caster::caster_registry::provider::{OutputSchema, ProviderCaster};
+use crate::parser::caster::caster_registry::revolut_csv::RevolutCsvCaster;
+use crate::parser::caster::diagnostic::{CastError, Diagnostic, Diagnostics};
+use crate::parser::caster::money::format_money_fields;
+use crate::parser::caster::number::normalize_amount_columns;
+use crate::parser::caster::time::{format_date_fields, normalize_date_columns, DateFormat};
+use crate::parser::options::ParseOptions;
+use chrono_tz::Tz;
+use serde_json::{Map, Value};
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock, RwLock};
+use tracing::{info, warn};
 
-pub async fn cast_transactions(json_array: &mut Value) -> Result<Value, String> {
-    if let Some(array) = json_array.as_array_mut() {
-        for item in array {
-            if let Some(obj_map) = item.as_object_mut() {
-                process_transaction(obj_map).await?;
+/// A set of `ProviderCaster`s, looked up by name.
+///
+/// Casters registered in code and casters built from `config.yaml` are kept
+/// apart, so the latter can be replaced on reload without losing the former.
+/// A definition shadows a caster registered in code under the same name.
+#[derive(Clone, Default)]
+pub struct CasterRegistry {
+    casters: Vec<Arc<dyn ProviderCaster>>,
+    definitions: Vec<Arc<dyn ProviderCaster>>,
+}
+
+impl CasterRegistry {
+    /// Creates a registry holding the casters shipped with Aurora.
+    pub fn with_builtin() -> CasterRegistry {
+        let mut registry: CasterRegistry = CasterRegistry::default();
+        registry.register(Arc::new(RevolutCsvCaster));
+        registry.register(Arc::new(AbnAmroMt940Caster));
+        registry
+    }
+
+    /// Registers a caster, replacing any caster registered under the same name.
+    ///
+    /// # Arguments
+    ///
+    /// * `caster` - The `ProviderCaster` to register.
+    pub fn register(&mut self, caster: Arc<dyn ProviderCaster>) {
+        if let Some(existing) = self
+            .casters
+            .iter_mut()
+            .find(|existing| existing.name() == caster.name())
+        {
+            info!("Replacing caster for provider '{}'", caster.name());
+            *existing = caster;
+        } else {
+            info!("Registering caster for provider '{}'", caster.name());
+            self.casters.push(caster);
+        }
+    }
+
+    /// Replaces every caster built from provider definitions.
+    ///
+    /// # Arguments
+    ///
+    /// * `definitions` - The casters built from the current `config.yaml`.
+    pub fn replace_definitions(&mut self, definitions: Vec<Arc<dyn ProviderCaster>>) {
+        for definition in &definitions {
+            if self.casters.iter().any(|caster| caster.name() == definition.name()) {
+                warn!(
+                    "Provider definition '{}' shadows the caster registered in code",
+                    definition.name()
+                );
             }
         }
+        self.definitions = definitions;
+    }
+
+    /// Looks up the caster of a provider.
+    ///
+    /// # Arguments
+    ///
+    /// * `document_provider` - The name of the provider, e.g. `revolut_csv`.
+    ///
+    /// # Returns
+    ///
+    /// The `ProviderCaster`, `None` if no caster is registered under that name.
+    pub fn get(&self, document_provider: &str) -> Option<Arc<dyn ProviderCaster>> {
+        self.definitions
+            .iter()
+            .chain(self.casters.iter())
+            .find(|caster| caster.name() == document_provider)
+            .cloned()
+    }
+
+    /// Returns every effective caster, registered in code first, then definitions.
+    pub fn casters(&self) -> Vec<Arc<dyn ProviderCaster>> {
+        self.casters
+            .iter()
+            .filter(|caster| {
+                !self
+                    .definitions
+                    .iter()
+                    .any(|definition| definition.name() == caster.name())
+            })
+            .chain(self.definitions.iter())
+            .cloned()
+            .collect()
     }
-    Ok(json_array.clone())
 }
 
-async fn process_transaction(obj_map: &mut Map<String, Value>) -> Result<(), String> {
-    info!("{:#?} objmap", obj_map);
-    // Clone the document_provider to avoid borrowing issues
-    let document_provider: Option<String> = obj_map
-        .get("document_provider")
-        .and_then(|v| v.as_str().map(|s| s.to_string()));
+/// The process-wide registry, created with the built-in casters on first use.
+fn shared_registry() -> &'static RwLock<Arc<CasterRegistry>> {
+    static REGISTRY: OnceLock<RwLock<Arc<CasterRegistry>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(Arc::new(CasterRegistry::with_builtin())))
+}
+
+/// Returns a snapshot of the process-wide registry.
+///
+/// The snapshot is unaffected by casters registered after it was taken, so a
+/// document is always cast against a single consistent set of providers.
+pub fn registry() -> Arc<CasterRegistry> {
+    match shared_registry().read() {
+        Ok(registry) => registry.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// Applies a change to a copy of the process-wide registry and swaps it in.
+///
+/// Documents being processed keep the snapshot they started with.
+fn update_registry<F: FnOnce(&mut CasterRegistry)>(update: F) {
+    let mut guard = match shared_registry().write() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    let mut updated: CasterRegistry = guard.as_ref().clone();
+    update(&mut updated);
+    *guard = Arc::new(updated);
+}
+
+/// Registers a caster in the process-wide registry.
+///
+/// Call this at startup to teach Aurora a provider it doesn't ship with.
+///
+/// # Arguments
+///
+/// * `caster` - The `ProviderCaster` to register, replacing any caster with the same name.
+pub fn register_caster(caster: Arc<dyn ProviderCaster>) {
+    update_registry(|registry| registry.register(caster));
+}
 
+/// Loads the provider definitions of `config.yaml` into the process-wide registry.
+///
+/// The definitions replace the ones loaded before, so this is used both at
+/// startup and to reload a changed file. When any definition is invalid the
+/// registry is left untouched and the previous definitions stay in use. The
+/// path can be overridden with the `AURORA_CONFIG_PATH` environment variable.
+///
+/// # Returns
+///
+/// A `Result` containing the names of the providers loaded, or why the file was rejected.
+pub fn load_configured_providers() -> anyhow::Result<Vec<String>> {
+    let path: PathBuf = get_config_path();
+    let definitions: Vec<ProviderDefinition> = load_definitions(&path)?;
+    let names: Vec<String> = definitions
+        .iter()
+        .map(|definition| definition.name.clone())
+        .collect();
+
+    let casters: Vec<Arc<dyn ProviderCaster>> = definitions
+        .into_iter()
+        .map(|definition| Arc::new(DefinitionCaster::new(definition)) as Arc<dyn ProviderCaster>)
+        .collect();
+    update_registry(|registry| registry.replace_definitions(casters));
+
+    info!("Loaded {} provider definitions from {}", names.len(), path.display());
+    Ok(names)
+}
+
+/// Casts every transaction of a detected document into the requested output shape.
+///
+/// The amount columns are normalized with the `NumberFormat` of the provider
+/// first, so every caster receives plain decimal strings, and the date columns
+/// with its `DateFormat`, so every caster receives RFC 3339 timestamps. Every
+/// casted row carries its source columns, as parsed, under `raw`.
+///
+/// Rows are cast independently: a row that can't be cast is left out of the
+/// result and reported in the `Diagnostics` instead of failing the document.
+///
+/// # Arguments
+///
+/// * `json_array` - A mutable reference to a JSON array of objects carrying a `document_provider` key.
+/// * `options` - The `ParseOptions` selecting the output shape, money and date format.
+/// * `registry` - The `CasterRegistry` snapshot the provider was detected with.
+///
+/// # Returns
+///
+/// The casted JSON array and the `Diagnostics` collected while casting it.
+pub async fn cast_transactions(
+    json_array: &mut Value,
+    options: &ParseOptions,
+    registry: &CasterRegistry,
+) -> (Value, Diagnostics) {
+    let mut diagnostics: Diagnostics = Diagnostics::default();
+    let document_provider: Option<String> = json_array
+        .get(0)
+        .and_then(|first| first.get("document_provider"))
+        .and_then(|v| v.as_str().map(|s| s.to_string()));
     info!("document_provider: {:?}", document_provider);
 
-    match document_provider.as_deref() {
-        Some("revolut_csv") => {
-            let revolut_transaction_old: RevolutTransactionOld =
-                from_value(Value::Object(obj_map.clone()))
-                    .map_err(|e| format!("Failed to deserialize RevolutTransactionOld: {}", e))?;
-            info!("revolut_transaction_old: {:#?}", revolut_transaction_old);
-
-            let revolut_transaction_target: RevolutTransactionTarget =
-                revolut_transaction_old.to_target()?;
-
-            info!(
-                "revolut_transaction_target: {:#?}",
-                revolut_transaction_target
-            );
-
-            let updated_data: Map<String, Value> = to_value(revolut_transaction_target)
-                .map_err(|e| format!("Failed to serialize RevolutTransactionTarget: {}", e))?
-                .as_object()
-                .cloned()
-                .ok_or("Failed to convert RevolutTransactionTarget to object")?;
-            info!("updated_data: {:#?}", updated_data);
-
-            obj_map.clear();
-            obj_map.extend(updated_data);
+    // Add more document providers by registering a `ProviderCaster`
+    let Some(caster) = document_provider
+        .as_deref()
+        .and_then(|provider| registry.get(provider))
+    else {
+        warn!("No caster registered for {:?}, returning rows as parsed", document_provider);
+        diagnostics.warnings.push(Diagnostic::document(format!(
+            "No caster registered for provider '{}', rows are returned as parsed",
+            document_provider.as_deref().unwrap_or("unknown")
+        )));
+        return (json_array.clone(), diagnostics);
+    };
+
+    // keep the rows as parsed, normalization rewrites amounts and dates in place
+    let raw_rows: Vec<Map<String, Value>> = json_array
+        .as_array()
+        .map(|array| array.iter().map(raw_columns).collect())
+        .unwrap_or_default();
+
+    diagnostics
+        .warnings
+        .extend(normalize_amount_columns(json_array, &caster.number_format()));
+    let date_format: DateFormat = caster.date_format();
+    normalize_date_columns(json_array, &date_format);
+    let tz: Tz = date_format.tz();
+
+    let mut casted: Vec<Value> = vec![];
+    if let Some(array) = json_array.as_array_mut() {
+        for (index, (item, raw)) in array.iter_mut().zip(raw_rows).enumerate() {
+            let Some(obj_map) = item.as_object_mut() else {
+                continue;
+            };
+            match process_transaction(obj_map, raw, caster.as_ref(), options, tz).await {
+                Ok(()) => casted.push(item.clone()),
+                Err(e) => {
+                    warn!("Failed to cast row {}: {}", index, e);
+                    diagnostics.errors.push(Diagnostic::row(index, e));
+                }
+            }
         }
-        // Add more document providers here as needed
-        _ => {}
     }
+    (Value::Array(casted), diagnostics)
+}
+
+async fn process_transaction(
+    obj_map: &mut Map<String, Value>,
+    raw: Map<String, Value>,
+    caster: &dyn ProviderCaster,
+    options: &ParseOptions,
+    tz: Tz,
+) -> Result<(), CastError> {
+    let mut updated_data: Map<String, Value> = caster.cast_row(obj_map, options.output_shape)?;
+    let output_schema: OutputSchema = caster.output_schema(options.output_shape);
+
+    let money_fields: Vec<&str> = output_schema.money_fields.iter().map(String::as_str).collect();
+    let date_fields: Vec<&str> = output_schema.date_fields.iter().map(String::as_str).collect();
+    format_money_fields(&mut updated_data, &money_fields, options.money_format);
+    format_date_fields(&mut updated_data, &date_fields, options.date_format, tz);
+    updated_data.insert("raw".to_string(), Value::Object(raw));
+
+    obj_map.clear();
+    obj_map.extend(updated_data);
 
     Ok(())
 }
+
+/// Copies the source columns of a parsed row, leaving out the detected `document_provider`.
+fn raw_columns(row: &Value) -> Map<String, Value> {
+    row.as_object()
+        .map(|obj_map| {
+            obj_map
+                .iter()
+                .filter(|(key, _)| *key != "document_provider")
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::caster::caster_registry::definition::parse_definitions;
+    use serde_json::json;
+
+    /// A definition taking over the name of the built-in Revolut caster.
+    fn revolut_definition() -> Arc<dyn ProviderCaster> {
+        let yaml: &str = r#"
+providers:
+  - name: revolut_csv
+    detection:
+      headers: [Date, Amount]
+    currency: EUR
+    columns:
+      booking_date: Date
+      amount: Amount
+"#;
+        Arc::new(DefinitionCaster::new(parse_definitions(yaml).unwrap().remove(0)))
+    }
+
+    fn revolut_row(amount: &str) -> Value {
+        json!({
+            "document_provider": "revolut_csv",
+            "type": "TRANSFER",
+            "product": "Savings",
+            "started_date": "2024-01-01 01:24:30",
+            "completed_date": "2024-01-01 01:24:30",
+            "description": "To EUR Pocket",
+            "amount": amount,
+            "fee": "0.00",
+            "currency": "EUR",
+            "state": "COMPLETED",
+            "balance": "100.00"
+        })
+    }
+
+    #[tokio::test]
+    async fn casted_rows_keep_their_source_columns_under_raw() {
+        let mut rows: Value = json!([revolut_row("50.00")]);
+        let (casted, diagnostics): (Value, Diagnostics) =
+            cast_transactions(&mut rows, &ParseOptions::default(), &registry()).await;
+
+        assert!(diagnostics.errors.is_empty());
+        let transaction: &Value = &casted[0];
+        assert_eq!(transaction["amount"], "50.00");
+        assert_eq!(transaction["booking_date"], 1704072270);
+        assert_eq!(transaction["raw"]["started_date"], "2024-01-01 01:24:30");
+        assert_eq!(transaction["raw"]["amount"], "50.00");
+        assert!(transaction["raw"].get("document_provider").is_none());
+    }
+
+    #[tokio::test]
+    async fn rows_that_cant_be_cast_are_reported_by_index() {
+        let mut rows: Value = json!([revolut_row("50.00"), revolut_row("n/a"), revolut_row("-1.5")]);
+        let (casted, diagnostics): (Value, Diagnostics) =
+            cast_transactions(&mut rows, &ParseOptions::default(), &registry()).await;
+
+        assert_eq!(casted.as_array().unwrap().len(), 2);
+        assert_eq!(casted[1]["amount"], "-1.50");
+        assert!(diagnostics.warnings.is_empty());
+        assert_eq!(diagnostics.errors.len(), 1);
+        assert_eq!(diagnostics.errors[0].row, Some(1));
+        assert_eq!(diagnostics.errors[0].error.field.as_deref(), Some("amount"));
+        assert_eq!(diagnostics.errors[0].error.raw_value.as_deref(), Some("n/a"));
+    }
+
+    #[tokio::test]
+    async fn rows_without_a_caster_are_returned_as_parsed() {
+        let mut rows: Value = json!([{ "document_provider": "unknown", "foo": "bar" }]);
+        let (casted, diagnostics): (Value, Diagnostics) =
+            cast_transactions(&mut rows, &ParseOptions::default(), &registry()).await;
+
+        assert_eq!(casted, rows);
+        assert!(diagnostics.errors.is_empty());
+        assert_eq!(diagnostics.warnings.len(), 1);
+        assert_eq!(diagnostics.warnings[0].row, None);
+    }
+
+    #[test]
+    fn a_definition_shadows_a_builtin_caster_until_it_is_reloaded_away() {
+        let mut registry: CasterRegistry = CasterRegistry::with_builtin();
+        let builtin: Arc<dyn ProviderCaster> = registry.get("revolut_csv").unwrap();
+        let definition: Arc<dyn ProviderCaster> = revolut_definition();
+
+        registry.replace_definitions(vec![definition.clone()]);
+        assert!(Arc::ptr_eq(&registry.get("revolut_csv").unwrap(), &definition));
+        let revolut: Vec<Arc<dyn ProviderCaster>> = registry
+            .casters()
+            .into_iter()
+            .filter(|caster| caster.name() == "revolut_csv")
+            .collect();
+        assert_eq!(revolut.len(), 1);
+        assert!(Arc::ptr_eq(&revolut[0], &definition));
+
+        // a reload without the definition brings the built-in caster back
+        registry.replace_definitions(vec![]);
+        assert!(Arc::ptr_eq(&registry.get("revolut_csv").unwrap(), &builtin));
+        assert_eq!(
+            registry.casters().len(),
+            CasterRegistry::with_builtin().casters().len()
+        );
+    }
+
+    #[test]
+    fn unknown_providers_have_no_caster() {
+        let registry: CasterRegistry = CasterRegistry::with_builtin();
+        assert!(registry.get("bank_of_nowhere").is_none());
+        assert!(registry.get("abn_amro_mt940").is_some());
+    }
+}
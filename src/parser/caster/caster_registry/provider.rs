@@ -0,0 +1,106 @@
+//! ## Provider casters
+//!
+//! A [`ProviderCaster`] knows how to recognise the rows of one document
+//! provider and how to cast them into a [`Transaction`] or the provider's own
+//! target struct. The casters shipped with Aurora live next to this module;
+//! applications embedding Aurora can add their own through
+//! [`register_caster`](super::register_caster).
+
+use serde::Serialize;
+use serde_json::{to_value, Map, Value};
+
+// crate imports
+use crate::parser::caster::diagnostic::CastError;
+use crate::parser::caster::number::NumberFormat;
+use crate::parser::caster::time::DateFormat;
+use crate::parser::caster::transaction::Transaction;
+use crate::parser::csv::CsvDialect;
+use crate::parser::options::OutputShape;
+use crate::parser::schema::detection::DetectionContext;
+
+/// The fields of a casted row that need output formatting.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OutputSchema {
+    /// The fields holding amounts, formatted per the requested `MoneyFormat`.
+    pub money_fields: Vec<String>,
+    /// The fields holding Unix time seconds, formatted per the requested `DateOutput`.
+    pub date_fields: Vec<String>,
+}
+
+impl OutputSchema {
+    /// Builds an `OutputSchema` from the `MONEY_FIELDS` and `DATE_FIELDS` of a struct.
+    pub fn new(money_fields: &[&str], date_fields: &[&str]) -> OutputSchema {
+        OutputSchema {
+            money_fields: money_fields.iter().map(|field| field.to_string()).collect(),
+            date_fields: date_fields.iter().map(|field| field.to_string()).collect(),
+        }
+    }
+
+    /// The schema of the canonical `Transaction`.
+    pub fn canonical() -> OutputSchema {
+        OutputSchema::new(&Transaction::MONEY_FIELDS, &Transaction::DATE_FIELDS)
+    }
+}
+
+/// Recognises and casts the rows of a single document provider.
+///
+/// Rows reach a caster as the string-valued JSON objects produced by the
+/// parsing engines, with their amount and date columns already normalized
+/// according to [`number_format`](ProviderCaster::number_format) and
+/// [`date_format`](ProviderCaster::date_format).
+pub trait ProviderCaster: Send + Sync {
+    /// The identifier stamped as `document_provider`, e.g. `revolut_csv`.
+    fn name(&self) -> &str;
+
+    /// Scores how likely a document comes from this provider.
+    ///
+    /// Implementations usually combine the helpers of `parser::schema::detection`.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - The `DetectionContext` holding the rows, file name and text of the document.
+    ///
+    /// # Returns
+    ///
+    /// A score between `0.0` (not this provider) and `1.0` (certainly this provider).
+    fn detect(&self, context: &DetectionContext) -> f32;
+
+    /// How the provider writes its CSV exports, sniffed by default.
+    fn csv_dialect(&self) -> Option<CsvDialect> {
+        None
+    }
+
+    /// How the provider writes its amounts, nothing is normalized by default.
+    fn number_format(&self) -> NumberFormat {
+        NumberFormat::default()
+    }
+
+    /// How the provider writes its dates, nothing is normalized by default.
+    fn date_format(&self) -> DateFormat {
+        DateFormat::default()
+    }
+
+    /// Casts a single row into the requested output shape.
+    ///
+    /// # Arguments
+    ///
+    /// * `row` - The normalized row, including its `document_provider` key.
+    /// * `shape` - The requested `OutputShape`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` containing the casted row as a JSON object, or a `CastError` naming the field that couldn't be casted.
+    fn cast_row(&self, row: &Map<String, Value>, shape: OutputShape) -> Result<Map<String, Value>, CastError>;
+
+    /// The fields of the casted rows that need output formatting.
+    fn output_schema(&self, shape: OutputShape) -> OutputSchema;
+}
+
+/// Serializes a casted transaction, either a `Transaction` or a provider target, into a JSON object.
+pub fn to_object<T: Serialize>(value: &T) -> Result<Map<String, Value>, String> {
+    to_value(value)
+        .map_err(|e| format!("Failed to serialize {}: {}", std::any::type_name::<T>(), e))?
+        .as_object()
+        .cloned()
+        .ok_or_else(|| format!("Failed to convert {} to object", std::any::type_name::<T>()))
+}
@@ -1,11 +1,20 @@
 use chrono::NaiveDateTime;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{from_value, Map, Value};
 use std::str::FromStr;
-use tracing::info;
 
-use crate::parser::caster::number::try_cast_to_f64;
-use crate::parser::caster::time::try_cast_to_unix;
+use crate::parser::caster::caster_registry::provider::{to_object, OutputSchema, ProviderCaster};
+use crate::parser::caster::diagnostic::CastError;
+use crate::parser::caster::number::locale::DecimalSeparator;
+use crate::parser::caster::number::{try_cast_to_decimal, NumberFormat};
+use crate::parser::caster::time::{try_cast_to_unix, DateFormat};
+use crate::parser::caster::transaction::{Transaction, TransactionStatus};
+use crate::parser::options::OutputShape;
+use crate::parser::schema::detection::{
+    header_overlap, value_match_ratio, weighted_score, DetectionContext, ValuePattern,
+};
+use crate::parser::schema::SchemaKeys;
 
 /// Represents a transaction in the Revolut system.
 ///
@@ -16,26 +25,28 @@ use crate::parser::caster::time::try_cast_to_unix;
 /// # Fields
 ///
 /// * `type` - A `String` representing the type of the transaction.
-/// * `amount` - A `String` representing the amount of the transaction, to be casted to `f64`.
-/// * `balance` - A `String` representing the balance after the transaction, to be casted to `f64`.
+/// * `amount` - A `String` representing the amount of the transaction, to be casted to `Decimal`.
+/// * `balance` - A `String` representing the balance after the transaction, to be casted to `Decimal`.
 /// * `product` - A `String` representing the product associated with the transaction.
 /// * `state` - A `String` representing the state of the transaction.
 /// * `started_date` - A `String` representing the date when the transaction started, to be casted to `i64`.
 /// * `currency` - A `String` representing the currency used in the transaction.
 /// * `completed_date` - A `String` representing the date when the transaction was completed, to be casted to `i64`.
-/// * `fee` - A `String` representing the fee associated with the transaction, to be casted to `f64`.
+/// * `fee` - A `String` representing the fee associated with the transaction, to be casted to `Decimal`.
 /// * `description` - A `String` providing a description of the transaction.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RevolutTransactionOld {
     pub document_provider: String,
-    pub amount: String,  // To be casted to f64
-    pub balance: String, // To be casted to f64
+    #[serde(rename = "type", default)]
+    pub transaction_type: String,
+    pub amount: String,  // To be casted to Decimal
+    pub balance: String, // To be casted to Decimal
     pub product: String,
     pub state: String,
     pub started_date: String, // To be casted to i64
     pub currency: String,
     pub completed_date: String, // To be casted to i64
-    pub fee: String,            // To be casted to f64
+    pub fee: String,            // To be casted to Decimal
     pub description: String,
 }
 
@@ -43,47 +54,51 @@ impl RevolutTransactionOld {
     /// Converts an instance of `RevolutTransactionOld` to `RevolutTransactionTarget`.
     ///
     /// This method attempts to cast string fields to their respective types
-    /// (`f64` for numeric fields and `i64` for date fields) and returns a
+    /// (`Decimal` for money fields and `i64` for date fields) and returns a
     /// `RevolutTransactionTarget` if successful.
     ///
     /// # Returns
     ///
     /// A `Result` which is:
     /// - `Ok(RevolutTransactionTarget)` if all fields are successfully casted.
-    /// - `Err(String)` if any field fails to cast.
-    pub fn to_target(&self) -> Result<RevolutTransactionTarget, String> {
-        let mut amount_value: Value = Value::String(self.amount.clone());
-        try_cast_to_f64(&mut amount_value);
-        let amount: f64 = amount_value
-            .as_f64()
-            .ok_or("Failed to cast amount to f64")?;
-
-        let mut balance_value: Value = Value::String(self.balance.clone());
-        try_cast_to_f64(&mut balance_value);
-        let balance: f64 = balance_value
-            .as_f64()
-            .ok_or("Failed to cast balance to f64")?;
-
-        let mut fee_value: Value = Value::String(self.fee.clone());
-        try_cast_to_f64(&mut fee_value);
-        let fee = fee_value.as_f64().ok_or("Failed to cast fee to f64")?;
+    /// - `Err(CastError)` naming the first field that fails to cast.
+    pub fn to_target(&self) -> Result<RevolutTransactionTarget, CastError> {
+        let amount: Decimal =
+            try_cast_to_decimal(&self.amount).ok_or_else(|| {
+            CastError::field("amount", &self.amount, "Failed to cast amount to Decimal")
+        })?;
+
+        let balance: Decimal =
+            try_cast_to_decimal(&self.balance).ok_or_else(|| {
+            CastError::field("balance", &self.balance, "Failed to cast balance to Decimal")
+        })?;
+
+        let fee: Decimal = try_cast_to_decimal(&self.fee)
+            .ok_or_else(|| CastError::field("fee", &self.fee, "Failed to cast fee to Decimal"))?;
 
         let mut started_date_value: Value = Value::String(self.started_date.clone());
         try_cast_to_unix(&mut started_date_value);
         let started_date: i64 = started_date_value
             .as_i64()
-            .ok_or("Failed to cast started_date to i64")?;
+            .ok_or_else(|| {
+                CastError::field("started_date", &self.started_date, "Failed to cast started_date to i64")
+            })?;
 
         let mut completed_date_value: Value = Value::String(self.completed_date.clone());
         try_cast_to_unix(&mut completed_date_value);
         let completed_date: i64 = completed_date_value
             .as_i64()
-            .ok_or("Failed to cast completed_date to i64")?;
-
-        info!("self {:#?}", self);
+            .ok_or_else(|| {
+                CastError::field(
+                    "completed_date",
+                    &self.completed_date,
+                    "Failed to cast completed_date to i64",
+                )
+            })?;
 
         Ok(RevolutTransactionTarget {
             document_provider: self.document_provider.clone(),
+            transaction_type: self.transaction_type.clone(),
             amount,
             balance,
             product: self.product.clone(),
@@ -101,16 +116,19 @@ impl RevolutTransactionOld {
 ///
 /// This struct is used to deserialize and serialize transaction data
 /// from and to JSON format. It includes various fields that describe
-/// the details of a transaction with numeric and date fields casted
-/// to `f64` and `i64` respectively.
+/// the details of a transaction with money and date fields casted
+/// to `Decimal` and `i64` respectively.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct RevolutTransactionTarget {
-    /// A `String` representing the type of the transaction.
+    /// A `String` representing the provider the transaction was detected as.
     pub document_provider: String,
-    /// A `f64` representing the amount of the transaction.
-    pub amount: f64,
-    /// A `f64` representing the balance after the transaction.
-    pub balance: f64,
+    /// A `String` representing the type of the transaction, e.g. `TRANSFER` or `CARD_PAYMENT`.
+    #[serde(rename = "type")]
+    pub transaction_type: String,
+    /// A `Decimal` representing the amount of the transaction.
+    pub amount: Decimal,
+    /// A `Decimal` representing the balance after the transaction.
+    pub balance: Decimal,
     /// A `String` representing the product associated with the transaction.
     pub product: String,
     /// A `String` representing the state of the transaction.
@@ -121,8 +139,178 @@ pub struct RevolutTransactionTarget {
     pub currency: String,
     /// An `i64` representing the date when the transaction was completed.
     pub completed_date: i64,
-    /// A `f64` representing the fee associated with the transaction.
-    pub fee: f64,
+    /// A `Decimal` representing the fee associated with the transaction.
+    pub fee: Decimal,
     /// A `String` providing a description of the transaction.
     pub description: String,
 }
+
+impl RevolutTransactionTarget {
+    /// The fields holding amounts, formatted per the requested `MoneyFormat`.
+    pub const MONEY_FIELDS: [&'static str; 3] = ["amount", "balance", "fee"];
+    /// The fields holding dates, formatted per the requested `DateOutput`.
+    pub const DATE_FIELDS: [&'static str; 2] = ["started_date", "completed_date"];
+
+    /// Maps the Revolut transaction into the canonical `Transaction`.
+    ///
+    /// Revolut amounts are already signed. The `type` and `product` have no
+    /// canonical counterpart and are kept in `provider_fields`.
+    pub fn to_transaction(&self) -> Transaction {
+        let mut provider_fields: Map<String, Value> = Map::new();
+        provider_fields.insert("type".to_string(), Value::from(self.transaction_type.clone()));
+        provider_fields.insert("product".to_string(), Value::from(self.product.clone()));
+        provider_fields.insert("state".to_string(), Value::from(self.state.clone()));
+
+        Transaction {
+            document_provider: self.document_provider.clone(),
+            booking_date: self.started_date,
+            value_date: Some(self.completed_date),
+            amount: self.amount,
+            currency: self.currency.clone(),
+            counterparty_name: None,
+            counterparty_iban: None,
+            description: self.description.clone(),
+            reference: None,
+            balance_after: Some(self.balance),
+            fee: Some(self.fee),
+            status: TransactionStatus::from_provider_state(&self.state),
+            provider_fields,
+            raw: Map::new(),
+        }
+    }
+}
+
+/// The `ProviderCaster` of Revolut personal account CSV exports.
+pub struct RevolutCsvCaster;
+
+impl ProviderCaster for RevolutCsvCaster {
+    fn name(&self) -> &str {
+        "revolut_csv"
+    }
+
+    fn detect(&self, context: &DetectionContext) -> f32 {
+        let header: f32 = header_overlap(context, &SchemaKeys::Revolut.keys());
+        if header == 0.0 {
+            return 0.0;
+        }
+        let values: f32 = weighted_score(&[
+            (value_match_ratio(context, "started_date", ValuePattern::Date), 1.0),
+            (value_match_ratio(context, "amount", ValuePattern::Amount), 1.0),
+        ]);
+        let score: f32 = weighted_score(&[(header, 0.7), (values, 0.3)]);
+        (score + 0.1 * context.file_name_score(&["revolut"])).min(1.0)
+    }
+
+    fn number_format(&self) -> NumberFormat {
+        NumberFormat {
+            decimal_separator: Some(DecimalSeparator::Dot),
+            amount_columns: vec!["amount".to_string(), "balance".to_string(), "fee".to_string()],
+            indicator_column: None,
+        }
+    }
+
+    fn date_format(&self) -> DateFormat {
+        DateFormat {
+            formats: vec!["%Y-%m-%d %H:%M:%S".to_string()],
+            timezone: None,
+            date_columns: vec!["started_date".to_string(), "completed_date".to_string()],
+        }
+    }
+
+    fn cast_row(&self, row: &Map<String, Value>, shape: OutputShape) -> Result<Map<String, Value>, CastError> {
+        let revolut_transaction_old: RevolutTransactionOld = from_value(Value::Object(row.clone()))
+            .map_err(|e| CastError::new(format!("Failed to deserialize RevolutTransactionOld: {}", e)))?;
+
+        let revolut_transaction_target: RevolutTransactionTarget =
+            revolut_transaction_old.to_target()?;
+
+        match shape {
+            OutputShape::Canonical => to_object(&revolut_transaction_target.to_transaction()),
+            OutputShape::Provider => to_object(&revolut_transaction_target),
+        }
+        .map_err(CastError::from)
+    }
+
+    fn output_schema(&self, shape: OutputShape) -> OutputSchema {
+        match shape {
+            OutputShape::Canonical => OutputSchema::canonical(),
+            OutputShape::Provider => OutputSchema::new(
+                &RevolutTransactionTarget::MONEY_FIELDS,
+                &RevolutTransactionTarget::DATE_FIELDS,
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A normalized row of a Revolut export, as it reaches the caster.
+    fn row(amount: &str, state: &str) -> Map<String, Value> {
+        json!({
+            "document_provider": "revolut_csv",
+            "type": "CARD_PAYMENT",
+            "product": "Current",
+            "started_date": "2024-01-01T01:24:30+00:00",
+            "completed_date": "2024-01-02T09:00:00+00:00",
+            "description": "Albert Heijn",
+            "amount": amount,
+            "fee": "0.25",
+            "currency": "EUR",
+            "state": state,
+            "balance": "87.50"
+        })
+        .as_object()
+        .cloned()
+        .unwrap()
+    }
+
+    #[test]
+    fn casts_rows_into_canonical_transactions() {
+        let casted: Map<String, Value> = RevolutCsvCaster
+            .cast_row(&row("-12.50", "COMPLETED"), OutputShape::Canonical)
+            .unwrap();
+        let transaction: Transaction = from_value(Value::Object(casted)).unwrap();
+
+        assert_eq!(transaction.document_provider, "revolut_csv");
+        assert_eq!(transaction.booking_date, 1704072270);
+        assert_eq!(transaction.value_date, Some(1704186000));
+        assert_eq!(transaction.amount, Decimal::new(-1250, 2));
+        assert_eq!(transaction.balance_after, Some(Decimal::new(8750, 2)));
+        assert_eq!(transaction.fee, Some(Decimal::new(25, 2)));
+        assert_eq!(transaction.currency, "EUR");
+        assert_eq!(transaction.description, "Albert Heijn");
+        assert_eq!(transaction.status, TransactionStatus::Completed);
+        assert_eq!(transaction.counterparty_name, None);
+        assert_eq!(transaction.provider_fields["type"], "CARD_PAYMENT");
+        assert_eq!(transaction.provider_fields["product"], "Current");
+    }
+
+    #[test]
+    fn casts_rows_into_the_provider_shape() {
+        let casted: Map<String, Value> = RevolutCsvCaster
+            .cast_row(&row("-12.50", "PENDING"), OutputShape::Provider)
+            .unwrap();
+        let target: RevolutTransactionTarget = from_value(Value::Object(casted)).unwrap();
+
+        assert_eq!(target.transaction_type, "CARD_PAYMENT");
+        assert_eq!(target.state, "PENDING");
+        assert_eq!(target.started_date, 1704072270);
+        assert_eq!(target.amount, Decimal::new(-1250, 2));
+    }
+
+    #[test]
+    fn names_the_field_that_cant_be_cast() {
+        let error: CastError = RevolutCsvCaster
+            .cast_row(&row("n/a", "COMPLETED"), OutputShape::Canonical)
+            .unwrap_err();
+        assert_eq!(error.field.as_deref(), Some("amount"));
+        assert_eq!(error.raw_value.as_deref(), Some("n/a"));
+
+        let mut missing: Map<String, Value> = row("-12.50", "COMPLETED");
+        missing.remove("currency");
+        assert!(RevolutCsvCaster.cast_row(&missing, OutputShape::Canonical).is_err());
+    }
+}
@@ -0,0 +1,111 @@
+//! ## Diagnostics
+//!
+//! Casting never gives up on a whole document because of a single row. Rows
+//! that can't be cast are left out of the data and reported as errors, values
+//! that had to be left as they were are reported as warnings, so clients see
+//! exactly what went wrong and where.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Why a single row couldn't be cast.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CastError {
+    /// The source column or target field at fault, if known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    /// The value that couldn't be cast, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub raw_value: Option<String>,
+    /// A human readable reason.
+    pub reason: String,
+}
+
+impl CastError {
+    /// Creates an error that isn't tied to a single field.
+    pub fn new<S: Into<String>>(reason: S) -> CastError {
+        CastError {
+            field: None,
+            raw_value: None,
+            reason: reason.into(),
+        }
+    }
+
+    /// Creates an error for a field holding a value that couldn't be cast.
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - The source column or target field at fault.
+    /// * `raw_value` - The value as found in the row.
+    /// * `reason` - A human readable reason.
+    pub fn field<S: Into<String>>(field: &str, raw_value: &str, reason: S) -> CastError {
+        CastError {
+            field: Some(field.to_string()),
+            raw_value: Some(raw_value.to_string()),
+            reason: reason.into(),
+        }
+    }
+
+    /// Creates an error for a required field that is missing or empty.
+    pub fn missing(field: &str) -> CastError {
+        CastError {
+            field: Some(field.to_string()),
+            raw_value: None,
+            reason: "is missing".to_string(),
+        }
+    }
+}
+
+impl From<String> for CastError {
+    fn from(reason: String) -> CastError {
+        CastError::new(reason)
+    }
+}
+
+impl fmt::Display for CastError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.field, &self.raw_value) {
+            (Some(field), Some(raw_value)) => {
+                write!(f, "{}: '{}' {}", field, raw_value, self.reason)
+            }
+            (Some(field), None) => write!(f, "{}: {}", field, self.reason),
+            _ => write!(f, "{}", self.reason),
+        }
+    }
+}
+
+/// A problem found while casting a document.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// The zero-based index of the row in the parsed document, `None` for the document as a whole.
+    pub row: Option<usize>,
+    #[serde(flatten)]
+    pub error: CastError,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic for a single row.
+    pub fn row(row: usize, error: CastError) -> Diagnostic {
+        Diagnostic {
+            row: Some(row),
+            error,
+        }
+    }
+
+    /// Creates a diagnostic about the document as a whole.
+    pub fn document<S: Into<String>>(reason: S) -> Diagnostic {
+        Diagnostic {
+            row: None,
+            error: CastError::new(reason),
+        }
+    }
+}
+
+/// Every problem found while casting a document.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Diagnostics {
+    /// Values that were kept as they were, the rows themselves were cast.
+    pub warnings: Vec<Diagnostic>,
+    /// Rows that couldn't be cast and were left out of the data.
+    pub errors: Vec<Diagnostic>,
+}
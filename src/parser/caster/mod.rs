@@ -1,4 +1,6 @@
 pub mod number;
 pub mod time;
 pub mod caster_registry;
-
+pub mod diagnostic;
+pub mod transaction;
+pub mod money;
\ No newline at end of file
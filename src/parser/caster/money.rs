@@ -0,0 +1,281 @@
+//! ## Money
+//!
+//! Amounts are carried through the caster pipeline as exact [`Decimal`]s and
+//! are only rounded to the minor units of their currency when serialized as
+//! [`MoneyFormat::MinorUnits`].
+//! Totals and balance checks must use the helpers in this module instead of
+//! floating point arithmetic.
+
+use rust_decimal::Decimal;
+use serde_json::{Map, Value};
+use tracing::warn;
+
+// crate imports
+use crate::parser::options::MoneyFormat;
+
+/// Returns the number of minor units (decimal places) of an ISO 4217 currency.
+///
+/// # Arguments
+///
+/// * `currency` - The ISO 4217 currency code, e.g. `EUR`.
+///
+/// # Returns
+///
+/// The exponent of the currency, `2` for unknown currencies.
+pub fn minor_units(currency: &str) -> u32 {
+    match currency.trim().to_uppercase().as_str() {
+        "BIF" | "CLP" | "DJF" | "GNF" | "ISK" | "JPY" | "KMF" | "KRW" | "PYG" | "RWF" | "UGX"
+        | "UYI" | "VND" | "VUV" | "XAF" | "XOF" | "XPF" => 0,
+        "BHD" | "IQD" | "JOD" | "KWD" | "LYD" | "OMR" | "TND" => 3,
+        _ => 2,
+    }
+}
+
+/// Rounds an amount to the minor units of its currency using banker's rounding.
+///
+/// The scale of the result always equals the minor units, so `12.5 EUR`
+/// becomes `12.50`.
+///
+/// # Arguments
+///
+/// * `amount` - The amount to round.
+/// * `currency` - The ISO 4217 currency code of the amount.
+///
+/// # Returns
+///
+/// The rounded `Decimal`.
+pub fn round_to_minor_units(amount: Decimal, currency: &str) -> Decimal {
+    let mut rounded: Decimal = amount.round_dp(minor_units(currency));
+    rounded.rescale(minor_units(currency));
+    rounded
+}
+
+/// Pads an amount to at least the minor units of its currency without rounding it.
+///
+/// `12.5 EUR` becomes `12.50`, while `0.125 EUR` keeps its third decimal.
+///
+/// # Arguments
+///
+/// * `amount` - The amount to pad.
+/// * `currency` - The ISO 4217 currency code of the amount.
+///
+/// # Returns
+///
+/// The `Decimal` with a scale of at least the minor units, equal to `amount`.
+pub fn pad_to_minor_units(amount: Decimal, currency: &str) -> Decimal {
+    let mut padded: Decimal = amount;
+    if padded.scale() < minor_units(currency) {
+        padded.rescale(minor_units(currency));
+    }
+    padded
+}
+
+/// Converts an amount to an integer number of minor units, e.g. `12.50 EUR` to `1250`.
+///
+/// # Arguments
+///
+/// * `amount` - The amount to convert.
+/// * `currency` - The ISO 4217 currency code of the amount.
+///
+/// # Returns
+///
+/// The amount in minor units, or `None` if it doesn't fit in an `i64`.
+pub fn to_minor_units(amount: Decimal, currency: &str) -> Option<i64> {
+    i64::try_from(round_to_minor_units(amount, currency).mantissa()).ok()
+}
+
+/// Converts an integer number of minor units back into an amount, e.g. `1250` to `12.50 EUR`.
+///
+/// # Arguments
+///
+/// * `minor` - The amount in minor units.
+/// * `currency` - The ISO 4217 currency code of the amount.
+///
+/// # Returns
+///
+/// The amount as a `Decimal`.
+pub fn from_minor_units(minor: i64, currency: &str) -> Decimal {
+    Decimal::new(minor, minor_units(currency))
+}
+
+/// Sums amounts exactly.
+///
+/// # Arguments
+///
+/// * `amounts` - The amounts to sum.
+///
+/// # Returns
+///
+/// The exact total, or `None` if the sum overflows.
+pub fn checked_sum<I: IntoIterator<Item = Decimal>>(amounts: I) -> Option<Decimal> {
+    amounts
+        .into_iter()
+        .try_fold(Decimal::ZERO, |total, amount| total.checked_add(amount))
+}
+
+/// Checks that an opening balance plus the movements equals the closing balance.
+///
+/// # Arguments
+///
+/// * `opening` - The opening balance.
+/// * `movements` - The signed amounts booked between the two balances.
+/// * `closing` - The closing balance.
+///
+/// # Returns
+///
+/// `true` when the balances reconcile to the cent.
+pub fn balances_reconcile<I: IntoIterator<Item = Decimal>>(
+    opening: Decimal,
+    movements: I,
+    closing: Decimal,
+) -> bool {
+    match checked_sum(movements).and_then(|total| opening.checked_add(total)) {
+        Some(computed) => computed == closing,
+        None => false,
+    }
+}
+
+/// Serializes an amount in the requested `MoneyFormat`.
+///
+/// # Arguments
+///
+/// * `amount` - The amount to serialize.
+/// * `currency` - The ISO 4217 currency code of the amount.
+/// * `format` - Whether to emit an exact decimal string or an integer in minor units.
+///
+/// # Returns
+///
+/// A JSON string such as `"12.50"` or a JSON integer such as `1250`. Only minor
+/// units are rounded, a string keeps every decimal of the amount.
+pub fn format_money(amount: Decimal, currency: &str, format: MoneyFormat) -> Value {
+    match format {
+        MoneyFormat::String => Value::String(pad_to_minor_units(amount, currency).to_string()),
+        MoneyFormat::MinorUnits => match to_minor_units(amount, currency) {
+            Some(minor) => Value::from(minor),
+            None => {
+                warn!(
+                    "Amount {} does not fit in minor units, keeping it as a string",
+                    amount
+                );
+                Value::String(amount.to_string())
+            }
+        },
+    }
+}
+
+/// Rewrites the money fields of a serialized transaction in the requested `MoneyFormat`.
+///
+/// Fields that are missing, `null` or not a decimal are left untouched.
+///
+/// # Arguments
+///
+/// * `obj_map` - The serialized transaction.
+/// * `money_fields` - The keys holding amounts.
+/// * `format` - The requested `MoneyFormat`.
+pub fn format_money_fields(
+    obj_map: &mut Map<String, Value>,
+    money_fields: &[&str],
+    format: MoneyFormat,
+) {
+    let currency: String = obj_map
+        .get("currency")
+        .and_then(|currency| currency.as_str())
+        .unwrap_or_default()
+        .to_string();
+
+    for key in money_fields {
+        let Some(value) = obj_map.get_mut(*key) else {
+            continue;
+        };
+        let amount: Option<Decimal> = match value {
+            Value::String(str_value) => str_value.parse::<Decimal>().ok(),
+            Value::Number(number) => number.to_string().parse::<Decimal>().ok(),
+            _ => None,
+        };
+        if let Some(amount) = amount {
+            *value = format_money(amount, &currency, format);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::caster::transaction::Transaction;
+    use serde_json::json;
+    use std::str::FromStr;
+
+    fn decimal(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn rounds_to_the_minor_units_of_the_currency() {
+        assert_eq!(round_to_minor_units(decimal("12.5"), "EUR").to_string(), "12.50");
+        // banker's rounding
+        assert_eq!(round_to_minor_units(decimal("0.125"), "EUR").to_string(), "0.12");
+        assert_eq!(round_to_minor_units(decimal("0.135"), "eur").to_string(), "0.14");
+        assert_eq!(round_to_minor_units(decimal("1234.5"), "JPY").to_string(), "1234");
+        assert_eq!(round_to_minor_units(decimal("1.5"), "KWD").to_string(), "1.500");
+    }
+
+    #[test]
+    fn pads_without_rounding() {
+        assert_eq!(pad_to_minor_units(decimal("12.5"), "EUR").to_string(), "12.50");
+        assert_eq!(pad_to_minor_units(decimal("0.125"), "EUR").to_string(), "0.125");
+        assert_eq!(pad_to_minor_units(decimal("1234.5"), "JPY").to_string(), "1234.5");
+        assert_eq!(pad_to_minor_units(decimal("-3"), "KWD").to_string(), "-3.000");
+    }
+
+    #[test]
+    fn strings_are_exact_and_minor_units_rounded() {
+        assert_eq!(format_money(decimal("0.125"), "EUR", MoneyFormat::String), "0.125");
+        assert_eq!(format_money(decimal("0.125"), "EUR", MoneyFormat::MinorUnits), 12);
+        assert_eq!(format_money(decimal("1234.5"), "JPY", MoneyFormat::String), "1234.5");
+        assert_eq!(format_money(decimal("1234.5"), "JPY", MoneyFormat::MinorUnits), 1234);
+    }
+
+    #[test]
+    fn converts_to_and_from_minor_units() {
+        assert_eq!(to_minor_units(decimal("12.50"), "EUR"), Some(1250));
+        assert_eq!(to_minor_units(decimal("-0.01"), "EUR"), Some(-1));
+        assert_eq!(to_minor_units(decimal("1250"), "JPY"), Some(1250));
+        assert_eq!(to_minor_units(Decimal::MAX, "EUR"), None);
+        assert_eq!(from_minor_units(1250, "EUR"), decimal("12.50"));
+        assert_eq!(from_minor_units(1250, "BHD"), decimal("1.250"));
+    }
+
+    #[test]
+    fn sums_and_reconciles_exactly() {
+        let movements: Vec<Decimal> = vec![decimal("0.1"), decimal("0.2"), decimal("-12.50")];
+        assert_eq!(checked_sum(movements.clone()), Some(decimal("-12.2")));
+        assert!(balances_reconcile(decimal("1000.00"), movements.clone(), decimal("987.80")));
+        assert!(!balances_reconcile(decimal("1000.00"), movements, decimal("987.81")));
+        assert_eq!(checked_sum(vec![Decimal::MAX, Decimal::ONE]), None);
+    }
+
+    #[test]
+    fn formats_money_fields() {
+        let mut transaction: Map<String, Value> = json!({
+            "amount": "-12.5",
+            "balance_after": 100,
+            "fee": null,
+            "currency": "EUR",
+            "description": "12.5",
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+
+        let mut as_string: Map<String, Value> = transaction.clone();
+        format_money_fields(&mut as_string, &Transaction::MONEY_FIELDS, MoneyFormat::String);
+        assert_eq!(as_string["amount"], "-12.50");
+        assert_eq!(as_string["balance_after"], "100.00");
+        assert_eq!(as_string["fee"], Value::Null);
+
+        format_money_fields(&mut transaction, &Transaction::MONEY_FIELDS, MoneyFormat::MinorUnits);
+        assert_eq!(transaction["amount"], -1250);
+        assert_eq!(transaction["balance_after"], 10000);
+        assert_eq!(transaction["description"], "12.5");
+    }
+}
@@ -0,0 +1,83 @@
+//! ## Debit/credit indicators
+//!
+//! Banks such as ING and Rabobank export unsigned amounts next to a column
+//! telling whether money left (`Af`, `Debit`, `D`) or entered (`Bij`,
+//! `Credit`, `C`) the account.
+
+use rust_decimal::Decimal;
+
+/// Whether a transaction debits or credits the account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebitCredit {
+    Debit,
+    Credit,
+}
+
+impl DebitCredit {
+    /// Parses a debit/credit indicator in Dutch, English, German, French or Swedish.
+    ///
+    /// # Arguments
+    ///
+    /// * `indicator` - The indicator as written in the source document, matched case-insensitively.
+    ///
+    /// # Returns
+    ///
+    /// The `DebitCredit`, or `None` when the indicator isn't recognised.
+    pub fn from_indicator(indicator: &str) -> Option<DebitCredit> {
+        match indicator.trim().to_lowercase().as_str() {
+            "af" | "d" | "dr" | "debit" | "debet" | "soll" | "s" | "-" | "uttag" => {
+                Some(DebitCredit::Debit)
+            }
+            "bij" | "c" | "cr" | "credit" | "haben" | "h" | "crédit" | "+" | "insättning" => {
+                Some(DebitCredit::Credit)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Signs an amount according to its debit/credit indicator.
+///
+/// The sign already on the amount is ignored: the indicator wins.
+///
+/// # Arguments
+///
+/// * `amount` - The amount, usually unsigned.
+/// * `indicator` - The indicator as written in the source document.
+///
+/// # Returns
+///
+/// The signed amount, or `None` when the indicator isn't recognised.
+pub fn apply_indicator(amount: Decimal, indicator: &str) -> Option<Decimal> {
+    match DebitCredit::from_indicator(indicator)? {
+        DebitCredit::Debit => Some(-amount.abs()),
+        DebitCredit::Credit => Some(amount.abs()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn recognises_indicators_in_every_language() {
+        for debit in ["Af", "AF", " af ", "D", "Debit", "Debet", "Soll", "Uttag", "-"] {
+            assert_eq!(DebitCredit::from_indicator(debit), Some(DebitCredit::Debit), "{}", debit);
+        }
+        for credit in ["Bij", "bij", "C", "Credit", "Crédit", "Haben", "Insättning", "+"] {
+            assert_eq!(DebitCredit::from_indicator(credit), Some(DebitCredit::Credit), "{}", credit);
+        }
+        assert_eq!(DebitCredit::from_indicator(""), None);
+        assert_eq!(DebitCredit::from_indicator("Overboeking"), None);
+    }
+
+    #[test]
+    fn the_indicator_wins_over_the_sign() {
+        let amount: Decimal = Decimal::from_str("12.50").unwrap();
+        assert_eq!(apply_indicator(amount, "Af"), Some(-amount));
+        assert_eq!(apply_indicator(-amount, "Af"), Some(-amount));
+        assert_eq!(apply_indicator(-amount, "Bij"), Some(amount));
+        assert_eq!(apply_indicator(amount, "?"), None);
+    }
+}
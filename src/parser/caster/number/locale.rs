@@ -0,0 +1,326 @@
+//! ## Locale-aware amounts
+//!
+//! European bank exports write amounts as `1.234,56`, `-12,50`, `€ 12,50`,
+//! `12.50-` or `(12.50)`. This module turns those into exact decimals, either
+//! with a known decimal separator or by inferring it from the value or from
+//! the whole column.
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Currency symbols and codes stripped from amounts before parsing.
+const CURRENCY_MARKERS: [&str; 12] = [
+    "EUR", "USD", "GBP", "SEK", "CHF", "DKK", "NOK", "PLN", "€", "$", "£", "kr",
+];
+
+/// The decimal separator of a number locale.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DecimalSeparator {
+    /// `1,234.56`, used by English speaking countries and most exports in ISO format.
+    Dot,
+    /// `1.234,56`, used by Dutch, Belgian, German, French and Swedish banks.
+    Comma,
+}
+
+impl DecimalSeparator {
+    fn as_char(&self) -> char {
+        match self {
+            DecimalSeparator::Dot => '.',
+            DecimalSeparator::Comma => ',',
+        }
+    }
+
+    fn thousands(&self) -> char {
+        match self {
+            DecimalSeparator::Dot => ',',
+            DecimalSeparator::Comma => '.',
+        }
+    }
+}
+
+/// Parses a locale-formatted amount.
+///
+/// Currency symbols, whitespace (including non-breaking spaces) and
+/// apostrophe thousands separators are ignored. A leading or trailing `-` and
+/// surrounding parentheses make the amount negative. Thousands separators must
+/// group the integer part in runs of three digits, `1.23,45` isn't an amount.
+///
+/// # Arguments
+///
+/// * `raw` - The amount as written in the source document.
+/// * `separator` - The decimal separator, inferred from the value when `None`.
+///
+/// # Returns
+///
+/// The parsed `Decimal`, or `None` if the value isn't an amount.
+pub fn parse_amount(raw: &str, separator: Option<DecimalSeparator>) -> Option<Decimal> {
+    let mut value: String = raw.replace('\u{2212}', "-");
+    for marker in CURRENCY_MARKERS {
+        value = value.replace(marker, "");
+    }
+    value.retain(|c| !c.is_whitespace() && c != '\'' && c != '\u{00A0}');
+
+    let mut negative: bool = false;
+    if value.starts_with('(') && value.ends_with(')') {
+        negative = true;
+        value = value[1..value.len() - 1].to_string();
+    }
+    if let Some(rest) = value.strip_prefix('-') {
+        negative = !negative;
+        value = rest.to_string();
+    } else if let Some(rest) = value.strip_suffix('-') {
+        negative = !negative;
+        value = rest.to_string();
+    }
+    let value: &str = value
+        .strip_prefix('+')
+        .or_else(|| value.strip_suffix('+'))
+        .unwrap_or(&value);
+
+    if value.is_empty()
+        || !value
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == '.' || c == ',')
+    {
+        return None;
+    }
+
+    let separator: Option<DecimalSeparator> = separator.or_else(|| infer_separator(value));
+    let normalized: String = match separator {
+        Some(separator) => {
+            if !is_grouped_in_thousands(value, separator) {
+                return None;
+            }
+            value
+                .replace(separator.thousands(), "")
+                .replace(separator.as_char(), ".")
+        }
+        // no separator at all, a plain integer
+        None => value.to_string(),
+    };
+
+    let amount: Decimal = Decimal::from_str(&normalized).ok()?;
+    Some(if negative { -amount } else { amount })
+}
+
+/// Checks that the thousands separators of a value only group its integer
+/// part, in runs of three digits after a leading group of one to three.
+fn is_grouped_in_thousands(value: &str, separator: DecimalSeparator) -> bool {
+    let thousands: char = separator.thousands();
+    let (integer_part, fraction): (&str, &str) = value
+        .split_once(separator.as_char())
+        .unwrap_or((value, ""));
+    if fraction.contains(thousands) {
+        return false;
+    }
+    if !integer_part.contains(thousands) {
+        return true;
+    }
+
+    let groups: Vec<&str> = integer_part.split(thousands).collect();
+    (1..=3).contains(&groups[0].len()) && groups[1..].iter().all(|group| group.len() == 3)
+}
+
+/// Infers the decimal separator from a single value.
+///
+/// # Arguments
+///
+/// * `value` - The amount, stripped of signs and currency markers.
+///
+/// # Returns
+///
+/// The decimal separator if the value gives it away, `None` when it holds
+/// no separator. A single separator followed by exactly three digits is read
+/// as a thousands separator unless the integer part is `0`.
+pub fn infer_separator(value: &str) -> Option<DecimalSeparator> {
+    let last_dot: Option<usize> = value.rfind('.');
+    let last_comma: Option<usize> = value.rfind(',');
+
+    match (last_dot, last_comma) {
+        (Some(dot), Some(comma)) if dot > comma => Some(DecimalSeparator::Dot),
+        (Some(_), Some(_)) => Some(DecimalSeparator::Comma),
+        (Some(dot), None) => Some(single_separator(value, dot, DecimalSeparator::Dot)),
+        (None, Some(comma)) => Some(single_separator(value, comma, DecimalSeparator::Comma)),
+        (None, None) => None,
+    }
+}
+
+/// Decides whether the only kind of separator in a value is the decimal one.
+fn single_separator(value: &str, position: usize, candidate: DecimalSeparator) -> DecimalSeparator {
+    let occurrences: usize = value.matches(candidate.as_char()).count();
+    let fraction_digits: usize = value.len() - position - 1;
+    let integer_part: &str = &value[..position];
+
+    let is_thousands: bool = occurrences > 1
+        || (fraction_digits == 3 && !integer_part.is_empty() && integer_part != "0");
+
+    match (is_thousands, candidate) {
+        (true, DecimalSeparator::Dot) => DecimalSeparator::Comma,
+        (true, DecimalSeparator::Comma) => DecimalSeparator::Dot,
+        (false, _) => candidate,
+    }
+}
+
+/// Infers the decimal separator of a whole column.
+///
+/// Only unambiguous values vote: values holding both separators, a separator
+/// followed by one or two digits, or a repeated separator. Ambiguous values
+/// such as `1.234` are skipped so a single one can't mislead the column.
+///
+/// # Arguments
+///
+/// * `values` - The raw values of the column.
+///
+/// # Returns
+///
+/// The decimal separator with the most votes, `None` when no value is conclusive.
+pub fn infer_column_separator<'a, I: IntoIterator<Item = &'a str>>(
+    values: I,
+) -> Option<DecimalSeparator> {
+    let mut dot_votes: usize = 0;
+    let mut comma_votes: usize = 0;
+
+    for value in values {
+        let value: String = value
+            .chars()
+            .filter(|c| c.is_ascii_digit() || *c == '.' || *c == ',')
+            .collect();
+        let dots: usize = value.matches('.').count();
+        let commas: usize = value.matches(',').count();
+
+        let vote: Option<DecimalSeparator> = match (dots, commas) {
+            (0, 0) => None,
+            (_, 0) if dots > 1 => Some(DecimalSeparator::Comma),
+            (0, _) if commas > 1 => Some(DecimalSeparator::Dot),
+            (1, 0) | (0, 1) => {
+                let position: usize = value.rfind(['.', ',']).unwrap_or_default();
+                match value.len() - position - 1 {
+                    1 | 2 => infer_separator(&value),
+                    _ => None,
+                }
+            }
+            _ => infer_separator(&value),
+        };
+
+        match vote {
+            Some(DecimalSeparator::Dot) => dot_votes += 1,
+            Some(DecimalSeparator::Comma) => comma_votes += 1,
+            None => {}
+        }
+    }
+
+    match (dot_votes, comma_votes) {
+        (0, 0) => None,
+        (dot, comma) if dot >= comma => Some(DecimalSeparator::Dot),
+        _ => Some(DecimalSeparator::Comma),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(raw: &str) -> Option<String> {
+        parse_amount(raw, None).map(|amount| amount.to_string())
+    }
+
+    #[test]
+    fn parses_both_notations() {
+        assert_eq!(amount("1.234,56").as_deref(), Some("1234.56"));
+        assert_eq!(amount("1,234.56").as_deref(), Some("1234.56"));
+        assert_eq!(amount("1.234.567,89").as_deref(), Some("1234567.89"));
+        assert_eq!(amount("1'234.56").as_deref(), Some("1234.56"));
+        assert_eq!(amount("1\u{00A0}234,56").as_deref(), Some("1234.56"));
+        assert_eq!(amount("42").as_deref(), Some("42"));
+    }
+
+    #[test]
+    fn parses_signs() {
+        assert_eq!(amount("-12,50").as_deref(), Some("-12.50"));
+        assert_eq!(amount("12.50-").as_deref(), Some("-12.50"));
+        assert_eq!(amount("(12.50)").as_deref(), Some("-12.50"));
+        assert_eq!(amount("\u{2212}12,50").as_deref(), Some("-12.50"));
+        assert_eq!(amount("+12,50").as_deref(), Some("12.50"));
+    }
+
+    #[test]
+    fn strips_currency_markers() {
+        assert_eq!(amount("€ 12,50").as_deref(), Some("12.50"));
+        assert_eq!(amount("12,50 EUR").as_deref(), Some("12.50"));
+        assert_eq!(amount("-€1.234,56").as_deref(), Some("-1234.56"));
+        assert_eq!(amount("$1,234.56").as_deref(), Some("1234.56"));
+        assert_eq!(amount("1 234,50 kr").as_deref(), Some("1234.50"));
+    }
+
+    #[test]
+    fn a_single_separator_before_three_digits_groups_thousands() {
+        assert_eq!(amount("1.234").as_deref(), Some("1234"));
+        assert_eq!(amount("1,234").as_deref(), Some("1234"));
+        assert_eq!(amount("0.234").as_deref(), Some("0.234"));
+        assert_eq!(amount(".234").as_deref(), Some("0.234"));
+        assert_eq!(amount("12.5").as_deref(), Some("12.5"));
+        // a known separator settles it
+        assert_eq!(
+            parse_amount("1.234", Some(DecimalSeparator::Dot)).map(|a| a.to_string()).as_deref(),
+            Some("1.234")
+        );
+        assert_eq!(
+            parse_amount("1.234", Some(DecimalSeparator::Comma)).map(|a| a.to_string()).as_deref(),
+            Some("1234")
+        );
+    }
+
+    #[test]
+    fn thousands_separators_must_group_three_digits() {
+        let comma = |raw: &str| parse_amount(raw, Some(DecimalSeparator::Comma)).map(|a| a.to_string());
+        let dot = |raw: &str| parse_amount(raw, Some(DecimalSeparator::Dot)).map(|a| a.to_string());
+
+        assert_eq!(comma("1.234.567,89").as_deref(), Some("1234567.89"));
+        assert_eq!(comma("1.23,45"), None);
+        assert_eq!(comma("1234.567,8"), None);
+        assert_eq!(comma(".123,45"), None);
+        assert_eq!(comma("12,34.5"), None);
+        assert_eq!(dot("1,234,567.89").as_deref(), Some("1234567.89"));
+        assert_eq!(dot("12,34"), None);
+        assert_eq!(dot("1,2345.00"), None);
+        // an inferred separator is held to the same rule
+        assert_eq!(amount("12.34.5"), None);
+    }
+
+    #[test]
+    fn rejects_values_that_are_not_amounts() {
+        assert_eq!(amount(""), None);
+        assert_eq!(amount("EUR"), None);
+        assert_eq!(amount("n/a"), None);
+        assert_eq!(amount("12a"), None);
+        assert_eq!(amount("1.2.3,4,5"), None);
+    }
+
+    #[test]
+    fn infers_the_separator_of_a_value() {
+        assert_eq!(infer_separator("1.234,56"), Some(DecimalSeparator::Comma));
+        assert_eq!(infer_separator("1,234.56"), Some(DecimalSeparator::Dot));
+        assert_eq!(infer_separator("12,5"), Some(DecimalSeparator::Comma));
+        assert_eq!(infer_separator("1.234.567"), Some(DecimalSeparator::Comma));
+        assert_eq!(infer_separator("1,234"), Some(DecimalSeparator::Dot));
+        assert_eq!(infer_separator("0,234"), Some(DecimalSeparator::Comma));
+        assert_eq!(infer_separator("1234"), None);
+    }
+
+    #[test]
+    fn infers_the_separator_of_a_column() {
+        assert_eq!(
+            infer_column_separator(["1.234", "-12,50", "7"]),
+            Some(DecimalSeparator::Comma)
+        );
+        assert_eq!(
+            infer_column_separator(["1,234", "€ 12.50", "1,000,000"]),
+            Some(DecimalSeparator::Dot)
+        );
+        // ambiguous values don't vote
+        assert_eq!(infer_column_separator(["1.234", "5.678", "9"]), None);
+        assert_eq!(infer_column_separator(Vec::<&str>::new()), None);
+    }
+}
@@ -0,0 +1,225 @@
+pub mod indicator;
+pub mod locale;
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+use tracing::{error, info, warn};
+
+// crate imports
+use crate::parser::caster::diagnostic::Diagnostic;
+use crate::parser::caster::number::indicator::apply_indicator;
+use crate::parser::caster::number::locale::{infer_column_separator, parse_amount, DecimalSeparator};
+
+/// A column holding a debit/credit indicator and the amount column it signs.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IndicatorColumn {
+    /// The column holding `Af`/`Bij`, `Debit`/`Credit` and the like.
+    pub indicator: String,
+    /// The column holding the unsigned amount.
+    pub amount: String,
+}
+
+/// How a provider writes its amounts.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct NumberFormat {
+    /// The decimal separator, inferred per column when `None`.
+    #[serde(default)]
+    pub decimal_separator: Option<DecimalSeparator>,
+    /// The columns holding amounts.
+    #[serde(default)]
+    pub amount_columns: Vec<String>,
+    /// The debit/credit indicator column, for providers exporting unsigned amounts.
+    #[serde(default)]
+    pub indicator_column: Option<IndicatorColumn>,
+}
+
+/// Normalizes the amount columns of a JSON array of objects into plain decimal strings.
+///
+/// Every amount is rewritten as `-1234.56`, so the casters only have to deal
+/// with a single notation. When the `NumberFormat` has no decimal separator it
+/// is inferred per column. A column whose values are all ambiguous, such as
+/// `1.234`, is read with a dot and reported in the returned warnings. Values
+/// that can't be parsed are left untouched and fail later in the caster.
+///
+/// # Arguments
+///
+/// * `data` - A mutable reference to a JSON array of objects with string values.
+/// * `format` - The `NumberFormat` of the provider.
+///
+/// # Returns
+///
+/// A document `Diagnostic` for every column whose decimal separator had to be assumed.
+pub fn normalize_amount_columns(data: &mut Value, format: &NumberFormat) -> Vec<Diagnostic> {
+    let mut warnings: Vec<Diagnostic> = vec![];
+    let Some(array) = data.as_array_mut() else {
+        error!("Provided data is not an array.");
+        return warnings;
+    };
+
+    for column in &format.amount_columns {
+        let values: Vec<&str> = array
+            .iter()
+            .filter_map(|row| row.get(column).and_then(|value| value.as_str()))
+            .collect();
+        let separator: DecimalSeparator = match format
+            .decimal_separator
+            .or_else(|| infer_column_separator(values.iter().copied()))
+        {
+            Some(separator) => separator,
+            None => {
+                if values.iter().any(|value| value.contains(['.', ','])) {
+                    warn!("No conclusive decimal separator in column '{}', assuming a dot", column);
+                    warnings.push(Diagnostic::document(format!(
+                        "The decimal separator of column '{}' is ambiguous, its amounts are read with a dot",
+                        column
+                    )));
+                }
+                DecimalSeparator::Dot
+            }
+        };
+        info!("Using decimal separator {:?} for column '{}'", separator, column);
+
+        for row in array.iter_mut() {
+            let Some(value) = row.get_mut(column) else {
+                continue;
+            };
+            let Some(str_value) = value.as_str() else {
+                continue;
+            };
+            if str_value.trim().is_empty() {
+                continue;
+            }
+            match parse_amount(str_value, Some(separator)) {
+                Some(amount) => *value = Value::String(amount.to_string()),
+                None => warn!("Failed to parse '{}' in column '{}' as an amount", str_value, column),
+            }
+        }
+    }
+
+    if let Some(indicator_column) = &format.indicator_column {
+        for row in array.iter_mut() {
+            let Some(obj_map) = row.as_object_mut() else {
+                continue;
+            };
+            let indicator: String = obj_map
+                .get(&indicator_column.indicator)
+                .and_then(|value| value.as_str())
+                .unwrap_or_default()
+                .to_string();
+            let Some(value) = obj_map.get_mut(&indicator_column.amount) else {
+                continue;
+            };
+            let signed: Option<Decimal> = value
+                .as_str()
+                .and_then(try_cast_to_decimal)
+                .and_then(|amount| apply_indicator(amount, &indicator));
+            match signed {
+                Some(amount) => *value = Value::String(amount.to_string()),
+                None => warn!(
+                    "Failed to apply debit/credit indicator '{}' to column '{}'",
+                    indicator, indicator_column.amount
+                ),
+            }
+        }
+    }
+    warnings
+}
+
+/// Attempts to cast a string to an exact `Decimal`.
+///
+/// # Arguments
+///
+/// * `str_value` - A string slice holding a plain decimal such as `-12.50` or `1e3`.
+///
+/// # Returns
+///
+/// The parsed `Decimal`, or `None` if the string isn't a number.
+pub fn try_cast_to_decimal(str_value: &str) -> Option<Decimal> {
+    let trimmed: &str = str_value.trim();
+    Decimal::from_str(trimmed)
+        .or_else(|_| Decimal::from_scientific(trimmed))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn casts_plain_decimals() {
+        assert_eq!(try_cast_to_decimal(" -12.50 "), Decimal::from_str("-12.50").ok());
+        assert_eq!(try_cast_to_decimal("1e3"), Decimal::from_str("1000").ok());
+        assert_eq!(try_cast_to_decimal("12,50"), None);
+        assert_eq!(try_cast_to_decimal(""), None);
+    }
+
+    #[test]
+    fn normalizes_amount_columns_per_column() {
+        let mut data: Value = json!([
+            { "bedrag": "1.234", "saldo": "1,234.56" },
+            { "bedrag": "-12,50", "saldo": "(12.50)" },
+            { "bedrag": "", "saldo": "n/a" },
+        ]);
+        let format: NumberFormat = NumberFormat {
+            amount_columns: vec!["bedrag".to_string(), "saldo".to_string()],
+            ..NumberFormat::default()
+        };
+        let warnings: Vec<Diagnostic> = normalize_amount_columns(&mut data, &format);
+
+        assert!(warnings.is_empty());
+        // `-12,50` gives the comma away, so `1.234` is read with thousands separators
+        assert_eq!(data[0]["bedrag"], "1234");
+        assert_eq!(data[1]["bedrag"], "-12.50");
+        assert_eq!(data[2]["bedrag"], "");
+        assert_eq!(data[0]["saldo"], "1234.56");
+        assert_eq!(data[1]["saldo"], "-12.50");
+        assert_eq!(data[2]["saldo"], "n/a");
+    }
+
+    #[test]
+    fn ambiguous_columns_are_read_with_a_dot_and_reported() {
+        let mut data: Value = json!([
+            { "amount": "1.234", "fee": "7" },
+            { "amount": "5.678", "fee": "12" },
+        ]);
+        let format: NumberFormat = NumberFormat {
+            amount_columns: vec!["amount".to_string(), "fee".to_string()],
+            ..NumberFormat::default()
+        };
+        let warnings: Vec<Diagnostic> = normalize_amount_columns(&mut data, &format);
+
+        assert_eq!(data[0]["amount"], "1.234");
+        assert_eq!(data[1]["amount"], "5.678");
+        assert_eq!(data[1]["fee"], "12");
+        // integers need no separator, only the amount column is reported
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].row, None);
+        assert!(warnings[0].error.reason.contains("'amount'"));
+    }
+
+    #[test]
+    fn signs_amounts_with_their_indicator() {
+        let mut data: Value = json!([
+            { "af_bij": "Af", "bedrag": "12,50" },
+            { "af_bij": "Bij", "bedrag": "1.000,00" },
+            { "af_bij": "?", "bedrag": "1,00" },
+        ]);
+        let format: NumberFormat = NumberFormat {
+            decimal_separator: Some(DecimalSeparator::Comma),
+            amount_columns: vec!["bedrag".to_string()],
+            indicator_column: Some(IndicatorColumn {
+                indicator: "af_bij".to_string(),
+                amount: "bedrag".to_string(),
+            }),
+        };
+        normalize_amount_columns(&mut data, &format);
+
+        assert_eq!(data[0]["bedrag"], "-12.50");
+        assert_eq!(data[1]["bedrag"], "1000.00");
+        // an unknown indicator leaves the amount unsigned
+        assert_eq!(data[2]["bedrag"], "1.00");
+    }
+}
@@ -1,6 +1,63 @@
-use chrono::{NaiveDateTime, ParseError};
-use serde_json::Value;
-use tracing::{error, info};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveDateTime, ParseError, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tracing::{error, info, warn};
+
+// crate imports
+use crate::parser::options::DateOutput;
+
+/// The formats tried, in order, when a provider doesn't declare its own or a
+/// column has to be inferred. Day-first formats come before month-first ones
+/// since Aurora mostly sees European exports. A `%Y` format only matches a
+/// four digit year, so two digit years fall through to the `%y` formats.
+pub const KNOWN_DATE_FORMATS: [&str; 18] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%d",
+    "%Y%m%d",
+    "%d-%m-%Y %H:%M:%S",
+    "%d-%m-%Y",
+    "%d-%m-%y",
+    "%d/%m/%Y %H:%M:%S",
+    "%d/%m/%Y %H:%M",
+    "%d/%m/%Y",
+    "%d/%m/%y",
+    "%d.%m.%Y",
+    "%d.%m.%y",
+    "%y%m%d",
+    "%m/%d/%Y %H:%M:%S",
+    "%m/%d/%Y",
+    "%Y/%m/%d",
+];
+
+/// How a provider writes its dates.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct DateFormat {
+    /// The `chrono` formats the provider uses, tried in order. Inferred per column when empty.
+    #[serde(default)]
+    pub formats: Vec<String>,
+    /// The IANA timezone the dates are written in, e.g. `Europe/Amsterdam`. Defaults to UTC.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// The columns holding dates.
+    #[serde(default)]
+    pub date_columns: Vec<String>,
+}
+
+impl DateFormat {
+    /// Returns the timezone of the provider, UTC when unset or unknown.
+    pub fn tz(&self) -> Tz {
+        match self.timezone.as_deref() {
+            Some(name) => name.parse::<Tz>().unwrap_or_else(|_| {
+                warn!("Unknown timezone '{}', falling back to UTC", name);
+                Tz::UTC
+            }),
+            None => Tz::UTC,
+        }
+    }
+}
 
 /// Attempts to cast a string value to Unix time seconds and updates the JSON value if successful.
 ///
@@ -30,7 +87,8 @@ pub fn try_cast_to_unix(value: &mut Value) {
 ///
 /// # Arguments
 ///
-/// * `date_str` - A string slice that holds the date in "YYYY-MM-DD HH:MM:SS" format.
+/// * `date_str` - A string slice that holds the date as RFC 3339 (as written by
+///   `normalize_date_columns`) or in "YYYY-MM-DD HH:MM:SS" format, taken as UTC.
 ///
 /// # Returns
 ///
@@ -38,6 +96,321 @@ pub fn try_cast_to_unix(value: &mut Value) {
 /// - `Ok(i64)` containing the Unix time seconds if parsing is successful.
 /// - `Err(ParseError)` if parsing fails.
 fn parse_to_unix_time(date_str: &str) -> Result<i64, ParseError> {
-    let naive_datetime: NaiveDateTime = NaiveDateTime::parse_from_str(date_str, "%Y-%m-%d %H:%M:%S")?;
-    Ok(naive_datetime.timestamp())
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(date_str) {
+        return Ok(datetime.timestamp());
+    }
+    let naive_datetime: NaiveDateTime =
+        NaiveDateTime::parse_from_str(date_str, "%Y-%m-%d %H:%M:%S")?;
+    Ok(naive_datetime.and_utc().timestamp())
+}
+
+/// Parses a date or date-time string with the first matching format.
+///
+/// Date-only formats resolve to midnight. `chrono` reads `%Y` from a single
+/// digit onwards, so `31.01.24` would parse as the year 24 with `%d.%m.%Y`:
+/// formats holding `%Y` only match years written with four digits.
+///
+/// # Arguments
+///
+/// * `date_str` - The date as written in the source document.
+/// * `formats` - The `chrono` formats to try, in order.
+///
+/// # Returns
+///
+/// The parsed `NaiveDateTime`, or `None` if no format matches.
+pub fn parse_naive_datetime<S: AsRef<str>>(date_str: &str, formats: &[S]) -> Option<NaiveDateTime> {
+    let trimmed: &str = date_str.trim();
+    formats.iter().find_map(|format| {
+        let format: &str = format.as_ref();
+        NaiveDateTime::parse_from_str(trimmed, format)
+            .ok()
+            .or_else(|| {
+                NaiveDate::parse_from_str(trimmed, format)
+                    .ok()
+                    .and_then(|date| date.and_hms_opt(0, 0, 0))
+            })
+            .filter(|parsed| !format.contains("%Y") || parsed.year() >= 1000)
+    })
+}
+
+/// Infers the format of a date column.
+///
+/// # Arguments
+///
+/// * `values` - The raw values of the column, empty values are ignored.
+///
+/// # Returns
+///
+/// The first of `KNOWN_DATE_FORMATS` that parses every value, `None` if none does.
+pub fn infer_column_date_format<'a, I: IntoIterator<Item = &'a str>>(
+    values: I,
+) -> Option<&'static str> {
+    let values: Vec<&str> = values
+        .into_iter()
+        .filter(|value| !value.trim().is_empty())
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+
+    KNOWN_DATE_FORMATS
+        .iter()
+        .find(|format| {
+            values
+                .iter()
+                .all(|value| parse_naive_datetime(value, &[**format]).is_some())
+        })
+        .copied()
+}
+
+/// Attaches a timezone to a local date-time.
+///
+/// Times falling in a DST gap or overlap resolve to the earliest valid instant.
+///
+/// # Arguments
+///
+/// * `naive` - The local date-time as written in the source document.
+/// * `tz` - The timezone of the source document.
+///
+/// # Returns
+///
+/// The date-time with its UTC offset.
+pub fn localize(naive: NaiveDateTime, tz: Tz) -> DateTime<FixedOffset> {
+    match tz.from_local_datetime(&naive).earliest() {
+        Some(datetime) => datetime.fixed_offset(),
+        None => {
+            // inside a DST gap, the wall clock time doesn't exist: shift past it
+            let shifted: NaiveDateTime = naive + chrono::Duration::hours(1);
+            tz.from_local_datetime(&shifted)
+                .earliest()
+                .map(|datetime| datetime.fixed_offset())
+                .unwrap_or_else(|| Utc.from_utc_datetime(&naive).fixed_offset())
+        }
+    }
+}
+
+/// Normalizes the date columns of a JSON array of objects into RFC 3339 strings.
+///
+/// Every date is rewritten as `2024-01-31T00:00:00+01:00` in the timezone of
+/// the provider, so the casters only have to deal with a single notation. When
+/// the `DateFormat` has no formats they are inferred per column. Values that
+/// can't be parsed are left untouched and fail later in the caster.
+///
+/// # Arguments
+///
+/// * `data` - A mutable reference to a JSON array of objects with string values.
+/// * `format` - The `DateFormat` of the provider.
+pub fn normalize_date_columns(data: &mut Value, format: &DateFormat) {
+    let Some(array) = data.as_array_mut() else {
+        error!("Provided data is not an array.");
+        return;
+    };
+    let tz: Tz = format.tz();
+
+    for column in &format.date_columns {
+        let formats: Vec<String> = if format.formats.is_empty() {
+            infer_column_date_format(
+                array
+                    .iter()
+                    .filter_map(|row| row.get(column).and_then(|value| value.as_str())),
+            )
+            .map(|inferred| vec![inferred.to_string()])
+            .unwrap_or_default()
+        } else {
+            format.formats.clone()
+        };
+        info!("Using date formats {:?} for column '{}'", formats, column);
+
+        for row in array.iter_mut() {
+            let Some(value) = row.get_mut(column) else {
+                continue;
+            };
+            let Some(str_value) = value.as_str() else {
+                continue;
+            };
+            if str_value.trim().is_empty() {
+                continue;
+            }
+            match parse_naive_datetime(str_value, &formats) {
+                Some(naive) => *value = Value::String(localize(naive, tz).to_rfc3339()),
+                None => warn!("Failed to parse '{}' in column '{}' as a date", str_value, column),
+            }
+        }
+    }
+}
+
+/// Rewrites the date fields of a serialized transaction in the requested `DateOutput`.
+///
+/// The fields are expected to hold Unix time seconds; `null` and other values
+/// are left untouched.
+///
+/// # Arguments
+///
+/// * `obj_map` - The serialized transaction.
+/// * `date_fields` - The keys holding dates.
+/// * `output` - The requested `DateOutput`.
+/// * `tz` - The timezone the offsets and calendar dates are expressed in.
+pub fn format_date_fields(
+    obj_map: &mut Map<String, Value>,
+    date_fields: &[&str],
+    output: DateOutput,
+    tz: Tz,
+) {
+    if output == DateOutput::UnixSeconds {
+        return;
+    }
+
+    for key in date_fields {
+        let Some(value) = obj_map.get_mut(*key) else {
+            continue;
+        };
+        let Some(datetime) = value
+            .as_i64()
+            .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        else {
+            continue;
+        };
+        let local: DateTime<Tz> = datetime.with_timezone(&tz);
+
+        *value = match output {
+            DateOutput::Iso8601 => Value::String(local.to_rfc3339()),
+            DateOutput::DateOnly => Value::String(local.format("%Y-%m-%d").to_string()),
+            DateOutput::UnixSeconds => continue,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDateTime {
+        date_time(year, month, day, 0, 0, 0)
+    }
+
+    fn date_time(year: i32, month: u32, day: u32, hour: u32, minute: u32, second: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day)
+            .and_then(|date| date.and_hms_opt(hour, minute, second))
+            .unwrap()
+    }
+
+    fn parse(value: &str) -> Option<NaiveDateTime> {
+        parse_naive_datetime(value, &KNOWN_DATE_FORMATS)
+    }
+
+    #[test]
+    fn parses_known_formats() {
+        assert_eq!(parse("20240131"), Some(date(2024, 1, 31)));
+        assert_eq!(parse("2024-01-31"), Some(date(2024, 1, 31)));
+        assert_eq!(parse("31/01/2024"), Some(date(2024, 1, 31)));
+        assert_eq!(parse("31-01-2024"), Some(date(2024, 1, 31)));
+        assert_eq!(parse("31.01.2024"), Some(date(2024, 1, 31)));
+        assert_eq!(parse("240131"), Some(date(2024, 1, 31)));
+        assert_eq!(parse(" 2024-01-31 01:24:30 "), Some(date_time(2024, 1, 31, 1, 24, 30)));
+        assert_eq!(parse("31 januari"), None);
+    }
+
+    #[test]
+    fn two_digit_years_are_not_read_as_four_digit_years() {
+        assert_eq!(parse("31.01.24"), Some(date(2024, 1, 31)));
+        assert_eq!(parse("31-01-24"), Some(date(2024, 1, 31)));
+        assert_eq!(parse("31/01/24"), Some(date(2024, 1, 31)));
+        assert_eq!(parse_naive_datetime("31.01.24", &["%d.%m.%Y"]), None);
+    }
+
+    #[test]
+    fn infers_the_format_of_a_column() {
+        assert_eq!(infer_column_date_format(["20240131", "20240201"]), Some("%Y%m%d"));
+        assert_eq!(infer_column_date_format(["2024-01-31", ""]), Some("%Y-%m-%d"));
+        assert_eq!(infer_column_date_format(["31/01/2024", "01/02/2024"]), Some("%d/%m/%Y"));
+        assert_eq!(infer_column_date_format(["240131", "240201"]), Some("%y%m%d"));
+        assert_eq!(infer_column_date_format(["31.01.24", "01.02.24"]), Some("%d.%m.%y"));
+        // `01/31/2024` can't be day-first, so the whole column is month-first
+        assert_eq!(infer_column_date_format(["01/02/2024", "01/31/2024"]), Some("%m/%d/%Y"));
+        assert_eq!(infer_column_date_format(["", " "]), None);
+        assert_eq!(infer_column_date_format(["2024-01-31", "n/a"]), None);
+    }
+
+    #[test]
+    fn localizes_in_the_timezone_of_the_provider() {
+        let amsterdam: Tz = "Europe/Amsterdam".parse().unwrap();
+        assert_eq!(
+            localize(date(2024, 1, 31), amsterdam).to_rfc3339(),
+            "2024-01-31T00:00:00+01:00"
+        );
+        assert_eq!(
+            localize(date(2024, 7, 1), amsterdam).to_rfc3339(),
+            "2024-07-01T00:00:00+02:00"
+        );
+    }
+
+    #[test]
+    fn localizes_times_in_a_dst_gap_past_it() {
+        let amsterdam: Tz = "Europe/Amsterdam".parse().unwrap();
+        // 02:30 doesn't exist on the last Sunday of March, the clock jumps from 02:00 to 03:00
+        let gap: NaiveDateTime = date_time(2024, 3, 31, 2, 30, 0);
+        let localized: DateTime<FixedOffset> = localize(gap, amsterdam);
+        assert_eq!(localized.to_rfc3339(), "2024-03-31T03:30:00+02:00");
+        assert_eq!(localized.timestamp(), gap.and_utc().timestamp() - 3600);
+    }
+
+    #[test]
+    fn localizes_times_in_a_dst_overlap_to_the_earliest() {
+        let amsterdam: Tz = "Europe/Amsterdam".parse().unwrap();
+        let overlap: NaiveDateTime = date_time(2024, 10, 27, 2, 30, 0);
+        assert_eq!(localize(overlap, amsterdam).to_rfc3339(), "2024-10-27T02:30:00+02:00");
+    }
+
+    #[test]
+    fn normalizes_date_columns() {
+        let mut data: Value = json!([
+            { "datum": "31.01.24", "omschrijving": "31.01.24" },
+            { "datum": "" },
+            { "datum": "01.02.24" },
+        ]);
+        let mut format: DateFormat = DateFormat {
+            formats: vec![],
+            timezone: Some("Europe/Amsterdam".to_string()),
+            date_columns: vec!["datum".to_string()],
+        };
+        normalize_date_columns(&mut data, &format);
+
+        assert_eq!(data[0]["datum"], "2024-01-31T00:00:00+01:00");
+        assert_eq!(data[0]["omschrijving"], "31.01.24");
+        assert_eq!(data[1]["datum"], "");
+        assert_eq!(data[2]["datum"], "2024-02-01T00:00:00+01:00");
+
+        let mut value: Value = data[0]["datum"].clone();
+        try_cast_to_unix(&mut value);
+        assert_eq!(value, json!(1706655600));
+
+        // values that don't match a declared format are left for the caster to report
+        let mut data: Value = json!([{ "datum": "n/a" }, { "datum": "31.01.24" }]);
+        format.formats = vec!["%d.%m.%y".to_string()];
+        normalize_date_columns(&mut data, &format);
+        assert_eq!(data[0]["datum"], "n/a");
+        assert_eq!(data[1]["datum"], "2024-01-31T00:00:00+01:00");
+    }
+
+    #[test]
+    fn formats_date_fields() {
+        let mut transaction: Map<String, Value> = Map::new();
+        transaction.insert("booking_date".to_string(), json!(1706655600));
+        transaction.insert("value_date".to_string(), Value::Null);
+        let amsterdam: Tz = "Europe/Amsterdam".parse().unwrap();
+
+        let mut iso: Map<String, Value> = transaction.clone();
+        format_date_fields(
+            &mut iso,
+            &["booking_date", "value_date"],
+            DateOutput::Iso8601,
+            amsterdam,
+        );
+        assert_eq!(iso["booking_date"], "2024-01-31T00:00:00+01:00");
+        assert_eq!(iso["value_date"], Value::Null);
+
+        format_date_fields(&mut transaction, &["booking_date"], DateOutput::DateOnly, amsterdam);
+        assert_eq!(transaction["booking_date"], "2024-01-31");
+    }
 }
@@ -0,0 +1,98 @@
+//! ## Canonical transaction
+//!
+//! Every provider caster in the `caster_registry` maps its own target struct
+//! into a [`Transaction`], so consumers of Aurora never have to special-case
+//! a bank. The provider-specific structs only exist as an intermediate step.
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// The lifecycle state of a transaction.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionStatus {
+    Pending,
+    Completed,
+    Reverted,
+    Declined,
+    Failed,
+    #[default]
+    Unknown,
+}
+
+impl TransactionStatus {
+    /// Maps a provider state such as Revolut's `COMPLETED` or `PENDING` to a status.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The state as written by the provider, matched case-insensitively.
+    ///
+    /// # Returns
+    ///
+    /// The matching `TransactionStatus`, `TransactionStatus::Unknown` when unrecognised.
+    pub fn from_provider_state(state: &str) -> TransactionStatus {
+        match state.trim().to_uppercase().as_str() {
+            "PENDING" => TransactionStatus::Pending,
+            "COMPLETED" | "BOOKED" | "SETTLED" => TransactionStatus::Completed,
+            "REVERTED" | "REVERSED" => TransactionStatus::Reverted,
+            "DECLINED" => TransactionStatus::Declined,
+            "FAILED" => TransactionStatus::Failed,
+            _ => TransactionStatus::Unknown,
+        }
+    }
+}
+
+/// A provider-agnostic transaction.
+///
+/// Dates are Unix time seconds and amounts are exact, signed decimals: money
+/// leaving the account is negative.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Transaction {
+    /// The provider the transaction was detected as, e.g. `revolut_csv`.
+    pub document_provider: String,
+    /// When the transaction was booked.
+    pub booking_date: i64,
+    /// When the transaction took effect on the balance, if the provider reports it.
+    pub value_date: Option<i64>,
+    /// The signed amount of the transaction.
+    pub amount: Decimal,
+    /// The ISO 4217 currency code.
+    pub currency: String,
+    /// The name of the counterparty, if known.
+    pub counterparty_name: Option<String>,
+    /// The IBAN of the counterparty, if known.
+    pub counterparty_iban: Option<String>,
+    /// A human readable description of the transaction.
+    pub description: String,
+    /// The payment reference, if any.
+    pub reference: Option<String>,
+    /// The balance of the account after the transaction, if known.
+    pub balance_after: Option<Decimal>,
+    /// The fee charged for the transaction, if reported separately.
+    pub fee: Option<Decimal>,
+    /// The lifecycle state of the transaction.
+    pub status: TransactionStatus,
+    /// Provider-specific fields that have no canonical counterpart.
+    pub provider_fields: Map<String, Value>,
+    /// Every column of the source row exactly as parsed, filled in by the caster registry.
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub raw: Map<String, Value>,
+}
+
+impl Transaction {
+    /// The fields holding amounts, formatted per the requested `MoneyFormat`.
+    pub const MONEY_FIELDS: [&'static str; 3] = ["amount", "balance_after", "fee"];
+    /// The fields holding dates, formatted per the requested `DateOutput`.
+    pub const DATE_FIELDS: [&'static str; 2] = ["booking_date", "value_date"];
+}
+
+/// Converts an empty string into `None`.
+pub fn non_empty(value: &str) -> Option<String> {
+    let trimmed: &str = value.trim();
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed.to_string())
+    }
+}
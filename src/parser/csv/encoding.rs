@@ -0,0 +1,224 @@
+//! ## Text encodings
+//!
+//! Bank exports are far from always UTF-8: Rabobank and older ABN AMRO
+//! exports are Windows-1252, some UK banks ship UTF-16 with a byte order
+//! mark. Before a CSV document is parsed its encoding is taken from, in order:
+//!
+//! 1. a byte order mark,
+//! 2. the encoding declared by the provider, see `CsvDialect::encoding`,
+//! 3. the content itself: valid UTF-8, NUL bytes betraying UTF-16, or a
+//!    statistical guess between the legacy single and double byte encodings.
+
+use anyhow::{Context, Result};
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use tracing::{info, warn};
+
+/// How many bytes are inspected when looking for UTF-16 without a byte order mark.
+const UTF16_SNIFF_LENGTH: usize = 1024;
+
+/// Where the encoding of a document was taken from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EncodingSource {
+    /// A byte order mark at the start of the document.
+    Bom,
+    /// The encoding declared by the provider or the request.
+    Declared,
+    /// Detected from the content of the document.
+    Detected,
+}
+
+/// The encoding a document was decoded with.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TextEncoding {
+    /// The WHATWG name of the encoding, e.g. `windows-1252`.
+    pub name: String,
+    /// Where the encoding was taken from.
+    pub source: EncodingSource,
+    /// Whether the encoding is certain, a statistical guess on little text is not.
+    pub confident: bool,
+    /// Whether invalid byte sequences were replaced with `U+FFFD` while decoding.
+    pub had_errors: bool,
+}
+
+/// Detects the encoding of a document without decoding it.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the document.
+/// * `declared` - The WHATWG label of the encoding declared by the provider, if any.
+///
+/// # Returns
+///
+/// A `Result` containing the encoding and where it was taken from, or an error for an unknown label.
+pub fn detect_encoding(
+    content: &[u8],
+    declared: Option<&str>,
+) -> Result<(&'static Encoding, EncodingSource, bool)> {
+    if let Some((encoding, _)) = Encoding::for_bom(content) {
+        return Ok((encoding, EncodingSource::Bom, true));
+    }
+
+    if let Some(label) = declared {
+        let encoding: &'static Encoding = Encoding::for_label(label.as_bytes())
+            .with_context(|| format!("Unknown encoding '{}'", label))?;
+        return Ok((encoding, EncodingSource::Declared, true));
+    }
+
+    if let Some(encoding) = sniff_utf16(content) {
+        return Ok((encoding, EncodingSource::Detected, true));
+    }
+    if std::str::from_utf8(content).is_ok() {
+        return Ok((UTF_8, EncodingSource::Detected, true));
+    }
+
+    let mut detector: EncodingDetector = EncodingDetector::new();
+    detector.feed(content, true);
+    let (encoding, confident) = detector.guess_assess(None, false);
+    Ok((encoding, EncodingSource::Detected, confident))
+}
+
+/// Decodes a document into UTF-8 text, detecting its encoding first.
+///
+/// A byte order mark is stripped from the text.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the document.
+/// * `declared` - The WHATWG label of the encoding declared by the provider, e.g. `windows-1252`.
+///
+/// # Returns
+///
+/// A `Result` containing the decoded text and the `TextEncoding` it was decoded
+/// with, or an error for an unknown encoding label.
+pub fn decode_text<'a>(
+    content: &'a [u8],
+    declared: Option<&str>,
+) -> Result<(Cow<'a, str>, TextEncoding)> {
+    let (encoding, source, confident) = detect_encoding(content, declared)?;
+    // `decode` strips a byte order mark, and lets it override the declared encoding
+    let (text, _, had_errors) = encoding.decode(content);
+    if had_errors {
+        warn!("Document is not valid {}, invalid bytes were replaced", encoding.name());
+    }
+    info!("Decoded document as {} ({:?})", encoding.name(), source);
+
+    Ok((
+        text,
+        TextEncoding {
+            name: encoding.name().to_lowercase(),
+            source,
+            confident,
+            had_errors,
+        },
+    ))
+}
+
+/// Recognises UTF-16 without a byte order mark by its NUL bytes.
+///
+/// Text in Latin script encoded as UTF-16 has a NUL byte in every other
+/// position, odd positions for little endian and even ones for big endian.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the document, only the start is inspected.
+///
+/// # Returns
+///
+/// `UTF_16LE` or `UTF_16BE`, or `None` when the bytes don't follow either pattern.
+pub fn sniff_utf16(content: &[u8]) -> Option<&'static Encoding> {
+    let sample: &[u8] = &content[..content.len().min(UTF16_SNIFF_LENGTH)];
+    if sample.len() < 4 {
+        return None;
+    }
+    let pairs: usize = sample.len() / 2;
+    let even_nuls: usize = sample.iter().step_by(2).filter(|byte| **byte == 0).count();
+    let odd_nuls: usize = sample.iter().skip(1).step_by(2).filter(|byte| **byte == 0).count();
+
+    if odd_nuls * 10 >= pairs * 8 && even_nuls * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even_nuls * 10 >= pairs * 8 && odd_nuls * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| if big_endian { unit.to_be_bytes() } else { unit.to_le_bytes() })
+            .collect()
+    }
+
+    #[test]
+    fn decodes_utf8_as_is() {
+        let (text, encoding) = decode_text("Datum;Bedrag\n31-01-2024;€ 12,50\n".as_bytes(), None).unwrap();
+        assert_eq!(text, "Datum;Bedrag\n31-01-2024;€ 12,50\n");
+        assert_eq!(encoding.name, "utf-8");
+        assert_eq!(encoding.source, EncodingSource::Detected);
+        assert!(encoding.confident);
+        assert!(!encoding.had_errors);
+    }
+
+    #[test]
+    fn a_byte_order_mark_wins_and_is_stripped() {
+        let mut content: Vec<u8> = vec![0xEF, 0xBB, 0xBF];
+        content.extend_from_slice(b"Date,Amount\n");
+        let (text, encoding) = decode_text(&content, Some("windows-1252")).unwrap();
+        assert_eq!(text, "Date,Amount\n");
+        assert_eq!(encoding.name, "utf-8");
+        assert_eq!(encoding.source, EncodingSource::Bom);
+
+        let mut content: Vec<u8> = vec![0xFF, 0xFE];
+        content.extend(utf16("Date,Amount\n", false));
+        let (text, encoding) = decode_text(&content, None).unwrap();
+        assert_eq!(text, "Date,Amount\n");
+        assert_eq!(encoding.name, "utf-16le");
+        assert_eq!(encoding.source, EncodingSource::Bom);
+    }
+
+    #[test]
+    fn decodes_the_declared_encoding() {
+        // "Café" as exported by Rabobank
+        let (text, encoding) = decode_text(b"Omschrijving\nCaf\xe9 de Zaak\n", Some("windows-1252")).unwrap();
+        assert_eq!(text, "Omschrijving\nCafé de Zaak\n");
+        assert_eq!(encoding.name, "windows-1252");
+        assert_eq!(encoding.source, EncodingSource::Declared);
+    }
+
+    #[test]
+    fn an_unknown_label_is_an_error() {
+        assert!(decode_text(b"Date,Amount\n", Some("klingon")).is_err());
+    }
+
+    #[test]
+    fn detects_utf16_without_a_byte_order_mark() {
+        let text: &str = "Date,Description,Amount\n2024-01-31,Tesco,-12.50\n";
+        assert_eq!(sniff_utf16(&utf16(text, false)), Some(UTF_16LE));
+        assert_eq!(sniff_utf16(&utf16(text, true)), Some(UTF_16BE));
+        assert_eq!(sniff_utf16(text.as_bytes()), None);
+
+        let content: Vec<u8> = utf16(text, true);
+        let (decoded, encoding) = decode_text(&content, None).unwrap();
+        assert_eq!(decoded, text);
+        assert_eq!(encoding.name, "utf-16be");
+        assert_eq!(encoding.source, EncodingSource::Detected);
+    }
+
+    #[test]
+    fn guesses_legacy_encodings() {
+        let content: &[u8] = b"Datum;Naam;Bedrag\n31-01-2024;Caf\xe9 Fran\xe7ais;12,50\n01-02-2024;H\xf4tel G\xe9n\xe9ral;99,00\n";
+        let (text, encoding) = decode_text(content, None).unwrap();
+        assert_eq!(encoding.name, "windows-1252");
+        assert_eq!(encoding.source, EncodingSource::Detected);
+        assert!(text.contains("Café Français"));
+        assert!(!encoding.had_errors);
+    }
+}
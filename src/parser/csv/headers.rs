@@ -1,6 +1,231 @@
+//! ## Header normalization and aliases
+//!
+//! Every bank names its columns differently: `Transactiedatum`, `Boekdatum`,
+//! `Datum`, `Booking Date` and `Buchungstag` all hold the booking date. Headers
+//! are first normalized into snake_case ASCII, then [`HeaderAliases`] maps the
+//! names known from Dutch, English, German, French and Swedish exports onto the
+//! canonical `Transaction` fields, so detection and casting see through them.
+
 use csv::StringRecord;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// The canonical `Transaction` fields and the normalized headers they are known by.
+pub const HEADER_ALIASES: [(&str, &[&str]); 11] = [
+    (
+        "booking_date",
+        &[
+            "date",
+            "datum",
+            "boekdatum",
+            "transactiedatum",
+            "booking_date",
+            "transaction_date",
+            "posting_date",
+            "buchungstag",
+            "buchungsdatum",
+            "date_operation",
+            "date_de_l_operation",
+            "date_comptable",
+            "bokforingsdag",
+            "bokforingsdatum",
+            "transaktionsdatum",
+        ],
+    ),
+    (
+        "value_date",
+        &[
+            "rentedatum",
+            "valutadatum",
+            "value_date",
+            "wertstellung",
+            "valuta_datum",
+            "date_valeur",
+            "date_de_valeur",
+            "valutadag",
+        ],
+    ),
+    (
+        "amount",
+        &[
+            "amount",
+            "bedrag",
+            "bedrag_eur",
+            "transactiebedrag",
+            "transaction_amount",
+            "betrag",
+            "betrag_eur",
+            "umsatz",
+            "montant",
+            "montant_eur",
+            "belopp",
+        ],
+    ),
+    (
+        "currency",
+        &["currency", "munt", "muntsoort", "valuta", "wahrung", "waehrung", "devise"],
+    ),
+    (
+        "counterparty_name",
+        &[
+            "naam",
+            "naam_tegenpartij",
+            "naam_omschrijving",
+            "tegenpartij",
+            "counterparty",
+            "counterparty_name",
+            "payee",
+            "beneficiary",
+            "empfanger",
+            "begunstigter_zahlungspflichtiger",
+            "beguenstigter_zahlungspflichtiger",
+            "auftraggeber_empfanger",
+            "beneficiaire",
+            "contrepartie",
+            "mottagare",
+            "motpart",
+        ],
+    ),
+    (
+        "counterparty_iban",
+        &[
+            "tegenrekening",
+            "tegenrekening_iban",
+            "iban_tegenpartij",
+            "counterparty_iban",
+            "counterparty_account",
+            "iban_empfanger",
+            "kontonummer_iban",
+            "compte_contrepartie",
+            "motpartens_konto",
+        ],
+    ),
+    (
+        "description",
+        &[
+            "omschrijving",
+            "mededelingen",
+            "description",
+            "memo",
+            "details",
+            "verwendungszweck",
+            "buchungstext",
+            "libelle",
+            "communication",
+            "beskrivning",
+            "meddelande",
+        ],
+    ),
+    (
+        "reference",
+        &[
+            "reference",
+            "referentie",
+            "kenmerk",
+            "betalingskenmerk",
+            "referenz",
+            "referens",
+        ],
+    ),
+    (
+        "balance_after",
+        &[
+            "balance",
+            "balance_after",
+            "saldo",
+            "saldo_na_mutatie",
+            "saldo_na_boeking",
+            "kontostand",
+            "solde",
+        ],
+    ),
+    ("fee", &["fee", "fees", "kosten", "gebuhr", "gebuhren", "frais", "avgift"]),
+    ("status", &["status", "state", "statut"]),
+];
+
+/// Maps normalized headers onto the canonical `Transaction` fields.
+///
+/// Start from [`builtin_aliases`] and [`extend`](HeaderAliases::extend) it for a
+/// provider with headers only it uses.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeaderAliases {
+    fields: HashMap<String, String>,
+}
+
+impl HeaderAliases {
+    /// Creates the aliases of `HEADER_ALIASES`, every field is also an alias of itself.
+    pub fn builtin() -> HeaderAliases {
+        let mut aliases: HeaderAliases = HeaderAliases::default();
+        for (field, headers) in HEADER_ALIASES {
+            aliases.extend(field, [field].into_iter().chain(headers.iter().copied()));
+        }
+        aliases
+    }
+
+    /// Adds aliases of a field, replacing whatever field they were an alias of before.
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - The canonical field, e.g. `booking_date`.
+    /// * `headers` - The headers it is known by, as written in the export or normalized.
+    pub fn extend<'a>(&mut self, field: &str, headers: impl IntoIterator<Item = &'a str>) {
+        for header in headers {
+            self.fields.insert(normalize_header(header), field.to_string());
+        }
+    }
+
+    /// Returns the canonical field a header is an alias of.
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - The header, as written in the export or normalized.
+    pub fn field(&self, header: &str) -> Option<&str> {
+        self.fields.get(&normalize_header(header)).map(String::as_str)
+    }
+
+    /// Returns the canonical field of a header, or the normalized header when it isn't an alias.
+    pub fn canonical(&self, header: &str) -> String {
+        self.field(header)
+            .map(str::to_string)
+            .unwrap_or_else(|| normalize_header(header))
+    }
+
+    /// Returns every normalized header known as a field.
+    pub fn aliases_of(&self, field: &str) -> Vec<String> {
+        let mut headers: Vec<String> = self
+            .fields
+            .iter()
+            .filter(|(_, aliased)| *aliased == field)
+            .map(|(header, _)| header.clone())
+            .collect();
+        headers.sort();
+        headers
+    }
+
+    /// Finds the header holding a field among the headers of a document.
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - The canonical field, e.g. `amount`.
+    /// * `headers` - The normalized headers of the document, in order.
+    ///
+    /// # Returns
+    ///
+    /// The first header that is an alias of the field, `None` if there is none.
+    pub fn find<'a>(&self, field: &str, headers: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+        headers
+            .into_iter()
+            .find(|header| self.field(header) == Some(field))
+    }
+}
+
+/// Returns the built-in `HeaderAliases`, shared by every detection and caster.
+pub fn builtin_aliases() -> &'static HeaderAliases {
+    static ALIASES: OnceLock<HeaderAliases> = OnceLock::new();
+    ALIASES.get_or_init(HeaderAliases::builtin)
+}
 
-/// Converts CSV headers to lowercase and replaces spaces with underscores.
+/// Normalizes CSV headers into snake_case ASCII.
 ///
 /// # Arguments
 ///
@@ -8,10 +233,122 @@ use csv::StringRecord;
 ///
 /// # Returns
 ///
-/// A `StringRecord` with headers converted to lowercase and spaces replaced with underscores.
+/// A `StringRecord` with every header normalized by `normalize_header`.
 pub fn normalize_headers(headers: &StringRecord) -> StringRecord {
-    headers
-        .iter()
-        .map(|header| header.to_lowercase().replace(' ', "_"))
-        .collect()
+    headers.iter().map(normalize_header).collect()
+}
+
+/// Normalizes a single header the way `normalize_headers` does, so configured
+/// column names can be matched against parsed rows.
+///
+/// Headers are lowercased, accents are stripped and every run of punctuation
+/// or whitespace becomes a single underscore: `Bedrag (EUR)` becomes
+/// `bedrag_eur` and `Date de l'opération` becomes `date_de_l_operation`.
+/// A header without any letter or digit is only lowercased.
+pub fn normalize_header(header: &str) -> String {
+    let mut normalized: String = String::with_capacity(header.len());
+    for c in header.to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            match fold_accent(c) {
+                Some(folded) => normalized.push_str(folded),
+                None => normalized.push(c),
+            }
+        } else if !normalized.is_empty() && !normalized.ends_with('_') {
+            normalized.push('_');
+        }
+    }
+    let normalized: &str = normalized.trim_end_matches('_');
+
+    if normalized.is_empty() {
+        header.trim().to_lowercase().replace(' ', "_")
+    } else {
+        normalized.to_string()
+    }
+}
+
+/// Strips the accent of a lowercase letter used in the languages of `HEADER_ALIASES`.
+fn fold_accent(c: char) -> Option<&'static str> {
+    let folded: &'static str = match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' => "a",
+        'æ' => "ae",
+        'ç' => "c",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' => "e",
+        'ì' | 'í' | 'î' | 'ï' => "i",
+        'ñ' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => "o",
+        'œ' => "oe",
+        'ù' | 'ú' | 'û' | 'ü' => "u",
+        'ý' | 'ÿ' => "y",
+        'ß' => "ss",
+        _ => return None,
+    };
+    Some(folded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_headers_into_snake_case_ascii() {
+        let headers: StringRecord = StringRecord::from(vec![
+            "Datum",
+            "Naam / Omschrijving",
+            "Bedrag (EUR)",
+            "Date de l'opération",
+            "Begünstigter/Zahlungspflichtiger",
+            "Währung",
+            "  Saldo na mutatie  ",
+            "#",
+        ]);
+        assert_eq!(
+            normalize_headers(&headers),
+            StringRecord::from(vec![
+                "datum",
+                "naam_omschrijving",
+                "bedrag_eur",
+                "date_de_l_operation",
+                "begunstigter_zahlungspflichtiger",
+                "wahrung",
+                "saldo_na_mutatie",
+                "#",
+            ])
+        );
+    }
+
+    #[test]
+    fn maps_the_headers_of_known_exports() {
+        let aliases: &HeaderAliases = builtin_aliases();
+        assert_eq!(aliases.field("Transactiedatum"), Some("booking_date"));
+        assert_eq!(aliases.field("Buchungstag"), Some("booking_date"));
+        assert_eq!(aliases.field("Bokföringsdag"), Some("booking_date"));
+        assert_eq!(aliases.field("Begünstigter/Zahlungspflichtiger"), Some("counterparty_name"));
+        assert_eq!(aliases.field("Montant (EUR)"), Some("amount"));
+        assert_eq!(aliases.field("Tegenrekening IBAN"), Some("counterparty_iban"));
+        assert_eq!(aliases.field("balance_after"), Some("balance_after"));
+        assert_eq!(aliases.field("Mutatiesoort"), None);
+
+        assert_eq!(aliases.canonical("Bedrag (EUR)"), "amount");
+        assert_eq!(aliases.canonical("Mutatiesoort"), "mutatiesoort");
+    }
+
+    #[test]
+    fn finds_the_first_header_holding_a_field() {
+        let headers: Vec<&str> = vec!["mutatiesoort", "boekdatum", "rentedatum", "datum"];
+        let aliases: &HeaderAliases = builtin_aliases();
+        assert_eq!(aliases.find("booking_date", headers.iter().copied()), Some("boekdatum"));
+        assert_eq!(aliases.find("value_date", headers.iter().copied()), Some("rentedatum"));
+        assert_eq!(aliases.find("amount", headers.iter().copied()), None);
+    }
+
+    #[test]
+    fn provider_aliases_replace_the_builtin_ones() {
+        let mut aliases: HeaderAliases = HeaderAliases::builtin();
+        aliases.extend("value_date", ["Datum", "Valuta dag"]);
+
+        assert_eq!(aliases.field("datum"), Some("value_date"));
+        assert_eq!(aliases.field("valuta_dag"), Some("value_date"));
+        assert!(aliases.aliases_of("value_date").contains(&"datum".to_string()));
+        assert!(!aliases.aliases_of("booking_date").contains(&"datum".to_string()));
+    }
 }
@@ -1,8 +1,12 @@
+pub mod encoding;
 pub mod headers;
+pub mod sniffer;
 
 use anyhow::{Context, Result};
 use csv::{Reader, ReaderBuilder};
-use serde_json::{json, Value};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufReader, Read};
@@ -10,8 +14,9 @@ use std::path::Path;
 use tracing::{error, info};
 
 // crate imports
-use crate::parser::caster::number::cast_keys_to_f64;
+use crate::parser::csv::encoding::{decode_text, TextEncoding};
 use crate::parser::csv::headers::normalize_headers;
+use crate::parser::csv::sniffer::{sniff_csv, CsvLayout, SniffedCsv};
 
 pub async fn csv_to_json(filepath: &str) -> Result<Value> {
     let path: &Path = Path::new(filepath);
@@ -32,9 +37,86 @@ pub async fn csv_to_json(filepath: &str) -> Result<Value> {
     convert_csv_reader_to_json(reader).await
 }
 
-pub async fn convert_csv_reader_to_json<R: Read>(reader: R) -> Result<Value> {
-    // Create a CSV reader
-    let mut csv_reader: Reader<R> = ReaderBuilder::new().from_reader(reader);
+/// The delimiters tried when sniffing a CSV document.
+pub const CANDIDATE_DELIMITERS: [u8; 4] = [b',', b';', b'\t', b'|'];
+
+/// How a CSV document is written.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct CsvDialect {
+    /// The field delimiter, sniffed when `None`.
+    #[serde(default)]
+    pub delimiter: Option<u8>,
+    /// The quote character, sniffed when `None`.
+    #[serde(default)]
+    pub quote: Option<u8>,
+    /// The WHATWG label of the text encoding, e.g. `windows-1252`. Detected when `None`.
+    #[serde(default)]
+    pub encoding: Option<String>,
+}
+
+/// How a CSV document was read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvMetadata {
+    /// The text encoding the document was decoded with.
+    pub encoding: TextEncoding,
+    /// The sniffed delimiter, quote character, header and footer lines.
+    pub layout: CsvLayout,
+    /// The key/value pairs found above the header, e.g. the account number and period.
+    pub preamble: Map<String, Value>,
+}
+
+/// Converts a CSV document to JSON with the given dialect.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the document.
+/// * `dialect` - The `CsvDialect` to read the document with, whatever it leaves unset is detected.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON array with an object per record and the `CsvMetadata` the document was read with.
+pub async fn convert_csv_with_dialect(
+    content: &[u8],
+    dialect: &CsvDialect,
+) -> Result<(Value, CsvMetadata)> {
+    let (text, encoding): (Cow<str>, TextEncoding) =
+        decode_text(content, dialect.encoding.as_deref())?;
+    let sniffed: SniffedCsv = sniff_csv(&text, dialect);
+    info!("Reading CSV as {:?}", sniffed.layout);
+
+    let body: &str = &text[sniffed.body.clone()];
+    let rows: Value = read_csv_to_json(
+        body.as_bytes(),
+        sniffed.layout.delimiter as u8,
+        sniffed.layout.quote as u8,
+    )
+    .await?;
+    Ok((
+        rows,
+        CsvMetadata {
+            encoding,
+            layout: sniffed.layout,
+            preamble: sniffed.preamble,
+        },
+    ))
+}
+
+pub async fn convert_csv_reader_to_json<R: Read>(mut reader: R) -> Result<Value> {
+    let mut content: Vec<u8> = vec![];
+    reader
+        .read_to_end(&mut content)
+        .context("Failed to read CSV")?;
+    let (text, _): (Cow<str>, TextEncoding) = decode_text(&content, None)?;
+    read_csv_to_json(text.as_bytes(), b',', b'"').await
+}
+
+async fn read_csv_to_json<R: Read>(reader: R, delimiter: u8, quote: u8) -> Result<Value> {
+    // Create a CSV reader, rows missing trailing fields are cast and reported per row
+    let mut csv_reader: Reader<R> = ReaderBuilder::new()
+        .delimiter(delimiter)
+        .quote(quote)
+        .flexible(true)
+        .from_reader(reader);
     info!("CSV reader created");
 
     // Convert CSV to JSON
@@ -64,3 +146,29 @@ pub async fn convert_csv_reader_to_json<R: Read>(reader: R) -> Result<Value> {
     info!("Successfully converted CSV to JSON");
     Ok(json!(records))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reads_a_windows_1252_export_with_a_preamble() {
+        let content: &[u8] = b"Rekeningnummer;NL91ABNA0417164300\r\n\r\nDatum;Omschrijving;Bedrag\r\n20240102;Caf\xe9 de Zaak;-2,50\r\n20240103;Salaris;2500,00\r\n";
+        let dialect: CsvDialect = CsvDialect {
+            encoding: Some("windows-1252".to_string()),
+            ..CsvDialect::default()
+        };
+        let (rows, metadata): (Value, CsvMetadata) =
+            convert_csv_with_dialect(content, &dialect).await.unwrap();
+
+        assert_eq!(metadata.encoding.name, "windows-1252");
+        assert_eq!(metadata.layout.delimiter, ';');
+        assert_eq!(metadata.layout.header_line, 2);
+        assert_eq!(metadata.preamble["Rekeningnummer"], "NL91ABNA0417164300");
+
+        let rows: &Vec<Value> = rows.as_array().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["omschrijving"], "Café de Zaak");
+        assert_eq!(rows[1]["bedrag"], "2500,00");
+    }
+}
@@ -0,0 +1,394 @@
+//! ## CSV dialect sniffing
+//!
+//! Bank exports rarely stick to plain comma separated values: ING and Belgian
+//! banks use `;`, TXT exports use tabs, and many exports put account details
+//! above the header and totals below the last transaction:
+//!
+//! ```text
+//! Account number;NL91ABNA0417164300
+//! Period;01-01-2024 - 31-01-2024
+//!
+//! Date;Description;Amount
+//! 20240102;Coffee;-2,50
+//! 20240103;Salary;2500,00
+//! Total;;2497,50
+//! ```
+//!
+//! The sniffer picks the delimiter that splits the most records into the same
+//! number of fields, takes the first record of that width as the header and
+//! the last one that isn't a total as the end of the data. Key/value pairs
+//! above the header are captured as the preamble of the document, lines below
+//! the data are skipped.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::ops::Range;
+
+// crate imports
+use crate::parser::csv::{CsvDialect, CANDIDATE_DELIMITERS};
+
+/// The quote characters tried when sniffing a CSV document, the first is the default.
+pub const CANDIDATE_QUOTES: [u8; 2] = [b'"', b'\''];
+
+/// How a CSV document turned out to be laid out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CsvLayout {
+    /// The field delimiter.
+    pub delimiter: char,
+    /// The quote character.
+    pub quote: char,
+    /// The zero-based line of the header, lines above it are the preamble.
+    pub header_line: usize,
+    /// The number of non-empty lines below the data that were skipped.
+    pub footer_lines: usize,
+}
+
+/// The outcome of sniffing a CSV document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SniffedCsv {
+    /// The layout of the document.
+    pub layout: CsvLayout,
+    /// The key/value pairs above the header, e.g. the account number and period.
+    pub preamble: Map<String, Value>,
+    /// The byte range of the text from the header up to and including the last record.
+    pub body: Range<usize>,
+}
+
+/// Sniffs the dialect and layout of a decoded CSV document.
+///
+/// A delimiter or quote declared by the provider is used as is.
+///
+/// # Arguments
+///
+/// * `text` - The decoded text of the document.
+/// * `dialect` - The `CsvDialect` declared by the provider, if any.
+///
+/// # Returns
+///
+/// The `SniffedCsv` describing where the header and the data are.
+pub fn sniff_csv(text: &str, dialect: &CsvDialect) -> SniffedCsv {
+    let default_quote: u8 = dialect.quote.unwrap_or(CANDIDATE_QUOTES[0]);
+    let candidates: Vec<u8> = match dialect.delimiter {
+        Some(delimiter) => vec![delimiter],
+        None => CANDIDATE_DELIMITERS.to_vec(),
+    };
+
+    // the delimiter splitting the most records into the same number of fields
+    let mut best: Option<(u8, usize, usize)> = None;
+    for delimiter in candidates.iter().copied() {
+        let widths: Vec<usize> = split_records(text, default_quote)
+            .into_iter()
+            .filter(|record| !text[record.clone()].trim().is_empty())
+            .map(|record| count_fields(&text[record], delimiter, default_quote))
+            .collect();
+        let Some(width) = modal_width(&widths) else {
+            continue;
+        };
+        let matching: usize = widths.iter().filter(|count| **count == width).count();
+        if best.is_none_or(|(_, best_matching, best_width)| {
+            (matching, width) > (best_matching, best_width)
+        }) {
+            best = Some((delimiter, matching, width));
+        }
+    }
+
+    let delimiter: u8 = best.map(|(delimiter, _, _)| delimiter).unwrap_or(candidates[0]);
+    let width: usize = best.map(|(_, _, width)| width).unwrap_or(1);
+    let quote: u8 = dialect
+        .quote
+        .unwrap_or_else(|| sniff_quote(text, delimiter));
+
+    let records: Vec<(usize, Range<usize>)> = split_records(text, quote)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, record)| !text[record.clone()].trim().is_empty())
+        .collect();
+    let wide: Vec<usize> = records
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, record))| count_fields(&text[record.clone()], delimiter, quote) == width)
+        .map(|(index, _)| index)
+        .collect();
+
+    // the header is the first record of full width that is followed by another one
+    let header: Option<usize> = wide
+        .windows(2)
+        .find(|pair| pair[1] == pair[0] + 1)
+        .map(|pair| pair[0])
+        .or_else(|| wide.first().copied());
+
+    // totals below the data often have the full width too, e.g. `Total;;2497,50`
+    let mut rows: Vec<(usize, Vec<String>)> = wide
+        .iter()
+        .filter(|index| header.is_some_and(|header| **index > header))
+        .map(|index| (*index, split_fields(&text[records[*index].1.clone()], delimiter, quote)))
+        .collect();
+    while rows.len() > 1 && is_total(&rows[rows.len() - 1].1, &rows[..rows.len() - 1]) {
+        rows.pop();
+    }
+    let last: Option<usize> = rows.last().map(|(index, _)| *index).or(header);
+
+    let (Some(header), Some(last)) = (header, last) else {
+        return SniffedCsv {
+            layout: CsvLayout {
+                delimiter: delimiter as char,
+                quote: quote as char,
+                header_line: 0,
+                footer_lines: 0,
+            },
+            preamble: Map::new(),
+            body: 0..text.len(),
+        };
+    };
+
+    let preamble: Map<String, Value> = records[..header]
+        .iter()
+        .filter_map(|(_, record)| preamble_pair(&text[record.clone()], delimiter, quote))
+        .map(|(key, value)| (key, Value::String(value)))
+        .collect();
+
+    SniffedCsv {
+        layout: CsvLayout {
+            delimiter: delimiter as char,
+            quote: quote as char,
+            header_line: records[header].0,
+            footer_lines: records.len() - last - 1,
+        },
+        preamble,
+        body: records[header].1.start..records[last].1.end,
+    }
+}
+
+/// Whether a record below the data is a total rather than a transaction.
+///
+/// A total leaves a field empty that every row above it fills, and its first
+/// field differs in kind from theirs, e.g. `Total` below a column of dates.
+fn is_total(record: &[String], rows: &[(usize, Vec<String>)]) -> bool {
+    let has_digits = |field: &str| field.chars().any(|c| c.is_ascii_digit());
+    let leaves_empty: bool = record.iter().enumerate().any(|(column, field)| {
+        field.is_empty()
+            && rows
+                .iter()
+                .all(|(_, row)| row.get(column).is_some_and(|value| !value.is_empty()))
+    });
+    let first: &str = record.first().map(String::as_str).unwrap_or_default();
+    let differs: bool = rows.iter().all(|(_, row)| {
+        row.first()
+            .is_some_and(|value| has_digits(value) != has_digits(first))
+    });
+    leaves_empty && differs
+}
+
+/// Splits a text into records at line breaks outside quoted fields.
+///
+/// The ranges exclude the line breaks. Only ASCII bytes are compared, so the
+/// ranges always fall on character boundaries.
+fn split_records(text: &str, quote: u8) -> Vec<Range<usize>> {
+    let bytes: &[u8] = text.as_bytes();
+    let mut records: Vec<Range<usize>> = vec![];
+    let mut start: usize = 0;
+    let mut quoted: bool = false;
+
+    for (index, byte) in bytes.iter().enumerate() {
+        if *byte == quote {
+            quoted = !quoted;
+        } else if *byte == b'\n' && !quoted {
+            let end: usize = if index > start && bytes[index - 1] == b'\r' {
+                index - 1
+            } else {
+                index
+            };
+            records.push(start..end);
+            start = index + 1;
+        }
+    }
+    if start < bytes.len() {
+        records.push(start..bytes.len());
+    }
+    records
+}
+
+/// Splits a single record into its fields, unquoted and trimmed.
+fn split_fields(record: &str, delimiter: u8, quote: u8) -> Vec<String> {
+    let mut fields: Vec<String> = vec![];
+    let mut field: Vec<u8> = vec![];
+    let mut quoted: bool = false;
+
+    for byte in record.bytes() {
+        if byte == quote {
+            quoted = !quoted;
+        } else if byte == delimiter && !quoted {
+            fields.push(String::from_utf8_lossy(&field).trim().to_string());
+            field.clear();
+        } else {
+            field.push(byte);
+        }
+    }
+    fields.push(String::from_utf8_lossy(&field).trim().to_string());
+    fields
+}
+
+fn count_fields(record: &str, delimiter: u8, quote: u8) -> usize {
+    let mut quoted: bool = false;
+    let mut count: usize = 1;
+    for byte in record.bytes() {
+        if byte == quote {
+            quoted = !quoted;
+        } else if byte == delimiter && !quoted {
+            count += 1;
+        }
+    }
+    count
+}
+
+/// Returns the most common number of fields above one, ties go to the widest.
+fn modal_width(widths: &[usize]) -> Option<usize> {
+    let mut frequencies: HashMap<usize, usize> = HashMap::new();
+    for width in widths.iter().filter(|width| **width > 1) {
+        *frequencies.entry(*width).or_default() += 1;
+    }
+    frequencies
+        .into_iter()
+        .max_by_key(|(width, frequency)| (*frequency, *width))
+        .map(|(width, _)| width)
+}
+
+/// Picks the quote character wrapping the most fields, `"` unless `'` is more common.
+fn sniff_quote(text: &str, delimiter: u8) -> u8 {
+    let delimiter: char = delimiter as char;
+    CANDIDATE_QUOTES
+        .iter()
+        .copied()
+        .map(|quote| {
+            let quote_char: char = quote as char;
+            let wrapped: usize = text
+                .lines()
+                .flat_map(|line| line.split(delimiter))
+                .map(str::trim)
+                .filter(|field| {
+                    field.len() >= 2 && field.starts_with(quote_char) && field.ends_with(quote_char)
+                })
+                .count();
+            (quote, wrapped)
+        })
+        .fold((CANDIDATE_QUOTES[0], 0), |best, candidate| {
+            if candidate.1 > best.1 {
+                candidate
+            } else {
+                best
+            }
+        })
+        .0
+}
+
+/// Reads a key/value pair from a preamble line, e.g. `Account number;NL91ABNA0417164300`
+/// or `Period: 01-01-2024 - 31-01-2024`.
+fn preamble_pair(record: &str, delimiter: u8, quote: u8) -> Option<(String, String)> {
+    let fields: Vec<String> = split_fields(record, delimiter, quote)
+        .into_iter()
+        .filter(|field| !field.is_empty())
+        .collect();
+
+    let (key, value): (String, String) = match fields.as_slice() {
+        [] => return None,
+        [single] => {
+            let (key, value) = single.split_once(':')?;
+            (key.to_string(), value.to_string())
+        }
+        [key, rest @ ..] => (key.to_string(), rest.join(" ")),
+    };
+
+    let key: &str = key.trim().trim_end_matches(':').trim();
+    let value: &str = value.trim();
+    if key.is_empty() || value.is_empty() {
+        return None;
+    }
+    Some((key.to_string(), value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ING_EXPORT: &str = "Account number;NL91ABNA0417164300\r
+Period;01-01-2024 - 31-01-2024\r
+\r
+Date;Description;Amount\r
+20240102;Coffee;-2,50\r
+20240103;Salary;2500,00\r
+Total;;2497,50\r
+";
+
+    #[test]
+    fn finds_the_header_data_and_preamble() {
+        let sniffed: SniffedCsv = sniff_csv(ING_EXPORT, &CsvDialect::default());
+
+        assert_eq!(
+            sniffed.layout,
+            CsvLayout {
+                delimiter: ';',
+                quote: '"',
+                header_line: 3,
+                footer_lines: 1,
+            }
+        );
+        assert_eq!(
+            &ING_EXPORT[sniffed.body.clone()],
+            "Date;Description;Amount\r\n20240102;Coffee;-2,50\r\n20240103;Salary;2500,00"
+        );
+        assert_eq!(sniffed.preamble["Account number"], "NL91ABNA0417164300");
+        assert_eq!(sniffed.preamble["Period"], "01-01-2024 - 31-01-2024");
+    }
+
+    #[test]
+    fn plain_comma_separated_values_have_no_preamble() {
+        let text: &str = "Date,Description,Amount\n2024-01-02,\"Coffee, large\",-2.50\n2024-01-03,Salary,2500.00\n";
+        let sniffed: SniffedCsv = sniff_csv(text, &CsvDialect::default());
+
+        assert_eq!(sniffed.layout.delimiter, ',');
+        assert_eq!(sniffed.layout.header_line, 0);
+        assert_eq!(sniffed.layout.footer_lines, 0);
+        assert!(sniffed.preamble.is_empty());
+        assert_eq!(&text[sniffed.body], text.trim_end());
+    }
+
+    #[test]
+    fn sniffs_tabs_and_single_quotes() {
+        let text: &str = "'Date'\t'Description'\t'Amount'\n'02-01-2024'\t'Coffee'\t'-2.50'\n'03-01-2024'\t'Salary'\t'2500.00'\n";
+        let sniffed: SniffedCsv = sniff_csv(text, &CsvDialect::default());
+
+        assert_eq!(sniffed.layout.delimiter, '\t');
+        assert_eq!(sniffed.layout.quote, '\'');
+    }
+
+    #[test]
+    fn a_declared_delimiter_is_used_as_is() {
+        let text: &str = "Date|Amount;Currency\n2024-01-02|-2.50;EUR\n";
+        let dialect: CsvDialect = CsvDialect {
+            delimiter: Some(b'|'),
+            ..CsvDialect::default()
+        };
+        assert_eq!(sniff_csv(text, &dialect).layout.delimiter, '|');
+    }
+
+    #[test]
+    fn reads_colon_separated_preamble_lines() {
+        assert_eq!(
+            preamble_pair("Period: 01-01-2024 - 31-01-2024", b',', b'"'),
+            Some(("Period".to_string(), "01-01-2024 - 31-01-2024".to_string()))
+        );
+        assert_eq!(
+            preamble_pair("\"IBAN:\",\"NL91ABNA0417164300\"", b',', b'"'),
+            Some(("IBAN".to_string(), "NL91ABNA0417164300".to_string()))
+        );
+        assert_eq!(preamble_pair("Transactions", b',', b'"'), None);
+    }
+
+    #[test]
+    fn line_breaks_inside_quotes_dont_split_records() {
+        let text: &str = "Date,Description\n2024-01-02,\"Coffee\nand cake\"\n";
+        assert_eq!(split_records(text, b'"').len(), 2);
+        assert_eq!(count_fields("2024-01-02,\"Coffee, large\",-2.50", b',', b'"'), 3);
+    }
+}
@@ -0,0 +1,263 @@
+//! ## Parsed documents
+//!
+//! The typed result of [`crate::parse`]. The HTTP API serializes a
+//! [`ParsedDocument`] as is, so library users and API clients see the same
+//! fields.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{from_value, Map, Value};
+use tracing::warn;
+
+// crate imports
+use crate::ocr::OcrMetadata;
+use crate::parser::caster::diagnostic::{Diagnostic, Diagnostics};
+use crate::parser::caster::transaction::Transaction;
+use crate::parser::csv::encoding::TextEncoding;
+use crate::parser::csv::sniffer::CsvLayout;
+use crate::parser::format::DocumentFormat;
+use crate::parser::options::{DateOutput, MoneyFormat, OutputShape, ParseOptions};
+use crate::parser::pdf::table::TableMetadata;
+use crate::parser::schema::detection::Detection;
+
+/// A parsed, detected and casted document.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ParsedDocument {
+    /// The format the content was detected as.
+    pub document_format: DocumentFormat,
+    /// The provider the document was detected as, and every candidate considered.
+    pub detection: Detection,
+    /// The records of the document, in the order they appear in it.
+    pub data: Vec<Record>,
+    /// Values that were kept as they were, the rows themselves were cast.
+    pub warnings: Vec<Diagnostic>,
+    /// Rows that couldn't be cast and were left out of `data`.
+    pub errors: Vec<Diagnostic>,
+    /// How the document was read.
+    #[serde(default)]
+    pub metadata: DocumentMetadata,
+}
+
+/// How a document was read, as far as it matters to the format.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct DocumentMetadata {
+    /// The text encoding of a CSV or MT940 document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<TextEncoding>,
+    /// The delimiter, quote character, header and footer lines of a CSV document.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dialect: Option<CsvLayout>,
+    /// The key/value pairs above the header of a CSV document, e.g. the account number and period.
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub preamble: Map<String, Value>,
+    /// How an image or scanned document was run through OCR.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ocr: Option<OcrMetadata>,
+    /// The columns of the table reconstructed from a PDF document, in the `table` extraction mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub table: Option<TableMetadata>,
+}
+
+/// A single record of a parsed document.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum Record {
+    /// A transaction in the canonical shape, with amounts and dates in their default format.
+    Transaction(Box<Transaction>),
+    /// Any other row: the provider shape, a custom money or date format, a row of
+    /// a provider without a caster, or a PDF page.
+    Row(Map<String, Value>),
+}
+
+impl ParsedDocument {
+    /// Assembles a document from the casted rows of the pipeline.
+    ///
+    /// # Arguments
+    ///
+    /// * `document_format` - The detected `DocumentFormat`.
+    /// * `detection` - The provider `Detection`.
+    /// * `rows` - The casted JSON array.
+    /// * `diagnostics` - The `Diagnostics` collected while casting.
+    /// * `metadata` - The `DocumentMetadata` of the parsing engine.
+    /// * `options` - The `ParseOptions` the rows were cast with.
+    pub fn new(
+        document_format: DocumentFormat,
+        detection: Detection,
+        rows: Value,
+        diagnostics: Diagnostics,
+        metadata: DocumentMetadata,
+        options: &ParseOptions,
+    ) -> ParsedDocument {
+        let typed: bool = options.output_shape == OutputShape::Canonical
+            && options.money_format == MoneyFormat::default()
+            && options.date_format == DateOutput::default();
+
+        let rows: Vec<Value> = match rows {
+            Value::Array(rows) => rows,
+            Value::Null => vec![],
+            row => vec![row],
+        };
+        let mut data: Vec<Record> = vec![];
+        let mut untyped: Vec<(usize, String)> = vec![];
+        for (index, row) in rows.into_iter().enumerate() {
+            let (record, failure): (Record, Option<String>) = Record::from_row(row, typed);
+            if let Some(reason) = failure {
+                untyped.push((index, reason));
+            }
+            data.push(record);
+        }
+
+        let mut warnings: Vec<Diagnostic> = diagnostics.warnings;
+        if let Some((index, reason)) = untyped.first() {
+            warn!("{} casted rows couldn't be typed as transactions", untyped.len());
+            warnings.push(Diagnostic::document(format!(
+                "{} casted rows couldn't be typed as transactions and are returned as plain rows, row {}: {}",
+                untyped.len(),
+                index,
+                reason
+            )));
+        }
+
+        ParsedDocument {
+            document_format,
+            detection,
+            data,
+            warnings,
+            errors: diagnostics.errors,
+            metadata,
+        }
+    }
+
+    /// Returns the records that are canonical transactions.
+    pub fn transactions(&self) -> impl Iterator<Item = &Transaction> {
+        self.data.iter().filter_map(|record| match record {
+            Record::Transaction(transaction) => Some(transaction.as_ref()),
+            Record::Row(_) => None,
+        })
+    }
+}
+
+impl Record {
+    /// Types a casted row, falling back to a plain row when it isn't a canonical transaction.
+    ///
+    /// Rows are only typed when amounts and dates are in their default format,
+    /// a `minor_units` amount would otherwise be read as a decimal.
+    ///
+    /// # Returns
+    ///
+    /// The `Record`, and why the row couldn't be typed when it was cast by a
+    /// provider. Casted rows carry their source columns under `raw`, rows without
+    /// them, such as PDF pages, were never transactions.
+    fn from_row(row: Value, typed: bool) -> (Record, Option<String>) {
+        let mut failure: Option<String> = None;
+        if typed {
+            match from_value::<Transaction>(row.clone()) {
+                Ok(transaction) => return (Record::Transaction(Box::new(transaction)), None),
+                Err(e) if row.get("raw").is_some() => failure = Some(e.to_string()),
+                Err(_) => {}
+            }
+        }
+        let record: Record = match row {
+            Value::Object(obj_map) => Record::Row(obj_map),
+            other => {
+                let mut obj_map: Map<String, Value> = Map::new();
+                obj_map.insert("value".to_string(), other);
+                Record::Row(obj_map)
+            }
+        };
+        (record, failure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn detection() -> Detection {
+        Detection {
+            document_provider: "revolut_csv".to_string(),
+            forced: true,
+            candidates: vec![],
+        }
+    }
+
+    fn transaction(amount: Value) -> Value {
+        json!({
+            "document_provider": "revolut_csv",
+            "booking_date": 1704072270,
+            "value_date": 1704072270,
+            "amount": amount,
+            "currency": "EUR",
+            "counterparty_name": null,
+            "counterparty_iban": null,
+            "description": "To EUR Pocket",
+            "reference": null,
+            "balance_after": "100.00",
+            "fee": "0.00",
+            "status": "completed",
+            "provider_fields": { "type": "TRANSFER" }
+        })
+    }
+
+    fn document(rows: Value, options: &ParseOptions) -> ParsedDocument {
+        ParsedDocument::new(
+            DocumentFormat::Csv,
+            detection(),
+            rows,
+            Diagnostics::default(),
+            DocumentMetadata::default(),
+            options,
+        )
+    }
+
+    #[test]
+    fn canonical_rows_are_typed_as_transactions() {
+        let rows: Value = json!([transaction(json!("50.00")), { "page_number": 1, "text": "Saldo" }]);
+        let document: ParsedDocument = document(rows.clone(), &ParseOptions::default());
+
+        assert!(matches!(document.data[0], Record::Transaction(_)));
+        assert!(matches!(document.data[1], Record::Row(_)));
+        assert_eq!(document.transactions().count(), 1);
+        assert_eq!(document.transactions().next().unwrap().description, "To EUR Pocket");
+        // records serialize as the rows they were built from
+        assert_eq!(serde_json::to_value(&document.data).unwrap(), rows);
+    }
+
+    #[test]
+    fn casted_rows_that_arent_transactions_are_reported() {
+        let mut broken: Value = transaction(json!("fifty"));
+        broken["raw"] = json!({ "Amount": "fifty" });
+        let rows: Value = json!([transaction(json!("50.00")), broken, { "page_number": 1, "text": "Saldo" }]);
+        let document: ParsedDocument = document(rows, &ParseOptions::default());
+
+        assert!(matches!(document.data[1], Record::Row(_)));
+        assert_eq!(document.transactions().count(), 1);
+        // only the casted row is reported, the page was never a transaction
+        assert_eq!(document.warnings.len(), 1);
+        assert_eq!(document.warnings[0].row, None);
+        assert!(document.warnings[0]
+            .error
+            .reason
+            .starts_with("1 casted rows couldn't be typed as transactions and are returned as plain rows, row 1:"));
+    }
+
+    #[test]
+    fn rows_in_a_custom_money_format_stay_rows() {
+        let options: ParseOptions = ParseOptions {
+            money_format: MoneyFormat::MinorUnits,
+            ..ParseOptions::default()
+        };
+        let document: ParsedDocument = document(json!([transaction(json!(5000))]), &options);
+
+        assert!(matches!(document.data[0], Record::Row(_)));
+        assert_eq!(document.transactions().count(), 0);
+    }
+
+    #[test]
+    fn single_values_become_a_record() {
+        assert!(document(Value::Null, &ParseOptions::default()).data.is_empty());
+
+        let document: ParsedDocument = document(json!("Saldo"), &ParseOptions::default());
+        assert_eq!(document.data, vec![Record::Row(json!({ "value": "Saldo" }).as_object().cloned().unwrap())]);
+    }
+}
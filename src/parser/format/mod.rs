@@ -0,0 +1,435 @@
+//! ## Document format detection
+//!
+//! Sniffs the format of an incoming bytestream so `handle_bytestream` can
+//! dispatch it to the right parsing engine. Detection looks at magic bytes,
+//! byte order marks and ZIP directory entries first and only falls back to the
+//! MIME type and file extension hints from [`Parser`] when the content itself
+//! is inconclusive. XML documents aren't supported, they are recognised as
+//! `Xml` so they are rejected rather than read as CSV.
+
+use encoding_rs::UTF_16LE;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tracing::info;
+
+// crate imports
+use crate::parser::csv::encoding::sniff_utf16;
+use crate::Parser;
+
+/// The number of leading bytes inspected for text based heuristics.
+const SNIFF_LEN: usize = 8192;
+
+/// The document formats Aurora knows how to recognise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DocumentFormat {
+    Csv,
+    Pdf,
+    Png,
+    Jpeg,
+    Tiff,
+    Xlsx,
+    Xls,
+    Ods,
+    Mt940,
+    Xml,
+    Unknown,
+}
+
+impl DocumentFormat {
+    /// Returns the snake case name used in API responses.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DocumentFormat::Csv => "csv",
+            DocumentFormat::Pdf => "pdf",
+            DocumentFormat::Png => "png",
+            DocumentFormat::Jpeg => "jpeg",
+            DocumentFormat::Tiff => "tiff",
+            DocumentFormat::Xlsx => "xlsx",
+            DocumentFormat::Xls => "xls",
+            DocumentFormat::Ods => "ods",
+            DocumentFormat::Mt940 => "mt940",
+            DocumentFormat::Xml => "xml",
+            DocumentFormat::Unknown => "unknown",
+        }
+    }
+
+    /// Whether the format is a raster image that has to go through OCR.
+    pub fn is_image(&self) -> bool {
+        matches!(
+            self,
+            DocumentFormat::Png | DocumentFormat::Jpeg | DocumentFormat::Tiff
+        )
+    }
+
+    /// Whether the format is a spreadsheet workbook.
+    pub fn is_spreadsheet(&self) -> bool {
+        matches!(
+            self,
+            DocumentFormat::Xlsx | DocumentFormat::Xls | DocumentFormat::Ods
+        )
+    }
+
+    /// Maps a file extension (without the leading dot) to a format.
+    pub fn from_extension(extension: &str) -> DocumentFormat {
+        match extension.to_lowercase().as_str() {
+            "csv" | "tsv" | "txt" => DocumentFormat::Csv,
+            "pdf" => DocumentFormat::Pdf,
+            "png" => DocumentFormat::Png,
+            "jpg" | "jpeg" => DocumentFormat::Jpeg,
+            "tif" | "tiff" => DocumentFormat::Tiff,
+            "xlsx" => DocumentFormat::Xlsx,
+            "xls" => DocumentFormat::Xls,
+            "ods" => DocumentFormat::Ods,
+            "sta" | "mt940" | "940" => DocumentFormat::Mt940,
+            "xml" => DocumentFormat::Xml,
+            _ => DocumentFormat::Unknown,
+        }
+    }
+
+    /// Maps a MIME type to a format.
+    pub fn from_mime(mime: &str) -> DocumentFormat {
+        match mime.split(';').next().unwrap_or("").trim() {
+            "text/csv" | "text/tab-separated-values" | "application/csv" => DocumentFormat::Csv,
+            "application/pdf" => DocumentFormat::Pdf,
+            "image/png" => DocumentFormat::Png,
+            "image/jpeg" => DocumentFormat::Jpeg,
+            "image/tiff" => DocumentFormat::Tiff,
+            "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" => {
+                DocumentFormat::Xlsx
+            }
+            "application/vnd.ms-excel" => DocumentFormat::Xls,
+            "application/vnd.oasis.opendocument.spreadsheet" => DocumentFormat::Ods,
+            "application/xml" | "text/xml" => DocumentFormat::Xml,
+            _ => DocumentFormat::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for DocumentFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Detects the format of a bytestream.
+///
+/// The content always wins over the hints; the extension and MIME type of the
+/// `parser` are only consulted when sniffing the bytes gives no answer.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the document.
+/// * `parser` - The `Parser` holding the file extension and MIME type hints.
+///
+/// # Returns
+///
+/// The detected `DocumentFormat`, or `DocumentFormat::Unknown`.
+pub fn detect_format(content: &[u8], parser: &Parser) -> DocumentFormat {
+    let sniffed: DocumentFormat = sniff_content(content);
+    if sniffed != DocumentFormat::Unknown {
+        info!("Detected document format from content: {}", sniffed);
+        return sniffed;
+    }
+
+    let from_extension: DocumentFormat = DocumentFormat::from_extension(&parser.file_extension);
+    if from_extension != DocumentFormat::Unknown {
+        info!(
+            "Detected document format from extension: {}",
+            from_extension
+        );
+        return from_extension;
+    }
+
+    let from_mime: DocumentFormat = DocumentFormat::from_mime(&parser.file_mime);
+    info!("Detected document format from MIME type: {}", from_mime);
+    from_mime
+}
+
+/// Sniffs the format purely from the content of the bytestream.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the document.
+///
+/// # Returns
+///
+/// The detected `DocumentFormat`, or `DocumentFormat::Unknown` when the bytes
+/// don't match any known signature.
+pub fn sniff_content(content: &[u8]) -> DocumentFormat {
+    if content.is_empty() {
+        return DocumentFormat::Unknown;
+    }
+
+    // binary signatures
+    if find_subslice(&content[..content.len().min(1024)], b"%PDF-").is_some() {
+        return DocumentFormat::Pdf;
+    }
+    if content.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return DocumentFormat::Png;
+    }
+    if content.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return DocumentFormat::Jpeg;
+    }
+    if content.starts_with(b"II*\0") || content.starts_with(b"MM\0*") {
+        return DocumentFormat::Tiff;
+    }
+    if content.starts_with(&[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1]) {
+        // OLE compound file, the container of legacy `.xls` workbooks
+        return DocumentFormat::Xls;
+    }
+    if content.starts_with(b"PK\x03\x04") {
+        return sniff_zip(content);
+    }
+
+    // text based formats
+    match decode_text_sample(content) {
+        Some(text) => sniff_text(&text),
+        None => DocumentFormat::Unknown,
+    }
+}
+
+/// Inspects the local file headers of a ZIP archive to tell OOXML and
+/// OpenDocument spreadsheets apart from other archives.
+fn sniff_zip(content: &[u8]) -> DocumentFormat {
+    if find_subslice(content, b"application/vnd.oasis.opendocument.spreadsheet").is_some() {
+        return DocumentFormat::Ods;
+    }
+    if find_subslice(content, b"xl/workbook.xml").is_some()
+        || find_subslice(content, b"xl/worksheets/").is_some()
+    {
+        return DocumentFormat::Xlsx;
+    }
+    DocumentFormat::Unknown
+}
+
+/// Classifies a decoded text sample as XML, MT940 or CSV.
+fn sniff_text(text: &str) -> DocumentFormat {
+    let trimmed: &str = text.trim_start();
+    if trimmed.is_empty() {
+        return DocumentFormat::Unknown;
+    }
+
+    // markup often contains commas too, it must not be mistaken for CSV
+    if trimmed.starts_with("<?xml") || (trimmed.starts_with('<') && trimmed.contains("</")) {
+        return DocumentFormat::Xml;
+    }
+
+    if looks_like_mt940(trimmed) {
+        return DocumentFormat::Mt940;
+    }
+
+    if looks_like_delimited(trimmed) {
+        return DocumentFormat::Csv;
+    }
+
+    DocumentFormat::Unknown
+}
+
+/// MT940 files start with a SWIFT block header (`{1:`) or the `:20:`
+/// transaction reference tag and always carry an account (`:25:`) tag.
+fn looks_like_mt940(text: &str) -> bool {
+    let has_line_tag = |tag: &str| text.lines().any(|line| line.trim_start().starts_with(tag));
+    (text.starts_with("{1:") || has_line_tag(":20:")) && has_line_tag(":25:")
+}
+
+/// A text is considered delimited when the first lines share a delimiter.
+fn looks_like_delimited(text: &str) -> bool {
+    let lines: Vec<&str> = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .take(10)
+        .collect();
+
+    // a title or preamble line above the header doesn't have to contain the delimiter
+    let required: usize = lines.len().min(2);
+    required > 0
+        && [',', ';', '\t', '|'].iter().any(|delimiter| {
+            lines.iter().filter(|line| line.contains(*delimiter)).count() >= required
+        })
+}
+
+/// Decodes the leading bytes of the content into text, honouring byte order
+/// marks and recognising UTF-16 without one. Returns `None` when the bytes look binary.
+fn decode_text_sample(content: &[u8]) -> Option<String> {
+    let sample: &[u8] = &content[..content.len().min(SNIFF_LEN)];
+
+    if let Some(rest) = sample.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return Some(String::from_utf8_lossy(rest).into_owned());
+    }
+    if let Some(rest) = sample.strip_prefix(&[0xFF, 0xFE]) {
+        return Some(decode_utf16(rest, u16::from_le_bytes));
+    }
+    if let Some(rest) = sample.strip_prefix(&[0xFE, 0xFF]) {
+        return Some(decode_utf16(rest, u16::from_be_bytes));
+    }
+    // UTF-16 is full of NUL bytes, it must be recognised before binary content is
+    if let Some(encoding) = sniff_utf16(sample) {
+        let to_u16: fn([u8; 2]) -> u16 = if encoding == UTF_16LE {
+            u16::from_le_bytes
+        } else {
+            u16::from_be_bytes
+        };
+        return Some(decode_utf16(sample, to_u16));
+    }
+
+    let control_bytes: usize = sample
+        .iter()
+        .filter(|byte| **byte < 0x09 || (**byte > 0x0D && **byte < 0x20))
+        .count();
+    if sample.contains(&0) || control_bytes * 100 > sample.len() {
+        return None;
+    }
+
+    // non UTF-8 text (e.g. Windows-1252) is still text, the lossy decode keeps
+    // the ASCII structure intact which is all the heuristics need
+    Some(String::from_utf8_lossy(sample).into_owned())
+}
+
+fn decode_utf16(bytes: &[u8], to_u16: fn([u8; 2]) -> u16) -> String {
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| to_u16([pair[0], pair[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MT940: &str = ":20:ABN AMRO BANK NV\r\n:25:123456789\r\n:28:13501/1\r\n:60F:C240130EUR1000,00\r\n";
+
+    fn utf16(text: &str, bom: [u8; 2], to_bytes: fn(u16) -> [u8; 2]) -> Vec<u8> {
+        let mut bytes: Vec<u8> = bom.to_vec();
+        bytes.extend(text.encode_utf16().flat_map(to_bytes));
+        bytes
+    }
+
+    #[test]
+    fn sniffs_binary_signatures() {
+        assert_eq!(sniff_content(b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n"), DocumentFormat::Pdf);
+        // some generators write junk before the header, readers accept it within 1024 bytes
+        assert_eq!(sniff_content(b"\r\n\r\n%PDF-1.4\n"), DocumentFormat::Pdf);
+        assert_eq!(sniff_content(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), DocumentFormat::Png);
+        assert_eq!(sniff_content(&[0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10]), DocumentFormat::Jpeg);
+        assert_eq!(sniff_content(b"II*\0\x08\0\0\0"), DocumentFormat::Tiff);
+        assert_eq!(sniff_content(b"MM\0*\0\0\0\x08"), DocumentFormat::Tiff);
+        assert_eq!(
+            sniff_content(&[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1, 0x00]),
+            DocumentFormat::Xls
+        );
+    }
+
+    #[test]
+    fn sniffs_zip_entries() {
+        let xlsx: &[u8] = b"PK\x03\x04\x14\0\x06\0\x08\0[Content_Types].xml...PK\x03\x04xl/workbook.xml";
+        assert_eq!(sniff_content(xlsx), DocumentFormat::Xlsx);
+
+        let ods: &[u8] = b"PK\x03\x04\x14\0\0\0\0\0mimetypeapplication/vnd.oasis.opendocument.spreadsheetPK";
+        assert_eq!(sniff_content(ods), DocumentFormat::Ods);
+
+        let docx: &[u8] = b"PK\x03\x04\x14\0\x06\0\x08\0word/document.xml";
+        assert_eq!(sniff_content(docx), DocumentFormat::Unknown);
+    }
+
+    #[test]
+    fn sniffs_mt940() {
+        assert_eq!(sniff_content(MT940.as_bytes()), DocumentFormat::Mt940);
+        let swift: String = format!("{{1:F01ABNANL2AXXXX0000000000}}{{4:\r\n{}-}}", MT940);
+        assert_eq!(sniff_content(swift.as_bytes()), DocumentFormat::Mt940);
+        // a `:20:` reference alone isn't a statement
+        assert_eq!(sniff_content(b":20:REFERENCE\n"), DocumentFormat::Unknown);
+    }
+
+    #[test]
+    fn sniffs_delimited_text() {
+        let comma: &[u8] = b"Type,Product,Started Date,Amount\nTRANSFER,Savings,2024-01-01 01:24:30,50.00\n";
+        assert_eq!(sniff_content(comma), DocumentFormat::Csv);
+
+        let semicolon: &[u8] = b"Datum;Naam;Bedrag\n31-01-2024;Albert Heijn;-12,50\n";
+        assert_eq!(sniff_content(semicolon), DocumentFormat::Csv);
+
+        let tab: &[u8] = b"Buchungstag\tBetrag\n31.01.2024\t-12,50\n";
+        assert_eq!(sniff_content(tab), DocumentFormat::Csv);
+
+        // Windows-1252 text isn't UTF-8, it's still text
+        let latin1: &[u8] = b"Date;Libell\xE9;Montant\n31/01/2024;Caf\xE9;-3,20\n";
+        assert_eq!(sniff_content(latin1), DocumentFormat::Csv);
+
+        assert_eq!(sniff_content(b"Just a sentence without columns\n"), DocumentFormat::Unknown);
+    }
+
+    #[test]
+    fn honours_byte_order_marks() {
+        let csv: &str = "Datum;Bedrag\n31-01-2024;-12,50\n";
+
+        let mut utf8: Vec<u8> = vec![0xEF, 0xBB, 0xBF];
+        utf8.extend_from_slice(csv.as_bytes());
+        assert_eq!(sniff_content(&utf8), DocumentFormat::Csv);
+
+        let utf16_le: Vec<u8> = utf16(csv, [0xFF, 0xFE], u16::to_le_bytes);
+        assert_eq!(sniff_content(&utf16_le), DocumentFormat::Csv);
+
+        let utf16_be: Vec<u8> = utf16(MT940, [0xFE, 0xFF], u16::to_be_bytes);
+        assert_eq!(sniff_content(&utf16_be), DocumentFormat::Mt940);
+    }
+
+    #[test]
+    fn sniffs_utf16_without_a_byte_order_mark() {
+        let csv: &str = "Datum;Naam;Bedrag\n31-01-2024;Albert Heijn;-12,50\n";
+
+        let utf16_le: Vec<u8> = csv.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        assert_eq!(sniff_content(&utf16_le), DocumentFormat::Csv);
+
+        let utf16_be: Vec<u8> = MT940.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(sniff_content(&utf16_be), DocumentFormat::Mt940);
+    }
+
+    #[test]
+    fn sniffs_xml() {
+        let camt: &[u8] = b"<?xml version=\"1.0\"?>\n<Document><Amt Ccy=\"EUR\">1,00</Amt>\n<Amt>2,00</Amt></Document>";
+        assert_eq!(sniff_content(camt), DocumentFormat::Xml);
+        let without_declaration: &[u8] = b"<Document>\n<Amt>1,00</Amt>;<Amt>2,00</Amt>\n</Document>";
+        assert_eq!(sniff_content(without_declaration), DocumentFormat::Xml);
+
+        // the content wins over a misleading extension
+        let parser: Parser = Parser {
+            file_url: "camt053.csv".to_string(),
+            file_extension: "csv".to_string(),
+            file_mime: "text/csv".to_string(),
+        };
+        assert_eq!(detect_format(camt, &parser), DocumentFormat::Xml);
+    }
+
+    #[test]
+    fn binary_content_is_unknown() {
+        assert_eq!(sniff_content(&[0x00, 0x01, 0x02, 0x03, 0x04]), DocumentFormat::Unknown);
+        assert_eq!(sniff_content(b""), DocumentFormat::Unknown);
+    }
+
+    #[test]
+    fn falls_back_to_the_hints_when_the_content_is_inconclusive() {
+        let parser: Parser = Parser {
+            file_url: "statement.xlsx".to_string(),
+            file_extension: "xlsx".to_string(),
+            file_mime: "application/octet-stream".to_string(),
+        };
+        assert_eq!(detect_format(b"\x01\x02", &parser), DocumentFormat::Xlsx);
+        // the content wins over the extension
+        assert_eq!(detect_format(b"%PDF-1.7\n", &parser), DocumentFormat::Pdf);
+
+        let parser: Parser = Parser {
+            file_url: String::new(),
+            file_extension: String::new(),
+            file_mime: "image/png".to_string(),
+        };
+        assert_eq!(detect_format(b"\x01\x02", &parser), DocumentFormat::Png);
+    }
+}
@@ -1,5 +1,10 @@
 pub mod csv;
 pub mod builder;
 pub mod caster;
+pub mod document;
 pub mod schema;
-pub mod pdf;
\ No newline at end of file
+pub mod pdf;
+pub mod format;
+pub mod mt940;
+pub mod options;
+pub mod spreadsheet;
\ No newline at end of file
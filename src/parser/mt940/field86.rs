@@ -0,0 +1,245 @@
+//! ## `:86:` information to account owner
+//!
+//! ABN AMRO fills the `:86:` tag in one of two ways. SEPA transactions use the
+//! structured layout with slash delimited codes
+//! (`/TRTP/SEPA OVERBOEKING/IBAN/NL12ABNA0123456789/BIC/ABNANL2A/NAME/J DOE/REMI/Invoice 123/EREF/NOTPROVIDED`),
+//! while older exports and card payments use free text with labels such as
+//! `Naam:` and `Omschrijving:`.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// The codes ABN AMRO uses in structured `:86:` fields.
+const STRUCTURED_CODES: &str =
+    "TRTP|IBAN|BIC|NAME|REMI|EREF|MARF|CSID|PREF|RTRN|ORDP|BENM|ID|ADDR|SVCL|NRTX";
+
+/// The labels ABN AMRO uses in unstructured `:86:` fields.
+const UNSTRUCTURED_LABELS: &str = "IBAN|BIC|Naam|Omschrijving|Kenmerk|Machtiging|Incassant";
+
+/// The decoded content of an `:86:` field.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Field86 {
+    /// The transaction type (`/TRTP/`), e.g. `SEPA OVERBOEKING`.
+    pub transaction_type: Option<String>,
+    /// The IBAN of the counterparty (`/IBAN/`).
+    pub counterparty_iban: Option<String>,
+    /// The BIC of the counterparty's bank (`/BIC/`).
+    pub counterparty_bic: Option<String>,
+    /// The name of the counterparty (`/NAME/`).
+    pub counterparty_name: Option<String>,
+    /// The remittance information (`/REMI/`).
+    pub remittance_information: Option<String>,
+    /// The end-to-end reference (`/EREF/`).
+    pub end_to_end_reference: Option<String>,
+    /// The SEPA direct debit mandate reference (`/MARF/`).
+    pub mandate_reference: Option<String>,
+    /// The SEPA creditor identifier (`/CSID/`).
+    pub creditor_id: Option<String>,
+    /// A human readable description of the transaction.
+    pub description: String,
+}
+
+/// Decodes the raw content of an `:86:` tag.
+///
+/// # Arguments
+///
+/// * `raw` - The content of the tag, continuation lines separated by `\n`.
+///
+/// # Returns
+///
+/// A `Field86` with every sub-field that could be found.
+pub fn decode_field86(raw: &str) -> Field86 {
+    if raw.trim_start().starts_with('/') {
+        // structured fields wrap at a fixed width, often in the middle of a word
+        decode_structured(&raw.replace(['\r', '\n'], ""))
+    } else {
+        decode_unstructured(&collapse_whitespace(&raw.replace(['\r', '\n'], " ")))
+    }
+}
+
+fn decode_structured(raw: &str) -> Field86 {
+    static CODE_RE: OnceLock<Regex> = OnceLock::new();
+    let code_re: &Regex = CODE_RE
+        .get_or_init(|| Regex::new(&format!("/({})/", STRUCTURED_CODES)).expect("valid regex"));
+
+    let mut field: Field86 = Field86::default();
+    let matches: Vec<(usize, usize, String)> = code_re
+        .captures_iter(raw)
+        .map(|captures| {
+            let whole = captures.get(0).expect("group 0 always matches");
+            (whole.start(), whole.end(), captures[1].to_string())
+        })
+        .collect();
+
+    for (index, (_, value_start, code)) in matches.iter().enumerate() {
+        let value_end: usize = matches
+            .get(index + 1)
+            .map(|(next_start, _, _)| *next_start)
+            .unwrap_or(raw.len());
+        let value: String = raw[*value_start..value_end]
+            .trim_end_matches('/')
+            .trim()
+            .to_string();
+        if value.is_empty() {
+            continue;
+        }
+
+        match code.as_str() {
+            "TRTP" => field.transaction_type = Some(value),
+            "IBAN" => field.counterparty_iban = Some(value),
+            "BIC" => field.counterparty_bic = Some(value),
+            "NAME" => field.counterparty_name = Some(value),
+            "REMI" => field.remittance_information = Some(strip_remittance_prefix(&value)),
+            "EREF" if value != "NOTPROVIDED" => field.end_to_end_reference = Some(value),
+            "MARF" => field.mandate_reference = Some(value),
+            "CSID" => field.creditor_id = Some(value),
+            _ => {}
+        }
+    }
+
+    field.description = field
+        .remittance_information
+        .clone()
+        .or_else(|| field.counterparty_name.clone())
+        .or_else(|| field.transaction_type.clone())
+        .unwrap_or_default();
+
+    field
+}
+
+/// Strips the `USTD//` and `STRD/CUR/` markers ABN prepends to remittance info.
+fn strip_remittance_prefix(value: &str) -> String {
+    let stripped: &str = value
+        .strip_prefix("USTD//")
+        .or_else(|| value.strip_prefix("STRD/CUR/"))
+        .unwrap_or(value);
+    stripped.trim().to_string()
+}
+
+fn decode_unstructured(raw: &str) -> Field86 {
+    static LABEL_RE: OnceLock<Regex> = OnceLock::new();
+    let label_re: &Regex = LABEL_RE.get_or_init(|| {
+        Regex::new(&format!(r"\b({}):\s*", UNSTRUCTURED_LABELS)).expect("valid regex")
+    });
+
+    let mut field: Field86 = Field86::default();
+    let matches: Vec<(usize, usize, String)> = label_re
+        .captures_iter(raw)
+        .map(|captures| {
+            let whole = captures.get(0).expect("group 0 always matches");
+            (whole.start(), whole.end(), captures[1].to_string())
+        })
+        .collect();
+
+    // text in front of the first label holds the transaction type, e.g. `SEPA Overboeking`
+    let leading: &str = matches
+        .first()
+        .map(|(start, _, _)| &raw[..*start])
+        .unwrap_or(raw)
+        .trim();
+    if !leading.is_empty() && !matches.is_empty() {
+        field.transaction_type = Some(leading.to_string());
+    }
+
+    for (index, (_, value_start, label)) in matches.iter().enumerate() {
+        let value_end: usize = matches
+            .get(index + 1)
+            .map(|(next_start, _, _)| *next_start)
+            .unwrap_or(raw.len());
+        let value: String = raw[*value_start..value_end].trim().to_string();
+        if value.is_empty() {
+            continue;
+        }
+
+        match label.as_str() {
+            "IBAN" => field.counterparty_iban = Some(value),
+            "BIC" => field.counterparty_bic = Some(value),
+            "Naam" => field.counterparty_name = Some(value),
+            "Omschrijving" => field.remittance_information = Some(value),
+            "Kenmerk" => field.end_to_end_reference = Some(value),
+            "Machtiging" => field.mandate_reference = Some(value),
+            "Incassant" => field.creditor_id = Some(value),
+            _ => {}
+        }
+    }
+
+    // card payments (`BEA`, `GEA`) carry no labels, the whole text is the description
+    field.description = field
+        .remittance_information
+        .clone()
+        .or_else(|| field.counterparty_name.clone())
+        .unwrap_or_else(|| raw.trim().to_string());
+
+    field
+}
+
+fn collapse_whitespace(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_structured_sepa_transfers() {
+        let raw: &str = "/TRTP/SEPA OVERBOEKING/IBAN/NL12ABNA0123456789/BIC/ABNANL2A/NAME/J\nDOE/REMI/USTD//Invoice 123/EREF/NOTPROVIDED";
+        let field: Field86 = decode_field86(raw);
+
+        assert_eq!(field.transaction_type.as_deref(), Some("SEPA OVERBOEKING"));
+        assert_eq!(field.counterparty_iban.as_deref(), Some("NL12ABNA0123456789"));
+        assert_eq!(field.counterparty_bic.as_deref(), Some("ABNANL2A"));
+        // structured fields wrap at a fixed width, the line break isn't a space
+        assert_eq!(field.counterparty_name.as_deref(), Some("JDOE"));
+        assert_eq!(field.remittance_information.as_deref(), Some("Invoice 123"));
+        assert_eq!(field.end_to_end_reference, None);
+        assert_eq!(field.description, "Invoice 123");
+    }
+
+    #[test]
+    fn decodes_structured_direct_debits() {
+        let raw: &str = "/TRTP/SEPA Incasso algemeen doorlopend/CSID/NL98ZZZ999999999999/NAME/Energie\n Maatschappij BV/MARF/M-2023-0042/REMI/STRD/CUR/Termijn januari/IBAN/NL91RABO0315273637/BIC/RABONL2U/EREF/E2E-0042";
+        let field: Field86 = decode_field86(raw);
+
+        assert_eq!(field.creditor_id.as_deref(), Some("NL98ZZZ999999999999"));
+        assert_eq!(field.counterparty_name.as_deref(), Some("Energie Maatschappij BV"));
+        assert_eq!(field.mandate_reference.as_deref(), Some("M-2023-0042"));
+        assert_eq!(field.remittance_information.as_deref(), Some("Termijn januari"));
+        assert_eq!(field.end_to_end_reference.as_deref(), Some("E2E-0042"));
+    }
+
+    #[test]
+    fn structured_fields_without_remittance_fall_back_to_the_name() {
+        let field: Field86 =
+            decode_field86("/TRTP/iDEAL/IBAN/NL12ABNA0123456789/NAME/Webshop BV/REMI//EREF/123");
+        assert_eq!(field.remittance_information, None);
+        assert_eq!(field.description, "Webshop BV");
+    }
+
+    #[test]
+    fn decodes_labelled_free_text() {
+        let raw: &str = "SEPA Overboeking                 IBAN: NL91RABO0315273637\nBIC: RABONL2U                    Naam: Acme BV\nOmschrijving: Huur januari\nKenmerk: 2024-01";
+        let field: Field86 = decode_field86(raw);
+
+        assert_eq!(field.transaction_type.as_deref(), Some("SEPA Overboeking"));
+        assert_eq!(field.counterparty_iban.as_deref(), Some("NL91RABO0315273637"));
+        assert_eq!(field.counterparty_bic.as_deref(), Some("RABONL2U"));
+        assert_eq!(field.counterparty_name.as_deref(), Some("Acme BV"));
+        assert_eq!(field.remittance_information.as_deref(), Some("Huur januari"));
+        assert_eq!(field.end_to_end_reference.as_deref(), Some("2024-01"));
+        assert_eq!(field.description, "Huur januari");
+    }
+
+    #[test]
+    fn card_payments_are_described_by_their_whole_text() {
+        let field: Field86 =
+            decode_field86("BEA   NR:XXXX1234 31.01.24/12.15 ALBERT HEIJN 1234,PAS123\nAMSTERDAM");
+        assert_eq!(field.transaction_type, None);
+        assert_eq!(field.counterparty_name, None);
+        assert_eq!(
+            field.description,
+            "BEA NR:XXXX1234 31.01.24/12.15 ALBERT HEIJN 1234,PAS123 AMSTERDAM"
+        );
+    }
+}
@@ -0,0 +1,491 @@
+//! ## SWIFT MT940 customer statements
+//!
+//! Parses MT940 statement files into statements and transactions and flattens
+//! the transactions into the same row-of-objects JSON the CSV parser produces,
+//! so they can flow through provider detection and the caster registry.
+//!
+//! Supported tags: `:20:`, `:25:`, `:28C:`, `:60F:`/`:60M:`, `:61:`, `:86:`,
+//! `:62F:`/`:62M:` and `:64:`.
+
+pub mod field86;
+
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, NaiveDate};
+use regex::Regex;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::OnceLock;
+use tracing::{info, warn};
+
+// crate imports
+use crate::parser::caster::diagnostic::Diagnostic;
+use crate::parser::caster::money::balances_reconcile;
+use crate::parser::caster::number::try_cast_to_decimal;
+use crate::parser::mt940::field86::{decode_field86, Field86};
+
+/// A booked or opening/closing balance (`:60F:`, `:62F:`, `:64:`).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Mt940Balance {
+    /// The date of the balance as `YYMMDD`.
+    pub date: String,
+    /// The ISO 4217 currency code.
+    pub currency: String,
+    /// The signed amount with a `.` decimal separator.
+    pub amount: String,
+}
+
+/// A single statement line (`:61:`) with its information (`:86:`).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Mt940Transaction {
+    /// The value date as `YYMMDD`.
+    pub value_date: String,
+    /// The entry (booking) date as `YYMMDD`, the value date when absent.
+    pub entry_date: String,
+    /// The debit/credit mark: `C`, `D`, `RC` (reversal of credit) or `RD`.
+    pub debit_credit_mark: String,
+    /// The signed amount with a `.` decimal separator.
+    pub amount: String,
+    /// The SWIFT transaction type identification code, e.g. `N658`.
+    pub transaction_code: String,
+    /// The reference for the account owner.
+    pub reference: String,
+    /// The bank reference following `//`, if any.
+    pub bank_reference: Option<String>,
+    /// The raw content of the `:86:` tag.
+    pub information: String,
+    /// The decoded content of the `:86:` tag.
+    pub details: Field86,
+}
+
+/// A single statement, from `:20:` up to the closing balance.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Mt940Statement {
+    /// The transaction reference number (`:20:`).
+    pub transaction_reference: String,
+    /// The account identification (`:25:`), usually the IBAN.
+    pub account: String,
+    /// The statement and sequence number (`:28C:`).
+    pub statement_number: String,
+    /// The opening balance (`:60F:` or `:60M:`).
+    pub opening_balance: Option<Mt940Balance>,
+    /// The statement lines (`:61:` with `:86:`).
+    pub transactions: Vec<Mt940Transaction>,
+    /// The closing balance (`:62F:` or `:62M:`).
+    pub closing_balance: Option<Mt940Balance>,
+    /// The closing available balance (`:64:`).
+    pub available_balance: Option<Mt940Balance>,
+}
+
+/// Parses the content of an MT940 file into its statements.
+///
+/// # Arguments
+///
+/// * `content` - The text content of the MT940 file.
+///
+/// # Returns
+///
+/// A `Result` containing the statements in file order, or an error if a tag is malformed.
+pub fn parse_mt940(content: &str) -> Result<Vec<Mt940Statement>> {
+    let mut statements: Vec<Mt940Statement> = vec![];
+
+    for (tag, value) in split_tags(content) {
+        if tag == "20" {
+            statements.push(Mt940Statement {
+                transaction_reference: value.trim().to_string(),
+                ..Default::default()
+            });
+            continue;
+        }
+
+        let Some(statement) = statements.last_mut() else {
+            warn!(
+                "Skipping MT940 tag :{}: found before the first :20: tag",
+                tag
+            );
+            continue;
+        };
+
+        match tag.as_str() {
+            "25" => statement.account = value.trim().to_string(),
+            "28C" | "28" => statement.statement_number = value.trim().to_string(),
+            "60F" | "60M" => statement.opening_balance = Some(parse_balance(&value)?),
+            "61" => statement.transactions.push(parse_statement_line(&value)?),
+            "86" => match statement.transactions.last_mut() {
+                Some(transaction) if transaction.information.is_empty() => {
+                    transaction.details = decode_field86(&value);
+                    transaction.information = value;
+                }
+                // an :86: after the closing balance is information about the statement itself
+                _ => info!("Ignoring statement level :86: tag"),
+            },
+            "62F" | "62M" => statement.closing_balance = Some(parse_balance(&value)?),
+            "64" => statement.available_balance = Some(parse_balance(&value)?),
+            _ => info!("Ignoring unsupported MT940 tag :{}:", tag),
+        }
+    }
+
+    if statements.is_empty() {
+        bail!("No MT940 statements found");
+    }
+
+    info!("Parsed {} MT940 statements", statements.len());
+    Ok(statements)
+}
+
+/// Flattens statements into one JSON object per transaction.
+///
+/// Every value is a string, mirroring the output of the CSV parser, and the
+/// balance after each transaction is derived from the opening balance.
+///
+/// # Arguments
+///
+/// * `statements` - The parsed statements.
+///
+/// # Returns
+///
+/// A `Value` holding a JSON array of transaction objects, and a warning per
+/// statement that doesn't reconcile with its closing balance.
+pub fn statements_to_json(statements: &[Mt940Statement]) -> (Value, Vec<Diagnostic>) {
+    let mut records: Vec<Value> = vec![];
+    let mut warnings: Vec<Diagnostic> = vec![];
+
+    for statement in statements {
+        let currency: String = statement
+            .opening_balance
+            .as_ref()
+            .or(statement.closing_balance.as_ref())
+            .map(|balance| balance.currency.clone())
+            .unwrap_or_default();
+        let opening: Option<Decimal> = statement
+            .opening_balance
+            .as_ref()
+            .and_then(|opening| try_cast_to_decimal(&opening.amount));
+        let amounts: Vec<Option<Decimal>> = statement
+            .transactions
+            .iter()
+            .map(|transaction| try_cast_to_decimal(&transaction.amount))
+            .collect();
+        warnings.extend(verify_closing_balance(statement, opening, &amounts));
+
+        let mut balance: Option<Decimal> = opening;
+        for (transaction, amount) in statement.transactions.iter().zip(amounts) {
+            balance = balance
+                .zip(amount)
+                .and_then(|(current, amount)| current.checked_add(amount));
+            let details: &Field86 = &transaction.details;
+
+            records.push(json!({
+                "account": statement.account,
+                "statement_number": statement.statement_number,
+                "value_date": transaction.value_date,
+                "entry_date": transaction.entry_date,
+                "amount": transaction.amount,
+                "currency": currency,
+                "balance": balance.map(|value| value.to_string()).unwrap_or_default(),
+                "transaction_code": transaction.transaction_code,
+                "reference": transaction.reference,
+                "transaction_type": details.transaction_type.clone().unwrap_or_default(),
+                "counterparty_iban": details.counterparty_iban.clone().unwrap_or_default(),
+                "counterparty_bic": details.counterparty_bic.clone().unwrap_or_default(),
+                "counterparty_name": details.counterparty_name.clone().unwrap_or_default(),
+                "end_to_end_reference": details.end_to_end_reference.clone().unwrap_or_default(),
+                "description": details.description,
+            }));
+        }
+    }
+
+    (json!(records), warnings)
+}
+
+/// Warns when the opening balance plus the booked amounts doesn't add up to
+/// the closing balance, which points at a truncated or misparsed file.
+fn verify_closing_balance(
+    statement: &Mt940Statement,
+    opening: Option<Decimal>,
+    amounts: &[Option<Decimal>],
+) -> Option<Diagnostic> {
+    let closing: Option<Decimal> = statement
+        .closing_balance
+        .as_ref()
+        .and_then(|closing| try_cast_to_decimal(&closing.amount));
+    let movements: Option<Vec<Decimal>> = amounts.iter().copied().collect();
+
+    let (opening, movements, closing) = (opening?, movements?, closing?);
+    if balances_reconcile(opening, movements, closing) {
+        return None;
+    }
+    let message: String = format!(
+        "MT940 statement {} of account {} does not reconcile with its closing balance {}",
+        statement.statement_number, statement.account, closing
+    );
+    warn!("{}", message);
+    Some(Diagnostic::document(message))
+}
+
+/// Splits the file into `(tag, value)` pairs, joining continuation lines with
+/// `\n`. SWIFT block headers, trailers and bank specific preambles such as
+/// ABN AMRO's `ABNANL2A` / `940` lines are skipped. Only a lone `-` or `-}`
+/// ends a message, narrative lines may start with a `-` themselves.
+fn split_tags(content: &str) -> Vec<(String, String)> {
+    static TAG_RE: OnceLock<Regex> = OnceLock::new();
+    let tag_re: &Regex =
+        TAG_RE.get_or_init(|| Regex::new(r"^:(\d{2}[A-Z]?):(.*)$").expect("valid regex"));
+
+    let mut tags: Vec<(String, String)> = vec![];
+    let mut current: Option<(String, String)> = None;
+
+    for line in content.lines() {
+        let line: &str = line.trim_end_matches('\r');
+        let line: &str = line.strip_prefix("{4:").unwrap_or(line);
+
+        if let Some(captures) = tag_re.captures(line) {
+            if let Some(finished) = current.take() {
+                tags.push(finished);
+            }
+            current = Some((captures[1].to_string(), captures[2].to_string()));
+        } else if line == "-"
+            || line.starts_with("-}")
+            || line.starts_with('{')
+            || line.starts_with('}')
+        {
+            if let Some(finished) = current.take() {
+                tags.push(finished);
+            }
+        } else if let Some((_, value)) = current.as_mut() {
+            value.push('\n');
+            value.push_str(line);
+        }
+    }
+
+    if let Some(finished) = current.take() {
+        tags.push(finished);
+    }
+    tags
+}
+
+/// Parses a balance tag: `C240131EUR1234,56`.
+fn parse_balance(value: &str) -> Result<Mt940Balance> {
+    static BALANCE_RE: OnceLock<Regex> = OnceLock::new();
+    let balance_re: &Regex = BALANCE_RE
+        .get_or_init(|| Regex::new(r"^([CD])(\d{6})([A-Z]{3})([\d,]+)").expect("valid regex"));
+
+    let captures = balance_re
+        .captures(value.trim())
+        .with_context(|| format!("Malformed MT940 balance: {}", value))?;
+
+    Ok(Mt940Balance {
+        date: captures[2].to_string(),
+        currency: captures[3].to_string(),
+        amount: signed_amount(&captures[4], &captures[1]),
+    })
+}
+
+/// Parses a statement line: `240131` `0131` `D` `12,50` `N658` `NONREF//bankref`.
+fn parse_statement_line(value: &str) -> Result<Mt940Transaction> {
+    static LINE_RE: OnceLock<Regex> = OnceLock::new();
+    let line_re: &Regex = LINE_RE.get_or_init(|| {
+        Regex::new(r"^(\d{6})(\d{4})?(RC|RD|C|D)([A-Z])?([\d,]+)([NFS][A-Z0-9]{3})([^\n]*)")
+            .expect("valid regex")
+    });
+
+    let captures = line_re
+        .captures(value.trim_start())
+        .with_context(|| format!("Malformed MT940 statement line: {}", value))?;
+
+    let value_date: String = captures[1].to_string();
+    let entry_date: String = match captures.get(2) {
+        Some(entry) => entry_date_with_year(&value_date, entry.as_str())?,
+        None => value_date.clone(),
+    };
+
+    let (reference, bank_reference) = match captures[7].split_once("//") {
+        Some((reference, bank_reference)) => {
+            (reference.trim(), Some(bank_reference.trim().to_string()))
+        }
+        None => (captures[7].trim(), None),
+    };
+
+    Ok(Mt940Transaction {
+        value_date,
+        entry_date,
+        debit_credit_mark: captures[3].to_string(),
+        amount: signed_amount(&captures[5], &captures[3]),
+        transaction_code: captures[6].to_string(),
+        reference: reference.to_string(),
+        bank_reference,
+        information: String::new(),
+        details: Field86::default(),
+    })
+}
+
+/// The entry date only carries `MMDD`, the year is taken from the value date
+/// and corrected when the booking crosses a year boundary.
+fn entry_date_with_year(value_date: &str, entry_mmdd: &str) -> Result<String> {
+    let value: NaiveDate = NaiveDate::parse_from_str(value_date, "%y%m%d")
+        .with_context(|| format!("Malformed MT940 value date: {}", value_date))?;
+
+    let month: u32 = entry_mmdd[..2].parse()?;
+    let mut year: i32 = value.year();
+    if value.month() == 12 && month == 1 {
+        year += 1;
+    } else if value.month() == 1 && month == 12 {
+        year -= 1;
+    }
+
+    Ok(format!("{:02}{}", year.rem_euclid(100), entry_mmdd))
+}
+
+/// Converts an MT940 amount (`1234,56`) into a signed amount (`-1234.56`).
+///
+/// Debits and reversals of credits reduce the balance.
+fn signed_amount(amount: &str, mark: &str) -> String {
+    let amount: String = amount.replace(',', ".");
+    let amount: &str = amount.trim_end_matches('.');
+    match mark {
+        "D" | "RC" => format!("-{}", amount),
+        _ => amount.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An ABN AMRO export with a structured SEPA transfer, a card payment and a
+    /// labelled free text transfer whose description continues on a line starting with `-`.
+    const ABN_AMRO: &str = "ABNANL2A\r
+940\r
+ABNANL2A\r
+:20:ABN AMRO BANK NV\r
+:25:123456789\r
+:28:24001/1\r
+:60F:C240130EUR1000,00\r
+:61:2401310131D12,50N658NONREF\r
+:86:/TRTP/SEPA OVERBOEKING/IBAN/NL12ABNA0123456789/BIC/ABNANL2A/NAME/\r
+J DOE/REMI/Invoice 123/EREF/NOTPROVIDED\r
+:61:2401310131D5,95N426NONREF\r
+:86:BEA   NR:XXXX1234 31.01.24/12.15 ALBERT HEIJN 1234,PAS123\r
+AMSTERDAM\r
+:61:2402010201C250,00N541NONREF//B4A01PG0DE5FQ0KH\r
+:86:SEPA Overboeking                 IBAN: NL91RABO0315273637\r
+BIC: RABONL2U                    Naam: Acme BV\r
+Omschrijving: Correctie factuur 2024-01\r
+-12,50 teveel betaald\r
+:62F:C240201EUR1231,55\r
+-\r
+";
+
+    #[test]
+    fn parses_an_abn_amro_statement() {
+        let statements: Vec<Mt940Statement> = parse_mt940(ABN_AMRO).unwrap();
+        assert_eq!(statements.len(), 1);
+
+        let statement: &Mt940Statement = &statements[0];
+        assert_eq!(statement.transaction_reference, "ABN AMRO BANK NV");
+        assert_eq!(statement.account, "123456789");
+        assert_eq!(statement.statement_number, "24001/1");
+        assert_eq!(
+            statement.opening_balance,
+            Some(Mt940Balance {
+                date: "240130".to_string(),
+                currency: "EUR".to_string(),
+                amount: "1000.00".to_string(),
+            })
+        );
+        assert_eq!(statement.closing_balance.as_ref().unwrap().amount, "1231.55");
+        assert_eq!(statement.transactions.len(), 3);
+
+        let transfer: &Mt940Transaction = &statement.transactions[0];
+        assert_eq!(transfer.value_date, "240131");
+        assert_eq!(transfer.entry_date, "240131");
+        assert_eq!(transfer.debit_credit_mark, "D");
+        assert_eq!(transfer.amount, "-12.50");
+        assert_eq!(transfer.transaction_code, "N658");
+        assert_eq!(transfer.reference, "NONREF");
+        assert_eq!(transfer.bank_reference, None);
+        assert_eq!(transfer.details.counterparty_name.as_deref(), Some("J DOE"));
+
+        let credit: &Mt940Transaction = &statement.transactions[2];
+        assert_eq!(credit.amount, "250.00");
+        assert_eq!(credit.bank_reference.as_deref(), Some("B4A01PG0DE5FQ0KH"));
+    }
+
+    #[test]
+    fn narrative_lines_starting_with_a_dash_are_kept() {
+        let statements: Vec<Mt940Statement> = parse_mt940(ABN_AMRO).unwrap();
+        let credit: &Mt940Transaction = &statements[0].transactions[2];
+        assert!(credit.information.ends_with("\n-12,50 teveel betaald"));
+        assert_eq!(
+            credit.details.description,
+            "Correctie factuur 2024-01 -12,50 teveel betaald"
+        );
+    }
+
+    #[test]
+    fn swift_blocks_and_multiple_statements() {
+        let content: &str = "{1:F01ABNANL2AXXXX0000000000}{2:I940ABNANL2AXXXXN}{4:
+:20:REF1
+:25:NL12ABNA0123456789
+:28C:1/1
+:60F:C231229EUR100,00
+:61:2312290102D10,00N426NONREF
+:86:GEA   NR:XXXX1234 29.12.23/18.01 GELDAUTOMAAT
+:62F:C240102EUR90,00
+-}{5:{CHK:123456789ABC}}
+{1:F01ABNANL2AXXXX0000000000}{4:
+:20:REF2
+:25:NL12ABNA0123456789
+:60M:C240102EUR90,00
+:62M:C240102EUR90,00
+-}";
+        let statements: Vec<Mt940Statement> = parse_mt940(content).unwrap();
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[0].statement_number, "1/1");
+        // the booking crosses the year boundary
+        assert_eq!(statements[0].transactions[0].entry_date, "240102");
+        assert_eq!(
+            statements[0].transactions[0].details.description,
+            "GEA NR:XXXX1234 29.12.23/18.01 GELDAUTOMAAT"
+        );
+        assert_eq!(statements[1].transaction_reference, "REF2");
+        assert!(statements[1].transactions.is_empty());
+    }
+
+    #[test]
+    fn rejects_malformed_content() {
+        assert!(parse_mt940("Datum;Bedrag\n31-01-2024;12,50\n").is_err());
+        assert!(parse_mt940(":20:REF\n:25:123\n:60F:C24013EUR1,00\n").is_err());
+        assert!(parse_mt940(":20:REF\n:25:123\n:61:2401310131X12,50N658NONREF\n").is_err());
+    }
+
+    #[test]
+    fn flattens_transactions_with_running_balances() {
+        let statements: Vec<Mt940Statement> = parse_mt940(ABN_AMRO).unwrap();
+        let (rows, warnings): (Value, Vec<Diagnostic>) = statements_to_json(&statements);
+
+        assert!(warnings.is_empty());
+        let rows: &Vec<Value> = rows.as_array().unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0]["currency"], "EUR");
+        assert_eq!(rows[0]["balance"], "987.50");
+        assert_eq!(rows[0]["counterparty_iban"], "NL12ABNA0123456789");
+        assert_eq!(rows[0]["end_to_end_reference"], "");
+        assert_eq!(rows[1]["balance"], "981.55");
+        assert_eq!(rows[2]["balance"], "1231.55");
+        assert_eq!(rows[2]["counterparty_name"], "Acme BV");
+    }
+
+    #[test]
+    fn a_statement_that_does_not_reconcile_is_a_warning() {
+        let truncated: String = ABN_AMRO.replace(":62F:C240201EUR1231,55", ":62F:C240201EUR1244,05");
+        let statements: Vec<Mt940Statement> = parse_mt940(&truncated).unwrap();
+        let (rows, warnings): (Value, Vec<Diagnostic>) = statements_to_json(&statements);
+
+        assert_eq!(rows.as_array().unwrap().len(), 3);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].row, None);
+        assert!(warnings[0].error.reason.contains("24001/1"));
+        assert!(warnings[0].error.reason.contains("1244.05"));
+    }
+}
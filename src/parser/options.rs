@@ -0,0 +1,85 @@
+//! ## Parse options
+//!
+//! Per-request options controlling how a document is parsed and which shape
+//! the output takes. Every field has a default so clients only send what they
+//! want to change.
+
+use serde::{Deserialize, Serialize};
+
+// crate imports
+use crate::ocr::OcrOptions;
+
+/// The shape transactions are returned in.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputShape {
+    /// The provider-agnostic `Transaction`.
+    #[default]
+    Canonical,
+    /// The provider-specific target struct, e.g. `RevolutTransactionTarget`.
+    Provider,
+}
+
+/// How amounts are serialized.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum MoneyFormat {
+    /// An exact decimal string with at least the minor units of the currency, e.g. `"12.50"`
+    /// or `"0.125"`, never rounded.
+    #[default]
+    String,
+    /// An integer number of minor units, e.g. `1250` for `12.50 EUR`.
+    MinorUnits,
+}
+
+/// How dates are serialized.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DateOutput {
+    /// Unix time seconds, e.g. `1704067200`.
+    #[default]
+    UnixSeconds,
+    /// An RFC 3339 timestamp in the timezone of the provider, e.g. `"2024-01-01T00:00:00+01:00"`.
+    Iso8601,
+    /// A calendar date in the timezone of the provider, e.g. `"2024-01-01"`.
+    DateOnly,
+}
+
+/// How text is extracted from PDF documents.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PdfExtraction {
+    /// The text of every page, a line per text line.
+    #[default]
+    Text,
+    /// The text of every page, along with its size and positioned text runs with their font.
+    Layout,
+    /// The rows of the transaction table reconstructed from the text runs, like the rows of a CSV document.
+    Table,
+}
+
+/// Options for a single parse request.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ParseOptions {
+    /// The shape transactions are returned in, defaults to `canonical`.
+    #[serde(default)]
+    pub output_shape: OutputShape,
+    /// How amounts are serialized, defaults to `string`.
+    #[serde(default)]
+    pub money_format: MoneyFormat,
+    /// How dates are serialized, defaults to `unix_seconds`.
+    #[serde(default)]
+    pub date_format: DateOutput,
+    /// Skips detection and casts the document as this provider, e.g. `revolut_csv`.
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// Fails the whole document when any row can't be cast, instead of reporting it under `errors`.
+    #[serde(default)]
+    pub strict: bool,
+    /// How text is extracted from PDF documents, defaults to `text`.
+    #[serde(default)]
+    pub pdf_extraction: PdfExtraction,
+    /// How images and scanned pages are run through OCR.
+    #[serde(default)]
+    pub ocr: OcrOptions,
+}
@@ -0,0 +1,344 @@
+//! ## Layout-aware PDF extraction
+//!
+//! `output_doc` flattens every page into lines of text, so the description and
+//! amount columns of a statement table merge together. In the `layout`
+//! extraction mode every text object of a page is returned as a [`TextRun`]
+//! with its position, font and size, so tables and fields can be found by
+//! where they are on the page:
+//!
+//! ```json
+//! {
+//!   "page_number": 1, "text": "...", "width": 595.0, "height": 842.0,
+//!   "runs": [
+//!     {
+//!       "page_number": 1, "text": "Bijschrijving",
+//!       "bbox": { "left": 56.7, "top": 210.4, "right": 118.2, "bottom": 219.9 },
+//!       "font_name": "Helvetica", "font_size": 9.0
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Positions are in PDF points, 1/72 inch, from the top left corner of the
+//! page as it is displayed, so after its `/Rotate` and relative to its crop
+//! box, runs are sorted top to bottom and left to right. Text inside form
+//! XObjects isn't returned. Runs are read with pdfium, see [`super`].
+
+use pdfium_render::prelude::{
+    PdfDocument, PdfPage as PdfiumPage, PdfPageObjectCommon, PdfPageObjectsCommon, PdfPageRenderRotation, PdfPageText,
+    PdfQuadPoints, Pdfium, PdfiumError,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+// crate imports
+use crate::error::AuroraError;
+use crate::ocr::BoundingBox;
+use crate::parser::pdf::load_pdfium;
+
+/// A run of text drawn with a single font at a single position.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TextRun {
+    /// The 1-based page number.
+    pub page_number: u32,
+    /// The text of the run.
+    pub text: String,
+    /// Where the run is on the page, in points from the top left corner.
+    pub bbox: BoundingBox,
+    /// The name of the font, e.g. `Helvetica-Bold`, `None` for words read with OCR.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub font_name: Option<String>,
+    /// The font size in points, after scaling by the text matrix.
+    pub font_size: f32,
+}
+
+/// The size and text runs of a single page.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PageLayout {
+    /// The 1-based page number.
+    pub page_number: u32,
+    /// The width of the page in points.
+    pub width: f32,
+    /// The height of the page in points.
+    pub height: f32,
+    /// Every text run of the page, top to bottom and left to right.
+    pub runs: Vec<TextRun>,
+}
+
+/// Extracts the text runs of every page of a PDF document.
+///
+/// Extraction runs on the blocking thread pool.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the PDF document.
+///
+/// # Returns
+///
+/// A `Result` containing a `PageLayout` per page in document order, or an
+/// `AuroraError::Parse` when pdfium isn't available or can't read the document.
+pub async fn extract_layout(content: Vec<u8>) -> Result<Vec<PageLayout>, AuroraError> {
+    let to_error = |message: String| AuroraError::Parse {
+        format: "pdf".to_string(),
+        message,
+    };
+
+    tokio::task::spawn_blocking(move || {
+        let pdfium: Pdfium = load_pdfium().map_err(to_error)?;
+        let document: PdfDocument = pdfium
+            .load_pdf_from_byte_slice(&content, None)
+            .map_err(|e| to_error(format!("Failed to load PDF: {}", e)))?;
+
+        let layouts: Vec<PageLayout> = document
+            .pages()
+            .iter()
+            .enumerate()
+            .map(|(index, page)| page_layout(&page, index as u32 + 1))
+            .collect::<Result<Vec<PageLayout>, PdfiumError>>()
+            .map_err(|e| to_error(format!("Failed to read text objects: {}", e)))?;
+
+        info!(
+            "Extracted {} text runs from {} pages",
+            layouts.iter().map(|layout| layout.runs.len()).sum::<usize>(),
+            layouts.len()
+        );
+        Ok(layouts)
+    })
+    .await
+    .map_err(|e| to_error(format!("Layout extraction task failed: {}", e)))?
+}
+
+/// Reads the text objects of a single page.
+fn page_layout(page: &PdfiumPage, page_number: u32) -> Result<PageLayout, PdfiumError> {
+    let frame: PageFrame = page_frame(page);
+    let text_page: PdfPageText = page.text()?;
+
+    let mut runs: Vec<TextRun> = vec![];
+    for object in page.objects().iter() {
+        let Some(text_object) = object.as_text_object() else {
+            continue;
+        };
+        let text: String = text_page.for_object(text_object);
+        if text.trim().is_empty() {
+            continue;
+        }
+        let bounds: PdfQuadPoints = object.bounds()?;
+
+        runs.push(TextRun {
+            page_number,
+            text: text.trim().to_string(),
+            bbox: frame.displayed_bbox(
+                bounds.left().value,
+                bounds.bottom().value,
+                bounds.right().value,
+                bounds.top().value,
+            ),
+            font_name: Some(text_object.font().name()),
+            font_size: text_object.scaled_font_size().value,
+        });
+    }
+    sort_reading_order(&mut runs);
+
+    let (width, height): (f32, f32) = frame.size();
+    Ok(PageLayout {
+        page_number,
+        width,
+        height,
+        runs,
+    })
+}
+
+/// Reads the visible area and rotation of a page, the crop box or else the media box.
+fn page_frame(page: &PdfiumPage) -> PageFrame {
+    let rotation: u16 = match page.rotation() {
+        Ok(PdfPageRenderRotation::Degrees90) => 90,
+        Ok(PdfPageRenderRotation::Degrees180) => 180,
+        Ok(PdfPageRenderRotation::Degrees270) => 270,
+        _ => 0,
+    };
+    match page.boundaries().crop().or_else(|_| page.boundaries().media()) {
+        Ok(boundary) => PageFrame {
+            left: boundary.bounds.left().value,
+            bottom: boundary.bounds.bottom().value,
+            width: boundary.bounds.width().value,
+            height: boundary.bounds.height().value,
+            rotation,
+        },
+        Err(_) => PageFrame {
+            left: 0.0,
+            bottom: 0.0,
+            width: page.width().value,
+            height: page.height().value,
+            rotation: 0,
+        },
+    }
+}
+
+/// The visible area of a page in PDF user space, and how it is turned when displayed.
+///
+/// Text objects are positioned in user space: from the bottom left corner,
+/// which needn't be `(0, 0)`, and before the `/Rotate` of the page is applied.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PageFrame {
+    /// The left edge of the visible area in user space.
+    pub left: f32,
+    /// The bottom edge of the visible area in user space.
+    pub bottom: f32,
+    /// The width of the visible area before rotating.
+    pub width: f32,
+    /// The height of the visible area before rotating.
+    pub height: f32,
+    /// The clockwise `/Rotate` of the page in degrees: 0, 90, 180 or 270.
+    pub rotation: u16,
+}
+
+impl PageFrame {
+    /// The width and height of the page as it is displayed.
+    pub fn size(&self) -> (f32, f32) {
+        match self.rotation {
+            90 | 270 => (self.height, self.width),
+            _ => (self.width, self.height),
+        }
+    }
+
+    /// Moves a rectangle in user space to the top left corner of the page as it is displayed.
+    ///
+    /// # Arguments
+    ///
+    /// * `left`, `bottom`, `right`, `top` - The edges of the rectangle in user space.
+    ///
+    /// # Returns
+    ///
+    /// The `BoundingBox` of the rectangle in points from the top left corner of the displayed page.
+    pub fn displayed_bbox(&self, left: f32, bottom: f32, right: f32, top: f32) -> BoundingBox {
+        let (x1, y1): (f32, f32) = self.displayed_point(left, bottom);
+        let (x2, y2): (f32, f32) = self.displayed_point(right, top);
+        BoundingBox {
+            left: x1.min(x2),
+            top: y1.min(y2),
+            right: x1.max(x2),
+            bottom: y1.max(y2),
+        }
+    }
+
+    fn displayed_point(&self, x: f32, y: f32) -> (f32, f32) {
+        let (x, y): (f32, f32) = (x - self.left, y - self.bottom);
+        match self.rotation {
+            90 => (y, x),
+            180 => (self.width - x, y),
+            270 => (self.height - y, self.width - x),
+            _ => (x, self.height - y),
+        }
+    }
+}
+
+/// Sorts runs top to bottom, and left to right within a line.
+pub fn sort_reading_order(runs: &mut Vec<TextRun>) {
+    let sorted: Vec<TextRun> = group_lines(runs).into_iter().flatten().cloned().collect();
+    *runs = sorted;
+}
+
+/// Groups runs into lines, top to bottom, with the runs of a line left to right.
+///
+/// Runs whose vertical centers are within half a line height of the first run
+/// of a line are on that line, even when their baselines differ slightly.
+///
+/// # Arguments
+///
+/// * `runs` - The runs of a single page, in any order.
+///
+/// # Returns
+///
+/// The lines of the page, every line holding at least one run.
+pub fn group_lines(runs: &[TextRun]) -> Vec<Vec<&TextRun>> {
+    let mut by_center: Vec<&TextRun> = runs.iter().collect();
+    by_center.sort_by(|a, b| center(&a.bbox).total_cmp(&center(&b.bbox)));
+
+    let mut lines: Vec<Vec<&TextRun>> = vec![];
+    for run in by_center {
+        match lines.last_mut() {
+            Some(line) if is_same_line(&line[0].bbox, &run.bbox) => line.push(run),
+            _ => lines.push(vec![run]),
+        }
+    }
+    for line in lines.iter_mut() {
+        line.sort_by(|a, b| a.bbox.left.total_cmp(&b.bbox.left));
+    }
+    lines
+}
+
+fn center(bbox: &BoundingBox) -> f32 {
+    (bbox.top + bbox.bottom) / 2.0
+}
+
+fn is_same_line(first: &BoundingBox, other: &BoundingBox) -> bool {
+    let half_line: f32 = (first.bottom - first.top).min(other.bottom - other.top).max(1.0) / 2.0;
+    (center(first) - center(other)).abs() <= half_line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(text: &str, left: f32, top: f32, height: f32) -> TextRun {
+        TextRun {
+            page_number: 1,
+            text: text.to_string(),
+            bbox: BoundingBox {
+                left,
+                top,
+                right: left + 40.0,
+                bottom: top + height,
+            },
+            font_name: None,
+            font_size: height,
+        }
+    }
+
+    #[test]
+    fn groups_runs_into_lines_in_reading_order() {
+        let mut runs: Vec<TextRun> = vec![
+            run("-23,45", 400.0, 215.5, 9.0),
+            run("Salaris", 120.0, 240.0, 9.0),
+            run("02-01-2024", 50.0, 215.0, 9.0),
+            // a larger font on the same line, its center is within half a line
+            run("Albert Heijn", 120.0, 213.0, 12.0),
+        ];
+
+        let lines: Vec<Vec<&str>> = group_lines(&runs)
+            .into_iter()
+            .map(|line| line.into_iter().map(|run| run.text.as_str()).collect())
+            .collect();
+        assert_eq!(lines, vec![vec!["02-01-2024", "Albert Heijn", "-23,45"], vec!["Salaris"]]);
+
+        sort_reading_order(&mut runs);
+        let texts: Vec<&str> = runs.iter().map(|run| run.text.as_str()).collect();
+        assert_eq!(texts, vec!["02-01-2024", "Albert Heijn", "-23,45", "Salaris"]);
+    }
+
+    #[test]
+    fn positions_are_relative_to_the_displayed_page() {
+        // an A4 page whose media box starts at (10, 20), a run near its top left corner
+        let frame: PageFrame = PageFrame {
+            left: 10.0,
+            bottom: 20.0,
+            width: 595.0,
+            height: 842.0,
+            rotation: 0,
+        };
+        let bbox = |frame: &PageFrame| frame.displayed_bbox(60.0, 830.0, 100.0, 840.0);
+
+        assert_eq!(frame.size(), (595.0, 842.0));
+        assert_eq!(bbox(&frame), BoundingBox { left: 50.0, top: 22.0, right: 90.0, bottom: 32.0 });
+
+        let turned: PageFrame = PageFrame { rotation: 90, ..frame };
+        assert_eq!(turned.size(), (842.0, 595.0));
+        assert_eq!(bbox(&turned), BoundingBox { left: 810.0, top: 50.0, right: 820.0, bottom: 90.0 });
+
+        let upside_down: PageFrame = PageFrame { rotation: 180, ..frame };
+        assert_eq!(bbox(&upside_down), BoundingBox { left: 505.0, top: 810.0, right: 545.0, bottom: 820.0 });
+
+        let turned_back: PageFrame = PageFrame { rotation: 270, ..frame };
+        assert_eq!(bbox(&turned_back), BoundingBox { left: 22.0, top: 505.0, right: 32.0, bottom: 545.0 });
+    }
+}
@@ -1,22 +1,678 @@
-use pdf_extract::{extract_text, OutputError};
-use tracing::info;
-use std::path::Path;
+//! ## PDF text extraction
+//!
+//! The text layer of every page is read with `pdf_extract`. Scanned bank
+//! statements have no text layer, so pages with no or negligible text are
+//! rendered with pdfium at the resolution of [`OcrOptions::dpi`] and run
+//! through OCR instead. Every page says where its text came from:
+//!
+//! ```json
+//! { "page_number": 2, "text": "...", "source": "ocr", "confidence": 91.0, "text_layer": "1 / 2" }
+//! ```
+//!
+//! The few characters the text layer of such a page did hold, e.g. a page
+//! number stamped on a scan, are kept in `text_layer`.
+//!
+//! Pdfium is loaded at runtime from `AURORA_PDFIUM_PATH`, or the system library
+//! path. Without it scanned pages keep their (empty) text layer, pages have no
+//! text runs in the `layout` and `table` modes, and the document gets a warning.
+//!
+//! With the `layout` [`PdfExtraction`] every page also carries its size and
+//! positioned text runs, see [`layout`], the words of pages read with OCR are
+//! runs too. The `table` extraction mode turns the runs into rows, see [`table`].
 
-/// Parses a PDF document from a file path and extracts the text of all pages.
+pub mod layout;
+pub mod table;
+
+use image::DynamicImage;
+use pdf_extract::{extract_text_from_mem_by_pages, OutputError};
+use pdfium_render::prelude::{PdfDocument, PdfRenderConfig, Pdfium, PdfiumError, PdfiumLibraryBindings};
+use serde::{Deserialize, Serialize};
+use std::{panic, thread};
+use tracing::{info, warn};
+
+// crate imports
+use crate::config::get_pdfium_path;
+use crate::error::AuroraError;
+use crate::ocr::preprocess::PreprocessStep;
+use crate::ocr::{ocr_pages, BoundingBox, OcrMetadata, OcrOptions, OcrPage};
+use crate::parser::options::PdfExtraction;
+use crate::parser::pdf::layout::{extract_layout, sort_reading_order, PageLayout, TextRun};
+
+/// Pages with fewer letters and digits than this are taken to have no text layer.
+pub const MIN_TEXT_LAYER_CHARS: usize = 16;
+
+/// Where the text of a page came from.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TextSource {
+    /// The text layer of the PDF.
+    #[default]
+    TextLayer,
+    /// OCR of the rendered page.
+    Ocr,
+}
+
+/// The text content of a single PDF page.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PdfPage {
+    /// The 1-based page number.
+    pub page_number: u32,
+    /// The extracted text of the page.
+    pub text: String,
+    /// Where the text came from.
+    #[serde(default)]
+    pub source: TextSource,
+    /// The text layer of a page read with OCR, when it held any text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_layer: Option<String>,
+    /// The mean OCR confidence between `0` and `100`, for pages read with OCR.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f32>,
+    /// The width of the page in points, in the `layout` and `table` extraction modes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<f32>,
+    /// The height of the page in points, in the `layout` and `table` extraction modes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<f32>,
+    /// The positioned text runs, in the `layout` and `table` extraction modes.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub runs: Vec<TextRun>,
+}
+
+/// The pages of a PDF document and how their text was read.
+#[derive(Debug, Clone, Default)]
+pub struct PdfText {
+    /// Every page, in document order.
+    pub pages: Vec<PdfPage>,
+    /// How pages without a text layer were run through OCR, `None` when none were.
+    pub ocr: Option<OcrMetadata>,
+    /// Why the layout or pages without a text layer couldn't be read.
+    pub warnings: Vec<String>,
+}
+
+/// Parses a PDF document from memory and extracts the text of every page.
+///
+/// `pdf_extract` is CPU bound and panics on some malformed documents, so it runs
+/// on the blocking thread pool and a panic fails the document instead of the worker.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the PDF document.
+///
+/// # Returns
+///
+/// A `Result` containing a vector of `PdfPage`, one per page in document order,
+/// or an `AuroraError::Parse` when the text can't be extracted.
+pub async fn output_doc(content: Vec<u8>) -> Result<Vec<PdfPage>, AuroraError> {
+    info!("Extracting text from PDF of {} bytes", content.len());
+    let to_error = |message: String| AuroraError::Parse {
+        format: "pdf".to_string(),
+        message,
+    };
+
+    let extracted: thread::Result<Result<Vec<String>, OutputError>> =
+        tokio::task::spawn_blocking(move || {
+            panic::catch_unwind(|| extract_text_from_mem_by_pages(&content))
+        })
+        .await
+        .map_err(|e| to_error(format!("Text extraction task failed: {}", e)))?;
+    let pages_text: Vec<String> = match extracted {
+        Ok(pages_text) => pages_text.map_err(|e| to_error(e.to_string()))?,
+        Err(_) => {
+            warn!("pdf_extract panicked while extracting text");
+            return Err(to_error("The PDF is malformed, its text can't be extracted".to_string()));
+        }
+    };
+
+    let pages: Vec<PdfPage> = pages_text
+        .into_iter()
+        .enumerate()
+        .map(|(index, text)| PdfPage {
+            page_number: index as u32 + 1,
+            text,
+            source: TextSource::TextLayer,
+            text_layer: None,
+            confidence: None,
+            width: None,
+            height: None,
+            runs: vec![],
+        })
+        .collect();
+
+    info!("Extracted text from {} pages", pages.len());
+    Ok(pages)
+}
+
+/// Extracts the text of every page, running pages without a text layer through OCR.
+///
+/// A page is read with OCR when its text layer holds fewer than
+/// `MIN_TEXT_LAYER_CHARS` letters and digits, its text is then the recognised
+/// text and whatever the text layer held moves to `text_layer`. When pdfium or Tesseract isn't available the text layer is
+/// kept and the reason is returned as a warning. In the `layout` and `table`
+/// extraction modes the size and text runs of every page are added, when
+/// pdfium isn't available or can't read the document the pages keep their
+/// text and the reason is returned as a warning too.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the PDF document.
+/// * `extraction` - The `PdfExtraction` mode of the request.
+/// * `options` - The `OcrOptions` of the request, its `dpi` is the resolution pages are rendered at.
+///
+/// # Returns
+///
+/// A `Result` containing the `PdfText`, or an `AuroraError` when the PDF can't
+/// be read or the OCR options are invalid.
+pub async fn read_pdf(
+    content: &[u8],
+    extraction: PdfExtraction,
+    options: &OcrOptions,
+) -> Result<PdfText, AuroraError> {
+    let mut pages: Vec<PdfPage> = output_doc(content.to_vec()).await?;
+
+    let mut warnings: Vec<String> = vec![];
+
+    if extraction != PdfExtraction::Text {
+        let layouts: Result<Vec<PageLayout>, AuroraError> = extract_layout(content.to_vec()).await;
+        if let Some(warning) = apply_layout(&mut pages, layouts) {
+            warnings.push(warning);
+        }
+    }
+
+    let scanned: Vec<u32> = pages
+        .iter()
+        .filter(|page| !has_text_layer(&page.text))
+        .map(|page| page.page_number)
+        .collect();
+    if scanned.is_empty() {
+        return Ok(PdfText {
+            pages,
+            ocr: None,
+            warnings,
+        });
+    }
+    info!("Pages {:?} have no text layer, reading them with OCR", scanned);
+    options.validate()?;
+
+    let recognised: Result<Vec<OcrPage>, AuroraError> =
+        match render_pages(content.to_vec(), scanned.clone(), options.dpi).await {
+            Ok(images) => ocr_pages(images, options).await,
+            Err(e) => Err(e),
+        };
+    let ocr_pages: Vec<OcrPage> = match recognised {
+        Ok(ocr_pages) => ocr_pages,
+        Err(e) => {
+            warn!("Failed to read pages {:?} with OCR: {}", scanned, e);
+            warnings.push(format!(
+                "{} no text layer and couldn't be read with OCR: {}",
+                describe_pages(&scanned),
+                e
+            ));
+            return Ok(PdfText {
+                pages,
+                ocr: None,
+                warnings,
+            });
+        }
+    };
+
+    apply_ocr(&mut pages, &ocr_pages, extraction);
+    Ok(PdfText {
+        pages,
+        ocr: Some(OcrMetadata::new(&ocr_pages, options)),
+        warnings,
+    })
+}
+
+/// Puts the recognised text of pages read with OCR in place of their text layer.
+///
+/// The text layer isn't thrown away: when it held any text it is kept in
+/// `text_layer`, in the `layout` and `table` modes the recognised words
+/// replace its runs.
+fn apply_ocr(pages: &mut [PdfPage], ocr_pages: &[OcrPage], extraction: PdfExtraction) {
+    for ocr_page in ocr_pages {
+        if let Some(page) = pages
+            .iter_mut()
+            .find(|page| page.page_number == ocr_page.page_number)
+        {
+            let text_layer: String = std::mem::replace(&mut page.text, ocr_page.text.clone());
+            page.text_layer = Some(text_layer).filter(|text| !text.trim().is_empty());
+            page.source = TextSource::Ocr;
+            page.confidence = Some(ocr_page.confidence);
+            if extraction != PdfExtraction::Text {
+                page.runs = ocr_runs(ocr_page);
+            }
+        }
+    }
+}
+
+/// Turns the words of a page read with OCR into text runs.
+///
+/// Positions are converted from pixels into points. Cropped borders are added
+/// back, so runs are positioned on the page, but a rotated or deskewed page
+/// keeps the orientation of its preprocessed image.
+fn ocr_runs(page: &OcrPage) -> Vec<TextRun> {
+    let scale: f32 = 72.0 / page.dpi.max(1) as f32;
+    let (offset_left, offset_top): (f32, f32) = page
+        .preprocessing
+        .iter()
+        .find_map(|step| match step {
+            PreprocessStep::CropBorders { left, top, .. } => Some((*left as f32, *top as f32)),
+            _ => None,
+        })
+        .unwrap_or((0.0, 0.0));
+    let mut runs: Vec<TextRun> = page
+        .words
+        .iter()
+        .map(|word| TextRun {
+            page_number: page.page_number,
+            text: word.text.clone(),
+            bbox: BoundingBox {
+                left: (word.bbox.left + offset_left) * scale,
+                top: (word.bbox.top + offset_top) * scale,
+                right: (word.bbox.right + offset_left) * scale,
+                bottom: (word.bbox.bottom + offset_top) * scale,
+            },
+            font_name: None,
+            font_size: (word.bbox.bottom - word.bbox.top) * scale,
+        })
+        .collect();
+    sort_reading_order(&mut runs);
+    runs
+}
+
+/// Adds the size and text runs of every page, the pages keep only their text when the layout couldn't be extracted.
 ///
 /// # Arguments
 ///
-/// * `file_path` - A string slice that holds the path to the PDF file to be parsed.
+/// * `pages` - The pages read from the text layer.
+/// * `layouts` - The result of `extract_layout` for the same document.
 ///
 /// # Returns
 ///
-/// A `Result` containing a vector of strings, each representing the text of a page, or an `OutputError` if an error occurs.
-pub async fn output_doc(file_path: &str) -> Result<Vec<String>, OutputError> {
-    info!("Document opened successfully");
+/// The warning for the document when the layout couldn't be extracted.
+fn apply_layout(pages: &mut [PdfPage], layouts: Result<Vec<PageLayout>, AuroraError>) -> Option<String> {
+    match layouts {
+        Ok(layouts) => {
+            for layout in layouts {
+                if let Some(page) = pages.iter_mut().find(|page| page.page_number == layout.page_number) {
+                    page.width = Some(layout.width);
+                    page.height = Some(layout.height);
+                    page.runs = layout.runs;
+                }
+            }
+            None
+        }
+        Err(e) => {
+            warn!("Failed to extract the layout of the PDF: {}", e);
+            Some(layout_warning(&e))
+        }
+    }
+}
+
+/// Describes why the layout of a document is missing, for a warning.
+fn layout_warning(error: &AuroraError) -> String {
+    let reason: String = match error {
+        AuroraError::Parse { message, .. } => message.clone(),
+        error => error.to_string(),
+    };
+    format!("The layout of the pages couldn't be extracted, only their text is returned: {}", reason)
+}
+
+/// Whether a page has a text layer worth keeping.
+fn has_text_layer(text: &str) -> bool {
+    text.chars().filter(|c| c.is_alphanumeric()).count() >= MIN_TEXT_LAYER_CHARS
+}
+
+/// Describes some pages for a warning, e.g. `Page 2 has` or `Pages 1, 3 have`.
+fn describe_pages(page_numbers: &[u32]) -> String {
+    let numbers: Vec<String> = page_numbers.iter().map(|number| number.to_string()).collect();
+    match numbers.as_slice() {
+        [number] => format!("Page {} has", number),
+        _ => format!("Pages {} have", numbers.join(", ")),
+    }
+}
+
+/// Loads the pdfium library from `AURORA_PDFIUM_PATH`, or the system library path.
+///
+/// The bindings aren't thread safe, so every rendering or extraction task loads its own.
+fn load_pdfium() -> Result<Pdfium, String> {
+    let bindings: Result<Box<dyn PdfiumLibraryBindings>, PdfiumError> = match get_pdfium_path() {
+        Some(path) => Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path(&path)),
+        None => Pdfium::bind_to_system_library(),
+    };
+    bindings.map(Pdfium::new).map_err(|e| {
+        warn!("Failed to load pdfium: {:?}", e);
+        "pdfium is not installed, set AURORA_PDFIUM_PATH to its directory".to_string()
+    })
+}
+
+/// Renders pages of a PDF document into images.
+///
+/// Rendering is CPU bound and runs on the blocking thread pool.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the PDF document.
+/// * `page_numbers` - The 1-based numbers of the pages to render.
+/// * `dpi` - The resolution to render at, a PDF point is 1/72 inch.
+///
+/// # Returns
+///
+/// A `Result` containing the page numbers and their images, or an `AuroraError::Ocr`
+/// when pdfium isn't available or a page can't be rendered.
+pub async fn render_pages(
+    content: Vec<u8>,
+    page_numbers: Vec<u32>,
+    dpi: u32,
+) -> Result<Vec<(u32, DynamicImage)>, AuroraError> {
+    tokio::task::spawn_blocking(move || {
+        let pdfium: Pdfium = load_pdfium().map_err(AuroraError::Ocr)?;
+        let to_error = |e: PdfiumError| AuroraError::Ocr(format!("Failed to render PDF: {}", e));
+        let document: PdfDocument = pdfium.load_pdf_from_byte_slice(&content, None).map_err(to_error)?;
+        let config: PdfRenderConfig = PdfRenderConfig::new().scale_page_by_factor(dpi as f32 / 72.0);
+
+        page_numbers
+            .into_iter()
+            .map(|page_number| {
+                let index: u16 = u16::try_from(page_number.saturating_sub(1))
+                    .map_err(|_| AuroraError::Ocr(format!("Page {} is out of range", page_number)))?;
+                let image: DynamicImage = document
+                    .pages()
+                    .get(index)
+                    .and_then(|page| page.render_with_config(&config).map(|bitmap| bitmap.as_image()))
+                    .map_err(to_error)?;
+                Ok((page_number, image))
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| AuroraError::Ocr(format!("Rendering task failed: {}", e)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ocr::OcrWord;
+    use crate::parser::pdf::table::{extract_table, Table};
+    use lopdf::content::{Content, Operation};
+    use lopdf::{dictionary, Document, Object, Stream};
+
+    /// Writes a PDF with a page per text, in Helvetica.
+    fn sample_pdf(pages: &[&str]) -> Vec<u8> {
+        let mut document: Document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let font_id = document.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+        let resources_id = document.add_object(dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+        });
+
+        let kids: Vec<Object> = pages
+            .iter()
+            .map(|text| {
+                let content: Content = Content {
+                    operations: vec![
+                        Operation::new("BT", vec![]),
+                        Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                        Operation::new("Td", vec![72.into(), 720.into()]),
+                        Operation::new("Tj", vec![Object::string_literal(*text)]),
+                        Operation::new("ET", vec![]),
+                    ],
+                };
+                let content_id = document.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+                document
+                    .add_object(dictionary! {
+                        "Type" => "Page",
+                        "Parent" => pages_id,
+                        "Contents" => content_id,
+                    })
+                    .into()
+            })
+            .collect();
+
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => kids.len() as i64,
+                "Kids" => kids,
+                "Resources" => resources_id,
+                "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+
+        let mut bytes: Vec<u8> = vec![];
+        document.save_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[tokio::test]
+    async fn extracts_the_text_layer_of_every_page() {
+        let pdf: Vec<u8> = sample_pdf(&["Rekeningafschrift januari 2024", "Saldo 1000,00"]);
+        let pages: Vec<PdfPage> = output_doc(pdf).await.unwrap();
+
+        assert_eq!(pages.len(), 2);
+        assert_eq!(pages[0].page_number, 1);
+        assert!(pages[0].text.contains("Rekeningafschrift januari 2024"));
+        assert_eq!(pages[1].page_number, 2);
+        assert!(pages[1].text.contains("Saldo 1000,00"));
+        assert_eq!(pages[1].source, TextSource::TextLayer);
+    }
+
+    #[tokio::test]
+    async fn malformed_documents_fail_with_a_parse_error() {
+        let mut pdf: Vec<u8> = sample_pdf(&["Rekeningafschrift"]);
+        pdf.truncate(pdf.len() / 2);
+
+        for content in [pdf, b"%PDF-1.7\n1 0 obj garbage".to_vec()] {
+            match output_doc(content).await {
+                Err(AuroraError::Parse { format, .. }) => assert_eq!(format, "pdf"),
+                other => panic!("expected a parse error, got {:?}", other.map(|pages| pages.len())),
+            }
+        }
+    }
+
+    #[test]
+    fn pages_with_a_few_characters_have_no_text_layer() {
+        assert!(!has_text_layer(""));
+        assert!(!has_text_layer(" \n 1 / 2 \n"));
+        assert!(has_text_layer("Rekeningafschrift januari"));
+    }
+
+    fn ocr_page(page_number: u32, text: &str) -> OcrPage {
+        OcrPage {
+            page_number,
+            text: text.to_string(),
+            width: 2480,
+            height: 3508,
+            dpi: 300,
+            confidence: 91.0,
+            words: vec![OcrWord {
+                text: text.to_string(),
+                confidence: 91.0,
+                line: 0,
+                bbox: BoundingBox {
+                    left: 300.0,
+                    top: 600.0,
+                    right: 900.0,
+                    bottom: 650.0,
+                },
+            }],
+            preprocessing: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn pages_read_with_ocr_keep_their_text_layer() {
+        let pdf: Vec<u8> = sample_pdf(&["Rekeningafschrift januari 2024", "1 / 2", ""]);
+        let mut pages: Vec<PdfPage> = output_doc(pdf).await.unwrap();
+        apply_ocr(
+            &mut pages,
+            &[ocr_page(2, "Saldo 1000,00"), ocr_page(3, "Totaal 12,50")],
+            PdfExtraction::Layout,
+        );
+
+        assert_eq!(pages[0].source, TextSource::TextLayer);
+        assert_eq!(pages[0].text_layer, None);
+
+        assert_eq!(pages[1].source, TextSource::Ocr);
+        assert_eq!(pages[1].text, "Saldo 1000,00");
+        assert_eq!(pages[1].text_layer.as_deref().map(str::trim), Some("1 / 2"));
+        assert_eq!(pages[1].confidence, Some(91.0));
+        assert_eq!(pages[1].runs.len(), 1);
+        assert_eq!(pages[1].runs[0].bbox.left, 72.0);
+
+        assert_eq!(pages[2].text, "Totaal 12,50");
+        assert_eq!(pages[2].text_layer, None);
+    }
+
+    /// A word of about 9 points at 300 dpi, positioned in pixels of the cropped image.
+    fn ocr_word(text: &str, line: u32, left: f32, top: f32) -> OcrWord {
+        OcrWord {
+            text: text.to_string(),
+            confidence: 90.0,
+            line,
+            bbox: BoundingBox {
+                left,
+                top,
+                right: left + text.chars().count() as f32 * 19.0,
+                bottom: top + 38.0,
+            },
+        }
+    }
+
+    #[test]
+    fn tables_are_rebuilt_from_words_read_with_ocr() {
+        let words: Vec<OcrWord> = vec![
+            ocr_word("Datum", 0, 100.0, 600.0),
+            ocr_word("Omschrijving", 0, 400.0, 600.0),
+            ocr_word("Bedrag", 0, 1550.0, 600.0),
+            ocr_word("Saldo", 0, 1900.0, 600.0),
+            ocr_word("02-01-2024", 1, 100.0, 660.0),
+            ocr_word("Albert", 1, 400.0, 660.0),
+            ocr_word("Heijn", 1, 533.0, 660.0),
+            ocr_word("-23,45", 1, 1550.0, 660.0),
+            ocr_word("976,55", 1, 1881.0, 660.0),
+            ocr_word("03-01-2024", 2, 100.0, 720.0),
+            ocr_word("Salaris", 2, 400.0, 720.0),
+            ocr_word("2.500,00", 2, 1512.0, 720.0),
+            ocr_word("3.476,55", 2, 1843.0, 720.0),
+        ];
+        let ocr: OcrPage = OcrPage {
+            words,
+            preprocessing: vec![PreprocessStep::CropBorders {
+                left: 100,
+                top: 200,
+                width: 2280,
+                height: 3108,
+            }],
+            ..ocr_page(1, "")
+        };
+        let page: PdfPage = PdfPage {
+            page_number: 1,
+            text: String::new(),
+            source: TextSource::Ocr,
+            text_layer: None,
+            confidence: Some(90.0),
+            width: Some(595.0),
+            height: Some(842.0),
+            runs: ocr_runs(&ocr),
+        };
+
+        // the cropped border is added back before converting pixels into points
+        assert_eq!(page.runs[0].text, "Datum");
+        assert_eq!(page.runs[0].bbox.left, 48.0);
+        assert_eq!(page.runs[0].bbox.top, 192.0);
+
+        let table: Table = extract_table(&[page]).unwrap();
+        assert_eq!(
+            table.rows,
+            vec![
+                serde_json::json!({
+                    "datum": "02-01-2024",
+                    "omschrijving": "Albert Heijn",
+                    "bedrag": "-23,45",
+                    "saldo": "976,55"
+                }),
+                serde_json::json!({
+                    "datum": "03-01-2024",
+                    "omschrijving": "Salaris",
+                    "bedrag": "2.500,00",
+                    "saldo": "3.476,55"
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn pages_keep_their_text_when_the_layout_cant_be_extracted() {
+        let mut pages: Vec<PdfPage> = vec![PdfPage {
+            page_number: 1,
+            text: "Rekeningafschrift januari 2024".to_string(),
+            source: TextSource::TextLayer,
+            text_layer: None,
+            confidence: None,
+            width: None,
+            height: None,
+            runs: vec![],
+        }];
+        let error: AuroraError = AuroraError::Parse {
+            format: "pdf".to_string(),
+            message: "pdfium is not installed".to_string(),
+        };
+        let warning: Option<String> = apply_layout(&mut pages, Err(error));
+        assert_eq!(pages[0].text, "Rekeningafschrift januari 2024");
+        assert_eq!(pages[0].width, None);
+        assert!(pages[0].runs.is_empty());
+        assert!(warning.unwrap().starts_with("The layout of the pages couldn't be extracted"));
+
+        let layout: PageLayout = PageLayout {
+            page_number: 1,
+            width: 595.0,
+            height: 842.0,
+            runs: vec![TextRun {
+                page_number: 1,
+                text: "Rekeningafschrift januari 2024".to_string(),
+                bbox: BoundingBox {
+                    left: 56.0,
+                    top: 40.0,
+                    right: 200.0,
+                    bottom: 52.0,
+                },
+                font_name: Some("Helvetica".to_string()),
+                font_size: 12.0,
+            }],
+        };
+        assert_eq!(apply_layout(&mut pages, Ok(vec![layout])), None);
+        assert_eq!(pages[0].text, "Rekeningafschrift januari 2024");
+        assert_eq!(pages[0].width, Some(595.0));
+        assert_eq!(pages[0].runs.len(), 1);
+    }
 
-    // Extract text from the document
-    let pages_text = extract_text(file_path).map_err(OutputError::from)?;
+    #[test]
+    fn layout_warnings_carry_the_reason() {
+        let error: AuroraError = AuroraError::Parse {
+            format: "pdf".to_string(),
+            message: "pdfium is not installed".to_string(),
+        };
+        assert_eq!(
+            layout_warning(&error),
+            "The layout of the pages couldn't be extracted, only their text is returned: pdfium is not installed"
+        );
+    }
 
-    info!("Extracted text from all pages");
-    Ok(pages_text.lines().map(String::from).collect())
+    #[test]
+    fn describes_pages_for_warnings() {
+        assert_eq!(describe_pages(&[2]), "Page 2 has");
+        assert_eq!(describe_pages(&[1, 3]), "Pages 1, 3 have");
+    }
 }
@@ -0,0 +1,654 @@
+//! ## Table reconstruction
+//!
+//! PDF bank statements lay their transactions out as a table, which only
+//! exists as positioned text. In the `table` extraction mode the text runs of
+//! every page are turned back into rows, the same objects
+//! `convert_csv_reader_to_json` produces, so a PDF statement goes through
+//! provider detection and casting like a CSV export:
+//!
+//! 1. The header row is the line with the most cells that are aliases of a
+//!    transaction field, e.g. `Datum`, `Omschrijving` and `Bedrag`.
+//! 2. Column boundaries are placed in the gutters between the header cells,
+//!    where the text below the header leaves the most room.
+//! 3. A line starts a transaction when it has a value in the date column, or
+//!    the first column without one. Lines holding only text right below a
+//!    transaction are its description wrapping onto the next line.
+//! 4. Page headers and footers, lines repeated on several pages near the top
+//!    or bottom, and header rows repeated on every page are skipped.
+//!
+//! ```json
+//! [
+//!   { "datum": "02-01-2024", "omschrijving": "Albert Heijn 1234 Amsterdam", "bedrag": "-23,45" }
+//! ]
+//! ```
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+
+// crate imports
+use crate::parser::csv::headers::{builtin_aliases, normalize_header, HeaderAliases};
+use crate::parser::pdf::layout::{group_lines, TextRun};
+use crate::parser::pdf::PdfPage;
+use crate::parser::schema::detection::ValuePattern;
+
+/// The fraction of a page at its top and bottom that page headers and footers are looked for in.
+const MARGIN_ZONE: f32 = 0.15;
+/// The canonical fields a transaction is anchored on, the first one found is used.
+const DATE_FIELDS: [&str; 2] = ["booking_date", "value_date"];
+/// The canonical fields holding amounts.
+const AMOUNT_FIELDS: [&str; 2] = ["amount", "balance_after"];
+
+/// A column of a reconstructed table.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TableColumn {
+    /// The normalized header, the key of the column in every row.
+    pub name: String,
+    /// The header as printed.
+    pub header: String,
+    /// The left edge of the header and the values of the column, in points.
+    pub left: f32,
+    /// The right edge of the header and the values of the column, in points.
+    pub right: f32,
+}
+
+/// How a table was reconstructed from a PDF document.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TableMetadata {
+    /// The columns, left to right.
+    pub columns: Vec<TableColumn>,
+    /// The page the header row was found on.
+    pub header_page: u32,
+    /// The pages holding rows of the table.
+    pub pages: Vec<u32>,
+    /// The lines below the header that were neither a row nor part of one, e.g. totals.
+    pub skipped_lines: usize,
+}
+
+/// A table reconstructed from the text runs of a PDF document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    /// The rows, objects keyed by the normalized column headers.
+    pub rows: Vec<Value>,
+    /// How the table was reconstructed.
+    pub metadata: TableMetadata,
+}
+
+/// A line of text runs on a page.
+struct Line<'a> {
+    page_number: u32,
+    top: f32,
+    bottom: f32,
+    runs: Vec<&'a TextRun>,
+}
+
+impl<'a> Line<'a> {
+    fn new(page_number: u32, runs: Vec<&'a TextRun>) -> Line<'a> {
+        let top: f32 = runs.iter().map(|run| run.bbox.top).fold(f32::MAX, f32::min);
+        let bottom: f32 = runs.iter().map(|run| run.bbox.bottom).fold(f32::MIN, f32::max);
+        Line {
+            page_number,
+            top,
+            bottom,
+            runs,
+        }
+    }
+
+    fn height(&self) -> f32 {
+        (self.bottom - self.top).max(1.0)
+    }
+
+    /// The text of the line with its digits masked, equal for every page of a
+    /// repeated header or footer such as `Pagina 1 van 3`.
+    fn pattern(&self) -> String {
+        self.runs
+            .iter()
+            .map(|run| run.text.as_str())
+            .collect::<Vec<&str>>()
+            .join(" ")
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_digit() { '#' } else { c })
+            .collect::<String>()
+            .split_whitespace()
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    /// Merges runs separated by less than a word space into cells.
+    fn cells(&self) -> Vec<Cell> {
+        let mut cells: Vec<Cell> = vec![];
+        for run in &self.runs {
+            let size: f32 = if run.font_size > 0.0 {
+                run.font_size
+            } else {
+                self.height()
+            };
+            match cells.last_mut() {
+                Some(cell) if run.bbox.left - cell.right < size * 0.8 => {
+                    cell.text.push(' ');
+                    cell.text.push_str(&run.text);
+                    cell.right = cell.right.max(run.bbox.right);
+                }
+                _ => cells.push(Cell {
+                    text: run.text.clone(),
+                    left: run.bbox.left,
+                    right: run.bbox.right,
+                }),
+            }
+        }
+        cells
+    }
+}
+
+/// Runs of a line close enough to be a single value.
+struct Cell {
+    text: String,
+    left: f32,
+    right: f32,
+}
+
+impl Cell {
+    fn center(&self) -> f32 {
+        (self.left + self.right) / 2.0
+    }
+}
+
+/// A transaction being assembled from its first line and the lines wrapping under it.
+struct PendingRow {
+    values: Vec<String>,
+    page_number: u32,
+    bottom: f32,
+}
+
+/// Reconstructs the transaction table of a PDF document from its text runs.
+///
+/// # Arguments
+///
+/// * `pages` - The pages of the document, with the text runs of the `layout` extraction mode.
+///
+/// # Returns
+///
+/// The `Table`, or `None` when no header row is found or no transaction follows it.
+pub fn extract_table(pages: &[PdfPage]) -> Option<Table> {
+    let aliases: &HeaderAliases = builtin_aliases();
+    let lines: Vec<Line> = pages
+        .iter()
+        .flat_map(|page| {
+            group_lines(&page.runs)
+                .into_iter()
+                .map(move |runs| Line::new(page.page_number, runs))
+        })
+        .collect();
+
+    let (header_index, header): (usize, Vec<Cell>) = find_header(&lines, aliases)?;
+    let header_page: u32 = lines[header_index].page_number;
+    let names: Vec<String> = column_names(&header);
+    let body: Vec<&Line> = body_lines(pages, &lines, header_index, &names);
+
+    let body_runs: Vec<&TextRun> = body.iter().flat_map(|line| line.runs.iter().copied()).collect();
+    let boundaries: Vec<f32> = column_boundaries(&header, &body_runs);
+    let mut columns: Vec<TableColumn> = header
+        .iter()
+        .zip(&names)
+        .map(|(cell, name)| TableColumn {
+            name: name.clone(),
+            header: cell.text.clone(),
+            left: cell.left,
+            right: cell.right,
+        })
+        .collect();
+
+    let anchor: usize = names
+        .iter()
+        .position(|name| aliases.field(name).is_some_and(|field| DATE_FIELDS.contains(&field)))
+        .unwrap_or(0);
+    let dated: bool = aliases
+        .field(&names[anchor])
+        .is_some_and(|field| DATE_FIELDS.contains(&field));
+
+    let split: Vec<(&Line, Vec<String>)> = body
+        .iter()
+        .map(|line| (*line, split_line(line, &boundaries, &mut columns)))
+        .collect();
+    let amounts: HashSet<usize> = amount_columns(&names, &split, anchor, aliases);
+
+    let mut rows: Vec<Value> = vec![];
+    let mut row_pages: Vec<u32> = vec![];
+    let mut skipped_lines: usize = 0;
+    let mut pending: Option<PendingRow> = None;
+    for (line, values) in split {
+        if is_anchor(&values[anchor], dated) {
+            if let Some(row) = pending.take() {
+                rows.push(to_row(&names, row.values));
+            }
+            if !row_pages.contains(&line.page_number) {
+                row_pages.push(line.page_number);
+            }
+            pending = Some(PendingRow {
+                values,
+                page_number: line.page_number,
+                bottom: line.bottom,
+            });
+            continue;
+        }
+
+        let only_text: bool = amounts.iter().all(|&column| values[column].is_empty());
+        match pending.as_mut() {
+            Some(row)
+                if only_text
+                    && row.page_number == line.page_number
+                    && line.top - row.bottom <= line.height() * 1.5 =>
+            {
+                for (value, wrapped) in row.values.iter_mut().zip(values) {
+                    if !wrapped.is_empty() {
+                        if !value.is_empty() {
+                            value.push(' ');
+                        }
+                        value.push_str(&wrapped);
+                    }
+                }
+                row.bottom = line.bottom;
+            }
+            _ => {
+                skipped_lines += 1;
+                if let Some(row) = pending.take() {
+                    rows.push(to_row(&names, row.values));
+                }
+            }
+        }
+    }
+    if let Some(row) = pending.take() {
+        rows.push(to_row(&names, row.values));
+    }
+
+    if rows.is_empty() {
+        return None;
+    }
+    Some(Table {
+        rows,
+        metadata: TableMetadata {
+            columns,
+            header_page,
+            pages: row_pages,
+            skipped_lines,
+        },
+    })
+}
+
+/// Finds the header row, the line with the most cells that are aliases of a transaction field.
+///
+/// # Returns
+///
+/// The index of the line and its cells, or `None` when no line has at least
+/// three cells of which two are aliases of different fields.
+fn find_header(lines: &[Line], aliases: &HeaderAliases) -> Option<(usize, Vec<Cell>)> {
+    let mut best: Option<(usize, Vec<Cell>, usize)> = None;
+    for (index, line) in lines.iter().enumerate() {
+        let cells: Vec<Cell> = line.cells();
+        if cells.len() < 3 {
+            continue;
+        }
+        let fields: HashSet<&str> = cells.iter().filter_map(|cell| aliases.field(&cell.text)).collect();
+        if fields.len() < 2 {
+            continue;
+        }
+        if best.as_ref().is_none_or(|(_, _, known)| fields.len() > *known) {
+            best = Some((index, cells, fields.len()));
+        }
+    }
+    best.map(|(index, cells, _)| (index, cells))
+}
+
+/// Normalizes the header cells into unique column names.
+fn column_names(header: &[Cell]) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+    for (index, cell) in header.iter().enumerate() {
+        let normalized: String = normalize_header(&cell.text);
+        let base: String = if normalized.is_empty() {
+            format!("column_{}", index + 1)
+        } else {
+            normalized
+        };
+        let mut name: String = base.clone();
+        let mut suffix: usize = 2;
+        while names.contains(&name) {
+            name = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        names.push(name);
+    }
+    names
+}
+
+/// Collects the lines below the header that can hold transactions.
+///
+/// Lines above a repeated header row are the page header of their page, and
+/// lines repeated on several pages within their top or bottom margin are page
+/// headers and footers, both are left out along with the repeated header rows.
+fn body_lines<'a>(pages: &[PdfPage], lines: &'a [Line<'a>], header_index: usize, names: &[String]) -> Vec<&'a Line<'a>> {
+    let heights: HashMap<u32, f32> = pages
+        .iter()
+        .map(|page| {
+            let height: f32 = page.height.unwrap_or_else(|| {
+                page.runs.iter().map(|run| run.bbox.bottom).fold(0.0, f32::max)
+            });
+            (page.page_number, height)
+        })
+        .collect();
+    let in_margin = |line: &Line| -> bool {
+        let height: f32 = heights.get(&line.page_number).copied().unwrap_or(0.0);
+        height > 0.0 && (line.top < height * MARGIN_ZONE || line.bottom > height * (1.0 - MARGIN_ZONE))
+    };
+
+    let mut pattern_pages: HashMap<String, HashSet<u32>> = HashMap::new();
+    for line in lines.iter().filter(|line| in_margin(line)) {
+        pattern_pages.entry(line.pattern()).or_default().insert(line.page_number);
+    }
+    let is_furniture = |line: &Line| -> bool {
+        in_margin(line) && pattern_pages.get(&line.pattern()).is_some_and(|pages| pages.len() >= 2)
+    };
+    let is_header = |line: &Line| -> bool {
+        let matching: usize = line
+            .cells()
+            .iter()
+            .filter(|cell| names.contains(&normalize_header(&cell.text)))
+            .count();
+        matching >= 2 && matching * 2 > names.len()
+    };
+
+    let mut body: Vec<&Line> = vec![];
+    let mut page_number: u32 = lines[header_index].page_number;
+    let mut page_start: usize = 0;
+    for line in &lines[header_index + 1..] {
+        if line.page_number != page_number {
+            page_number = line.page_number;
+            page_start = body.len();
+        }
+        if is_header(line) {
+            body.truncate(page_start);
+        } else if !is_furniture(line) {
+            body.push(line);
+        }
+    }
+    body
+}
+
+/// Places the boundaries between columns in the gutters between the header cells.
+///
+/// Between the centers of two neighbouring header cells the boundary goes in
+/// the middle of the widest stretch covered by the fewest runs below the
+/// header, so right aligned amounts wider than their header stay in their column.
+///
+/// # Returns
+///
+/// The x position of every boundary, left to right, one less than there are columns.
+fn column_boundaries(header: &[Cell], body: &[&TextRun]) -> Vec<f32> {
+    let width: usize = body
+        .iter()
+        .map(|run| run.bbox.right)
+        .chain(header.iter().map(|cell| cell.right))
+        .fold(0.0, f32::max)
+        .ceil() as usize
+        + 1;
+    let mut coverage: Vec<u32> = vec![0; width];
+    for run in body {
+        let from: usize = run.bbox.left.max(0.0) as usize;
+        let to: usize = (run.bbox.right.max(0.0).ceil() as usize).min(width);
+        for covered in coverage.iter_mut().take(to).skip(from) {
+            *covered += 1;
+        }
+    }
+
+    header
+        .windows(2)
+        .map(|pair| {
+            let from: usize = pair[0].center().max(0.0) as usize;
+            let to: usize = (pair[1].center().max(0.0) as usize).min(width);
+            if to <= from + 1 {
+                return (pair[0].right + pair[1].left) / 2.0;
+            }
+            let fewest: u32 = coverage[from..to].iter().copied().min().unwrap_or(0);
+            from as f32 + middle_of_widest(&coverage[from..to], fewest)
+        })
+        .collect()
+}
+
+/// Returns the middle of the widest stretch of a value.
+fn middle_of_widest(values: &[u32], target: u32) -> f32 {
+    let (mut best_start, mut best_length): (usize, usize) = (0, 0);
+    let mut start: Option<usize> = None;
+    for (index, value) in values.iter().chain([&u32::MAX]).enumerate() {
+        if *value == target {
+            start.get_or_insert(index);
+        } else if let Some(stretch) = start.take() {
+            if index - stretch > best_length {
+                best_start = stretch;
+                best_length = index - stretch;
+            }
+        }
+    }
+    best_start as f32 + best_length as f32 / 2.0
+}
+
+/// Splits a line into the values of the columns, by the left edge of its runs.
+///
+/// The edges of the columns are widened to the runs they receive.
+fn split_line(line: &Line, boundaries: &[f32], columns: &mut [TableColumn]) -> Vec<String> {
+    let mut values: Vec<String> = vec![String::new(); columns.len()];
+    for run in &line.runs {
+        let column: usize = boundaries.iter().take_while(|boundary| run.bbox.left >= **boundary).count();
+        if !values[column].is_empty() {
+            values[column].push(' ');
+        }
+        values[column].push_str(&run.text);
+        columns[column].left = columns[column].left.min(run.bbox.left);
+        columns[column].right = columns[column].right.max(run.bbox.right);
+    }
+    values
+}
+
+/// Finds the columns holding amounts, by their header or by most of their values being amounts.
+fn amount_columns(
+    names: &[String],
+    lines: &[(&Line, Vec<String>)],
+    anchor: usize,
+    aliases: &HeaderAliases,
+) -> HashSet<usize> {
+    (0..names.len())
+        .filter(|&column| column != anchor)
+        .filter(|&column| {
+            if aliases.field(&names[column]).is_some_and(|field| AMOUNT_FIELDS.contains(&field)) {
+                return true;
+            }
+            let values: Vec<&str> = lines
+                .iter()
+                .map(|(_, values)| values[column].as_str())
+                .filter(|value| !value.is_empty())
+                .collect();
+            let amounts: usize = values.iter().filter(|value| ValuePattern::Amount.matches(value)).count();
+            amounts >= 2 && amounts * 10 >= values.len() * 6
+        })
+        .collect()
+}
+
+/// Whether a value starts a transaction.
+///
+/// A date starts with a digit and is short, e.g. `02-01-2024` or `2 jan`,
+/// which rules out lines such as `Pagina 1 van 3` or `Saldo per 31-01-2024`.
+fn is_anchor(value: &str, dated: bool) -> bool {
+    if !dated {
+        return !value.is_empty();
+    }
+    value.starts_with(|c: char| c.is_ascii_digit()) && value.chars().count() <= 16
+}
+
+fn to_row(names: &[String], values: Vec<String>) -> Value {
+    let row: Map<String, Value> = names
+        .iter()
+        .cloned()
+        .zip(values.into_iter().map(Value::String))
+        .collect();
+    Value::Object(row)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ocr::BoundingBox;
+    use crate::parser::pdf::TextSource;
+
+    /// A run of 9 point Helvetica, about 4.5 points per character.
+    fn run(page_number: u32, text: &str, left: f32, top: f32) -> TextRun {
+        TextRun {
+            page_number,
+            text: text.to_string(),
+            bbox: BoundingBox {
+                left,
+                top,
+                right: left + text.chars().count() as f32 * 4.5,
+                bottom: top + 9.0,
+            },
+            font_name: Some("Helvetica".to_string()),
+            font_size: 9.0,
+        }
+    }
+
+    fn page(page_number: u32, runs: Vec<TextRun>) -> PdfPage {
+        PdfPage {
+            page_number,
+            text: String::new(),
+            source: TextSource::TextLayer,
+            text_layer: None,
+            confidence: None,
+            width: Some(595.0),
+            height: Some(842.0),
+            runs,
+        }
+    }
+
+    fn header(page_number: u32, top: f32) -> Vec<TextRun> {
+        vec![
+            run(page_number, "Datum", 50.0, top),
+            run(page_number, "Omschrijving", 120.0, top),
+            run(page_number, "Bedrag", 400.0, top),
+            run(page_number, "Saldo", 480.0, top),
+        ]
+    }
+
+    /// A two page ING-like statement, the amounts are right aligned under their header.
+    fn statement() -> Vec<PdfPage> {
+        let mut first: Vec<TextRun> = vec![
+            run(1, "Rekeningafschrift", 50.0, 40.0),
+            run(1, "NL91ABNA0417164300", 300.0, 60.0),
+        ];
+        first.extend(header(1, 200.0));
+        first.extend([
+            run(1, "02-01-2024", 50.0, 215.0),
+            run(1, "Albert Heijn 1234", 120.0, 215.0),
+            run(1, "-23,45", 400.0, 215.0),
+            run(1, "976,55", 480.0, 215.0),
+            run(1, "Amsterdam", 120.0, 227.0),
+            run(1, "03-01-2024", 50.0, 240.0),
+            run(1, "Salaris januari", 120.0, 240.0),
+            run(1, "2.500,00", 391.0, 240.0),
+            run(1, "3.476,55", 471.0, 240.0),
+            run(1, "Pagina 1 van 2", 260.0, 800.0),
+        ]);
+
+        let mut second: Vec<TextRun> = vec![run(2, "Rekeningafschrift", 50.0, 40.0)];
+        second.extend(header(2, 100.0));
+        second.extend([
+            run(2, "04-01-2024", 50.0, 115.0),
+            run(2, "Huur", 120.0, 115.0),
+            run(2, "-1.200,00", 386.5, 115.0),
+            run(2, "2.276,55", 471.0, 115.0),
+            run(2, "Totaal", 120.0, 140.0),
+            run(2, "1.276,55", 391.0, 140.0),
+            run(2, "Pagina 2 van 2", 260.0, 800.0),
+        ]);
+
+        vec![page(1, first), page(2, second)]
+    }
+
+    #[test]
+    fn rebuilds_the_rows_of_a_statement() {
+        let table: Table = extract_table(&statement()).unwrap();
+
+        assert_eq!(
+            table.rows,
+            vec![
+                serde_json::json!({
+                    "datum": "02-01-2024",
+                    "omschrijving": "Albert Heijn 1234 Amsterdam",
+                    "bedrag": "-23,45",
+                    "saldo": "976,55"
+                }),
+                serde_json::json!({
+                    "datum": "03-01-2024",
+                    "omschrijving": "Salaris januari",
+                    "bedrag": "2.500,00",
+                    "saldo": "3.476,55"
+                }),
+                serde_json::json!({
+                    "datum": "04-01-2024",
+                    "omschrijving": "Huur",
+                    "bedrag": "-1.200,00",
+                    "saldo": "2.276,55"
+                }),
+            ]
+        );
+        assert_eq!(table.metadata.header_page, 1);
+        assert_eq!(table.metadata.pages, vec![1, 2]);
+        // the total below the last transaction
+        assert_eq!(table.metadata.skipped_lines, 1);
+
+        let names: Vec<&str> = table.metadata.columns.iter().map(|column| column.name.as_str()).collect();
+        assert_eq!(names, vec!["datum", "omschrijving", "bedrag", "saldo"]);
+        assert_eq!(table.metadata.columns[2].header, "Bedrag");
+        assert_eq!(table.metadata.columns[2].left, 386.5);
+    }
+
+    #[test]
+    fn pages_without_a_table_have_no_rows() {
+        let pages: Vec<PdfPage> = vec![page(
+            1,
+            vec![
+                run(1, "Geachte heer Jansen,", 50.0, 100.0),
+                run(1, "Uw nieuwe pas is onderweg.", 50.0, 115.0),
+            ],
+        )];
+        assert_eq!(extract_table(&pages), None);
+
+        // a header without any transaction below it
+        assert_eq!(extract_table(&[page(1, header(1, 200.0))]), None);
+    }
+
+    #[test]
+    fn duplicate_and_empty_headers_get_unique_names() {
+        let cells: Vec<Cell> = ["Bedrag", "Bedrag", "***"]
+            .iter()
+            .map(|text| Cell {
+                text: text.to_string(),
+                left: 0.0,
+                right: 0.0,
+            })
+            .collect();
+        assert_eq!(column_names(&cells), vec!["bedrag", "bedrag_2", "***"]);
+    }
+
+    #[test]
+    fn dates_anchor_transactions() {
+        assert!(is_anchor("02-01-2024", true));
+        assert!(is_anchor("2 jan", true));
+        assert!(!is_anchor("Saldo per 31-01-2024", true));
+        assert!(!is_anchor("", true));
+        assert!(is_anchor("Huur", false));
+    }
+
+    #[test]
+    fn finds_the_middle_of_the_widest_gutter() {
+        assert_eq!(middle_of_widest(&[2, 0, 0, 1, 0, 0, 0, 0, 2], 0), 6.0);
+        assert_eq!(middle_of_widest(&[1, 1, 1], 1), 1.5);
+    }
+}
@@ -0,0 +1,370 @@
+//! ## Provider detection
+//!
+//! Detection scores every known provider against a parsed document instead
+//! of stopping at the first key set that fits. The building blocks below let a
+//! `ProviderCaster` combine header overlap, value patterns and filename or
+//! text markers into a single confidence between `0.0` and `1.0`.
+
+use iban::Iban;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+// crate imports
+use crate::parser::caster::number::locale::parse_amount;
+use crate::parser::caster::time::{parse_naive_datetime, KNOWN_DATE_FORMATS};
+use crate::parser::csv::headers::{builtin_aliases, HeaderAliases};
+
+/// The lowest confidence at which the best candidate is accepted.
+pub const MIN_CONFIDENCE: f64 = 0.5;
+
+/// Everything detection gets to look at.
+#[derive(Debug, Clone)]
+pub struct DetectionContext<'a> {
+    /// The parsed rows of the document.
+    pub rows: &'a [Value],
+    /// The file name or URL of the document, if known.
+    pub file_name: Option<&'a str>,
+    /// The free text of the document, the `text` of every PDF page joined together.
+    pub text: String,
+}
+
+impl<'a> DetectionContext<'a> {
+    /// Creates a context, collecting the free text from the `text` field of the rows.
+    ///
+    /// # Arguments
+    ///
+    /// * `rows` - The parsed rows of the document.
+    /// * `file_name` - The file name or URL of the document, if known.
+    pub fn new(rows: &'a [Value], file_name: Option<&'a str>) -> DetectionContext<'a> {
+        let text: String = rows
+            .iter()
+            .filter_map(|row| row.get("text").and_then(|text| text.as_str()))
+            .collect::<Vec<&str>>()
+            .join("\n");
+
+        DetectionContext {
+            rows,
+            file_name,
+            text,
+        }
+    }
+
+    /// Returns the keys of the first row.
+    pub fn header(&self) -> HashSet<&str> {
+        self.rows
+            .iter()
+            .find_map(|row| row.as_object())
+            .map(|obj_map| obj_map.keys().map(String::as_str).collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns `1.0` when the file name holds any of the markers, `0.0` otherwise.
+    ///
+    /// Only whole words of the file stem count, so `ing` matches
+    /// `ing-2024.csv` but not `billing.csv` or a host such as `ing.example.com`.
+    ///
+    /// # Arguments
+    ///
+    /// * `markers` - Lowercase words such as `revolut` or `abn amro`.
+    pub fn file_name_score(&self, markers: &[&str]) -> f32 {
+        let Some(file_name) = self.file_name else {
+            return 0.0;
+        };
+        let stem: Vec<String> = words(file_stem(file_name));
+        let found: bool = markers.iter().any(|marker| {
+            let marker: Vec<String> = words(marker);
+            !marker.is_empty() && stem.windows(marker.len()).any(|window| window == marker.as_slice())
+        });
+        if found {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns the fraction of the markers found in the free text of the document.
+    ///
+    /// # Arguments
+    ///
+    /// * `markers` - Fragments such as `Financieel jaaroverzicht`, matched case-insensitively.
+    pub fn text_score(&self, markers: &[&str]) -> f32 {
+        if markers.is_empty() || self.text.is_empty() {
+            return 0.0;
+        }
+        let text: String = self.text.to_lowercase();
+        let found: usize = markers
+            .iter()
+            .filter(|marker| text.contains(&marker.to_lowercase()))
+            .count();
+        found as f32 / markers.len() as f32
+    }
+}
+
+/// A value pattern checked by `value_match_ratio`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValuePattern {
+    /// An IBAN with a valid checksum, spaces allowed.
+    Iban,
+    /// A date in one of the `KNOWN_DATE_FORMATS`.
+    Date,
+    /// A locale-formatted amount.
+    Amount,
+}
+
+impl ValuePattern {
+    /// Whether a single value matches the pattern.
+    pub fn matches(&self, value: &str) -> bool {
+        match self {
+            ValuePattern::Iban => is_valid_iban(value),
+            ValuePattern::Date => parse_naive_datetime(value, &KNOWN_DATE_FORMATS).is_some(),
+            ValuePattern::Amount => parse_amount(value, None).is_some(),
+        }
+    }
+}
+
+/// A detected provider and how confident detection is about it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ProviderCandidate {
+    /// The name of the provider, e.g. `revolut_csv`.
+    pub document_provider: String,
+    /// The confidence between `0.0` and `1.0`, rounded to two decimals.
+    pub confidence: f64,
+}
+
+/// The outcome of provider detection.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Detection {
+    /// The provider stamped on the rows, `unknown` when no candidate is confident enough.
+    pub document_provider: String,
+    /// Whether the provider was forced by the request instead of detected.
+    pub forced: bool,
+    /// Every provider scoring above zero, most likely first.
+    pub candidates: Vec<ProviderCandidate>,
+}
+
+/// Scores how well the header of a document matches the keys a provider writes.
+///
+/// The score is the Jaccard index of both key sets, so an extra or missing
+/// column lowers the score instead of ruling the provider out. Keys are
+/// compared by their canonical field, so `Boekdatum` matches `Datum`.
+///
+/// # Arguments
+///
+/// * `context` - The `DetectionContext` of the document.
+/// * `expected` - The keys the provider writes.
+///
+/// # Returns
+///
+/// A score between `0.0` (no key in common) and `1.0` (identical key sets).
+pub fn header_overlap(context: &DetectionContext, expected: &HashSet<&str>) -> f32 {
+    header_overlap_with(context, expected, builtin_aliases())
+}
+
+/// Scores header overlap like `header_overlap`, with the aliases of a single provider.
+///
+/// # Arguments
+///
+/// * `context` - The `DetectionContext` of the document.
+/// * `expected` - The keys the provider writes.
+/// * `aliases` - The `HeaderAliases` of the provider.
+///
+/// # Returns
+///
+/// A score between `0.0` (no key in common) and `1.0` (identical key sets).
+pub fn header_overlap_with(
+    context: &DetectionContext,
+    expected: &HashSet<&str>,
+    aliases: &HeaderAliases,
+) -> f32 {
+    let canonical = |keys: &HashSet<&str>| -> HashSet<String> {
+        keys.iter().map(|key| aliases.canonical(key)).collect()
+    };
+    let header: HashSet<String> = canonical(&context.header());
+    let expected: HashSet<String> = canonical(expected);
+    let union: usize = header.union(&expected).count();
+    if union == 0 {
+        return 0.0;
+    }
+    header.intersection(&expected).count() as f32 / union as f32
+}
+
+/// Returns the fraction of non-empty values of a column matching a pattern.
+///
+/// # Arguments
+///
+/// * `context` - The `DetectionContext` of the document.
+/// * `column` - The column to check.
+/// * `pattern` - The `ValuePattern` the values should match.
+///
+/// # Returns
+///
+/// A ratio between `0.0` and `1.0`, `0.0` when the column is missing or empty.
+pub fn value_match_ratio(context: &DetectionContext, column: &str, pattern: ValuePattern) -> f32 {
+    let values: Vec<&str> = context
+        .rows
+        .iter()
+        .filter_map(|row| row.get(column).and_then(|value| value.as_str()))
+        .filter(|value| !value.trim().is_empty())
+        .collect();
+    if values.is_empty() {
+        return 0.0;
+    }
+    let matching: usize = values.iter().filter(|value| pattern.matches(value)).count();
+    matching as f32 / values.len() as f32
+}
+
+/// Combines weighted scores into a single confidence.
+///
+/// # Arguments
+///
+/// * `scores` - Pairs of a score between `0.0` and `1.0` and its weight.
+///
+/// # Returns
+///
+/// The weighted average, clamped between `0.0` and `1.0`.
+pub fn weighted_score(scores: &[(f32, f32)]) -> f32 {
+    let total_weight: f32 = scores.iter().map(|(_, weight)| weight).sum();
+    if total_weight <= 0.0 {
+        return 0.0;
+    }
+    let total: f32 = scores.iter().map(|(score, weight)| score * weight).sum();
+    (total / total_weight).clamp(0.0, 1.0)
+}
+
+/// Returns the stem of the last path segment of a file name or URL, e.g. `Revolut-2024`.
+fn file_stem(file_name: &str) -> &str {
+    let path: &str = file_name.split(['?', '#']).next().unwrap_or_default();
+    let segment: &str = path.rsplit(['/', '\\']).next().unwrap_or_default();
+    match segment.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem,
+        _ => segment,
+    }
+}
+
+/// Splits text into lowercase words of letters and digits.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Checks the country format and the mod-97 checksum of an IBAN.
+///
+/// # Arguments
+///
+/// * `value` - The IBAN, spaces are ignored.
+///
+/// # Returns
+///
+/// `true` when the value is a valid IBAN.
+pub fn is_valid_iban(value: &str) -> bool {
+    Iban::from_str(value).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rows() -> Vec<Value> {
+        vec![
+            json!({
+                "datum": "31-01-2024",
+                "bedrag": "-12,50",
+                "tegenrekening": "NL91 ABNA 0417 1643 00"
+            }),
+            json!({ "datum": "01-02-2024", "bedrag": "1.000,00", "tegenrekening": "" }),
+            json!({ "datum": "n/a", "bedrag": "EUR", "tegenrekening": "NL91ABNA0417164301" }),
+        ]
+    }
+
+    #[test]
+    fn validates_iban_checksums() {
+        assert!(is_valid_iban("NL91ABNA0417164300"));
+        assert!(is_valid_iban("nl91 abna 0417 1643 00"));
+        assert!(is_valid_iban("DE89370400440532013000"));
+        assert!(!is_valid_iban("NL91ABNA0417164301"));
+        assert!(!is_valid_iban("NL91ABNA"));
+        assert!(!is_valid_iban("9191ABNA0417164300"));
+        assert!(!is_valid_iban("NL91-ABNA-0417-1643-00"));
+        // the length of the country is checked too, not only the checksum
+        assert!(!is_valid_iban("NL91ABNA04171643"));
+        assert!(!is_valid_iban("XX91ABNA0417164300"));
+    }
+
+    #[test]
+    fn matches_value_patterns_per_column() {
+        let rows: Vec<Value> = rows();
+        let context: DetectionContext = DetectionContext::new(&rows, None);
+
+        let dates: f32 = value_match_ratio(&context, "datum", ValuePattern::Date);
+        assert!((dates - 2.0 / 3.0).abs() < 1e-6);
+        let amounts: f32 = value_match_ratio(&context, "bedrag", ValuePattern::Amount);
+        assert!((amounts - 2.0 / 3.0).abs() < 1e-6);
+        // empty values don't count
+        assert_eq!(value_match_ratio(&context, "tegenrekening", ValuePattern::Iban), 0.5);
+        assert_eq!(value_match_ratio(&context, "missing", ValuePattern::Date), 0.0);
+    }
+
+    #[test]
+    fn header_overlap_sees_through_aliases() {
+        let rows: Vec<Value> = rows();
+        let context: DetectionContext = DetectionContext::new(&rows, None);
+
+        let aliased: HashSet<&str> = ["boekdatum", "transactiebedrag", "tegenrekening"].into();
+        assert_eq!(header_overlap(&context, &aliased), 1.0);
+
+        let partial: HashSet<&str> = ["datum", "bedrag", "omschrijving", "saldo"].into();
+        assert!((header_overlap(&context, &partial) - 2.0 / 5.0).abs() < 1e-6);
+
+        let empty: Vec<Value> = vec![];
+        assert_eq!(header_overlap(&DetectionContext::new(&empty, None), &HashSet::new()), 0.0);
+    }
+
+    #[test]
+    fn scores_file_names_and_text_markers() {
+        let pages: Vec<Value> = vec![
+            json!({ "page_number": 1, "text": "ABN AMRO Bank N.V.\nFinancieel Jaaroverzicht 2024" }),
+            json!({ "page_number": 2, "text": "Pagina 2" }),
+        ];
+        let context: DetectionContext =
+            DetectionContext::new(&pages, Some("https://cdn.example.com/Revolut-2024.csv"));
+
+        assert_eq!(context.file_name_score(&["revolut"]), 1.0);
+        assert_eq!(context.file_name_score(&["mt940", "sta"]), 0.0);
+        assert_eq!(context.text_score(&["ABN AMRO", "financieel jaaroverzicht"]), 1.0);
+        assert_eq!(context.text_score(&["ABN AMRO", "Rabobank"]), 0.5);
+        assert_eq!(context.text_score(&[]), 0.0);
+        assert_eq!(DetectionContext::new(&pages, None).file_name_score(&["revolut"]), 0.0);
+    }
+
+    #[test]
+    fn file_name_markers_match_words_of_the_stem() {
+        let empty: Vec<Value> = vec![];
+        let score = |file_name: &str, markers: &[&str]| {
+            DetectionContext::new(&empty, Some(file_name)).file_name_score(markers)
+        };
+
+        assert_eq!(score("ING_NL20INGB0001234567_2024.csv", &["ing"]), 1.0);
+        assert_eq!(score("C:\\exports\\ing-januari.csv", &["ing"]), 1.0);
+        assert_eq!(score("https://example.com/MT940_ABN_AMRO.sta?token=1", &["abn amro"]), 1.0);
+        // substrings of other words, the host, the path and the query don't count
+        assert_eq!(score("billing.csv", &["ing"]), 0.0);
+        assert_eq!(score("https://ing.example.com/export.csv", &["ing"]), 0.0);
+        assert_eq!(score("https://example.com/revolut/statement.csv", &["revolut"]), 0.0);
+        assert_eq!(score("statement.csv?source=revolut", &["revolut"]), 0.0);
+        assert_eq!(score("statement.sta", &["sta"]), 0.0);
+        assert_eq!(score("amro-abn.csv", &["abn amro"]), 0.0);
+    }
+
+    #[test]
+    fn weighs_scores() {
+        assert_eq!(weighted_score(&[(1.0, 3.0), (0.0, 1.0)]), 0.75);
+        assert_eq!(weighted_score(&[(2.0, 1.0)]), 1.0);
+        assert_eq!(weighted_score(&[(1.0, 0.0)]), 0.0);
+        assert_eq!(weighted_score(&[]), 0.0);
+    }
+}
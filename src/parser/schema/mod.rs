@@ -1,15 +1,43 @@
+pub mod detection;
+
 use serde_json::{json, Map, Value};
 use std::collections::HashSet;
 use tracing::{info, warn};
 
+// crate imports
+use crate::parser::caster::caster_registry::CasterRegistry;
+use crate::parser::schema::detection::{
+    header_overlap, Detection, DetectionContext, ProviderCandidate, MIN_CONFIDENCE,
+};
+
 /// Enum representing different key sets for various schemas.
 pub enum SchemaKeys {
     Revolut,
     ShopifyOrders,
     AbnAmroFinancialYearOVerviewDutch,
+    AbnAmroMt940,
 }
 
 impl SchemaKeys {
+    /// The providers Aurora recognises but has no caster for yet, with their names.
+    pub const RECOGNISED_ONLY: [(SchemaKeys, &'static str); 2] = [
+        (SchemaKeys::ShopifyOrders, "shopify_orders_csv"),
+        (
+            SchemaKeys::AbnAmroFinancialYearOVerviewDutch,
+            "abn_amro_financial_year_overview_dutch",
+        ),
+    ];
+
+    /// Text markers identifying the provider in free text such as PDF pages.
+    pub fn text_markers(&self) -> &'static [&'static str] {
+        match self {
+            SchemaKeys::AbnAmroFinancialYearOVerviewDutch => {
+                &["ABN AMRO", "Financieel jaaroverzicht"]
+            }
+            _ => &[],
+        }
+    }
+
     pub fn keys(&self) -> HashSet<&'static str> {
         match self {
             SchemaKeys::Revolut => vec![
@@ -60,95 +88,206 @@ impl SchemaKeys {
             ]
             .into_iter()
             .collect(),
+            SchemaKeys::AbnAmroMt940 => vec![
+                "account",
+                "statement_number",
+                "value_date",
+                "entry_date",
+                "amount",
+                "currency",
+                "balance",
+                "transaction_code",
+                "reference",
+                "transaction_type",
+                "counterparty_iban",
+                "counterparty_bic",
+                "counterparty_name",
+                "end_to_end_reference",
+                "description",
+            ]
+            .into_iter()
+            .collect(),
         }
     }
 }
 
-/// Represents a struct with a name and a set of expected keys.
-pub struct RevolutPersonalSchema {
-    pub name: String,
-    pub keys: Vec<String>,
+/// Ranks every known provider by how well it matches a document.
+///
+/// Providers with a registered caster score themselves, providers Aurora only
+/// recognises are scored on header overlap and text markers.
+///
+/// # Arguments
+///
+/// * `context` - The `DetectionContext` of the document.
+/// * `registry` - The `CasterRegistry` to score.
+///
+/// # Returns
+///
+/// Every provider scoring above zero, most likely first.
+pub fn rank_providers(context: &DetectionContext, registry: &CasterRegistry) -> Vec<ProviderCandidate> {
+    let mut candidates: Vec<ProviderCandidate> = registry
+        .casters()
+        .iter()
+        .map(|caster| (caster.name().to_string(), caster.detect(context)))
+        .chain(SchemaKeys::RECOGNISED_ONLY.iter().map(|(schema, name)| {
+            let score: f32 = header_overlap(context, &schema.keys())
+                .max(context.text_score(schema.text_markers()));
+            (name.to_string(), score)
+        }))
+        .filter(|(_, score)| *score > 0.0)
+        .map(|(document_provider, score)| ProviderCandidate {
+            document_provider,
+            confidence: (f64::from(score) * 100.0).round() / 100.0,
+        })
+        .collect();
+
+    // stable, so ties go to the provider registered first
+    candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    candidates
 }
 
-/// Determines the most likely struct match for a given JSON object based on key similarity.
+/// Determines the document provider of a JSON array and stamps it on every object.
 ///
 /// # Arguments
 ///
 /// * `object` - A reference to a `serde_json::Value` which is expected to be a JSON array of objects.
-/// * `schemas` - A slice of `StructSchema` representing the available struct schemas to match against.
+/// * `file_name` - The file name or URL of the document, used for filename markers.
+/// * `forced_provider` - A provider requested by the caller, skipping detection.
+/// * `registry` - The `CasterRegistry` snapshot the document is parsed with.
 ///
 /// # Returns
 ///
-/// A `Value` representing the JSON array with an added key "document_provider" indicating the name of the struct that best matches the keys in the objects.
-pub fn determine_document_provider(object: &Value, schemas: &[RevolutPersonalSchema]) -> Value {
+/// A `Result` containing the JSON array with an added key "document_provider" and the `Detection`
+/// it is based on, or a message when the forced provider doesn't exist.
+pub fn determine_document_provider(
+    object: &Value,
+    file_name: Option<&str>,
+    forced_provider: Option<&str>,
+    registry: &CasterRegistry,
+) -> Result<(Value, Detection), String> {
     info!("Determining document provider for object: {:#?}", object);
     let mut result: Value = object.clone();
 
     if !object.is_array() {
         warn!("Provided value is not an array. Marking document provider as Unknown.");
-        return json!([{
-            "document_provider": "unknown",
-            "data": object
-        }]);
+        let detection: Detection = Detection {
+            document_provider: "unknown".to_string(),
+            forced: false,
+            candidates: vec![],
+        };
+        return Ok((
+            json!([{
+                "document_provider": "unknown",
+                "data": object
+            }]),
+            detection,
+        ));
     }
 
-    let revolut_keys: HashSet<&str> = SchemaKeys::Revolut.keys();
-    let shopify_keys: HashSet<&str> = SchemaKeys::ShopifyOrders.keys();
-    let abn_amro_keys: HashSet<&str> = SchemaKeys::AbnAmroFinancialYearOVerviewDutch.keys();
-
-    let mut document_provider: String = "unknown".to_string();
+    let rows: &[Value] = object.as_array().map(Vec::as_slice).unwrap_or_default();
+    let context: DetectionContext = DetectionContext::new(rows, file_name);
+    let candidates: Vec<ProviderCandidate> = rank_providers(&context, registry);
+    info!("Provider candidates: {:?}", candidates);
 
-    if let Some(array) = object.as_array() {
-        for item in array {
-            if let Some(obj_map) = item.as_object() {
-                if obj_map
-                    .keys()
-                    .all(|key| revolut_keys.contains(key.as_str()))
-                {
-                    document_provider = "revolut_csv".to_string();
-                    break;
-                } else if obj_map
-                    .keys()
-                    .all(|key| shopify_keys.contains(key.as_str()))
-                {
-                    document_provider = "shopify_orders_csv".to_string();
-                    break;
-                } else if obj_map
-                    .keys()
-                    .all(|key| abn_amro_keys.contains(key.as_str()))
-                {
-                    document_provider = "abn_amro_financial_year_overview_dutch".to_string();
-                    break;
-                }
+    let detection: Detection = match forced_provider {
+        Some(forced) => {
+            let known: bool = registry.get(forced).is_some()
+                || SchemaKeys::RECOGNISED_ONLY
+                    .iter()
+                    .any(|(_, name)| *name == forced);
+            if !known {
+                return Err(format!("Unknown document provider '{}'", forced));
             }
-        }
-    }
-
-    for schema in schemas {
-        if let Some(array) = object.as_array() {
-            for item in array {
-                if let Some(obj_map) = item.as_object() {
-                    if schema.keys.iter().all(|key| obj_map.contains_key(key)) {
-                        document_provider = schema.name.clone();
-                        break;
-                    }
-                }
+            Detection {
+                document_provider: forced.to_string(),
+                forced: true,
+                candidates,
             }
         }
-    }
+        None => Detection {
+            document_provider: candidates
+                .first()
+                .filter(|best| best.confidence >= MIN_CONFIDENCE)
+                .map(|best| best.document_provider.clone())
+                .unwrap_or_else(|| "unknown".to_string()),
+            forced: false,
+            candidates,
+        },
+    };
 
-    info!("Document provider determined: {}", document_provider);
+    info!("Document provider determined: {}", detection.document_provider);
 
     if let Some(array) = result.as_array_mut() {
         for item in array {
             if let Some(obj_map) = item.as_object_mut() {
                 obj_map.insert(
                     "document_provider".to_string(),
-                    Value::String(document_provider.clone()),
+                    Value::String(detection.document_provider.clone()),
                 );
             }
         }
     }
 
-    result
+    Ok((result, detection))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::caster::caster_registry::registry;
+
+    fn revolut_rows() -> Value {
+        json!([
+            {
+                "type": "TRANSFER",
+                "product": "Savings",
+                "started_date": "2024-01-01 01:24:30",
+                "completed_date": "2024-01-01 01:24:30",
+                "description": "To EUR Pocket",
+                "amount": "50.00",
+                "fee": "0.00",
+                "currency": "EUR",
+                "state": "COMPLETED",
+                "balance": "100.00"
+            }
+        ])
+    }
+
+    #[test]
+    fn detects_and_stamps_the_best_candidate() {
+        let (rows, detection): (Value, Detection) =
+            determine_document_provider(&revolut_rows(), Some("statement.csv"), None, &registry()).unwrap();
+
+        assert_eq!(detection.document_provider, "revolut_csv");
+        assert!(!detection.forced);
+        assert_eq!(detection.candidates[0].document_provider, "revolut_csv");
+        assert!(detection.candidates[0].confidence >= MIN_CONFIDENCE);
+        assert!(detection
+            .candidates
+            .windows(2)
+            .all(|pair| pair[0].confidence >= pair[1].confidence));
+        assert_eq!(rows[0]["document_provider"], "revolut_csv");
+    }
+
+    #[test]
+    fn a_document_nothing_matches_is_unknown() {
+        let rows: Value = json!([{ "foo": "bar", "baz": "1" }]);
+        let (rows, detection): (Value, Detection) =
+            determine_document_provider(&rows, None, None, &registry()).unwrap();
+        assert_eq!(detection.document_provider, "unknown");
+        assert_eq!(rows[0]["document_provider"], "unknown");
+    }
+
+    #[test]
+    fn forced_providers_skip_detection() {
+        let (rows, detection): (Value, Detection) =
+            determine_document_provider(&revolut_rows(), None, Some("abn_amro_mt940"), &registry()).unwrap();
+        assert_eq!(detection.document_provider, "abn_amro_mt940");
+        assert!(detection.forced);
+        assert_eq!(rows[0]["document_provider"], "abn_amro_mt940");
+
+        let unknown: Result<(Value, Detection), String> =
+            determine_document_provider(&revolut_rows(), None, Some("bank_of_nowhere"), &registry());
+        assert_eq!(unknown.unwrap_err(), "Unknown document provider 'bank_of_nowhere'");
+    }
 }
@@ -0,0 +1,163 @@
+//! ## Spreadsheet parsing
+//!
+//! Reads `.xlsx`, `.xls` and `.ods` workbooks with `calamine`. The first
+//! worksheet holding data is read like a CSV document: the first non-empty row
+//! is the header and every row below it becomes an object of strings keyed by
+//! the normalized header, so the casters don't have to tell them apart.
+//! Dates are written as `%Y-%m-%d %H:%M:%S`, or `%Y-%m-%d` when they have no time.
+
+use anyhow::{Context, Result};
+use calamine::{open_workbook_auto_from_rs, Data, Range, Reader, Sheets};
+use chrono::{NaiveDateTime, NaiveTime};
+use csv::StringRecord;
+use serde_json::{Map, Value};
+use std::io::Cursor;
+use tracing::info;
+
+// crate imports
+use crate::parser::csv::headers::normalize_headers;
+
+/// Converts the first worksheet holding data to JSON.
+///
+/// # Arguments
+///
+/// * `content` - The raw bytes of the workbook.
+///
+/// # Returns
+///
+/// A `Result` containing a JSON array with an object per row below the header,
+/// or an error when the workbook can't be read or holds no data.
+pub fn read_spreadsheet(content: &[u8]) -> Result<Value> {
+    let mut workbook: Sheets<Cursor<&[u8]>> =
+        open_workbook_auto_from_rs(Cursor::new(content)).context("Failed to open workbook")?;
+
+    for (index, name) in workbook.sheet_names().into_iter().enumerate() {
+        let range: Range<Data> = match workbook.worksheet_range_at(index) {
+            Some(range) => range.with_context(|| format!("Failed to read worksheet '{}'", name))?,
+            None => continue,
+        };
+        if range.is_empty() {
+            continue;
+        }
+        info!("Reading worksheet '{}' of {:?}", name, range.get_size());
+        return Ok(range_to_json(&range));
+    }
+
+    anyhow::bail!("The workbook has no worksheet holding data")
+}
+
+/// Converts the cells of a worksheet to JSON, the first non-empty row is the header.
+///
+/// # Arguments
+///
+/// * `range` - The used cells of the worksheet.
+///
+/// # Returns
+///
+/// A JSON array with an object per non-empty row below the header.
+pub fn range_to_json(range: &Range<Data>) -> Value {
+    let mut rows = range
+        .rows()
+        .map(|row| row.iter().map(cell_to_string).collect::<Vec<String>>())
+        .filter(|row| row.iter().any(|cell| !cell.trim().is_empty()));
+
+    let headers: StringRecord = match rows.next() {
+        Some(header) => normalize_headers(&StringRecord::from(header)),
+        None => return Value::Array(vec![]),
+    };
+
+    let records: Vec<Value> = rows
+        .map(|row| {
+            let record: Map<String, Value> = headers
+                .iter()
+                .zip(row)
+                .filter(|(header, _)| !header.is_empty())
+                .map(|(header, cell)| (header.to_string(), Value::String(cell)))
+                .collect();
+            Value::Object(record)
+        })
+        .collect();
+    Value::Array(records)
+}
+
+/// Writes a cell the way it would have been written in a CSV export.
+fn cell_to_string(cell: &Data) -> String {
+    match cell {
+        Data::String(text) => text.trim().to_string(),
+        Data::DateTime(date_time) if !date_time.is_duration() => date_time
+            .as_datetime()
+            .map(format_date_time)
+            .unwrap_or_else(|| cell.to_string()),
+        Data::Error(_) | Data::Empty => String::new(),
+        cell => cell.to_string(),
+    }
+}
+
+fn format_date_time(date_time: NaiveDateTime) -> String {
+    if date_time.time() == NaiveTime::MIN {
+        date_time.format("%Y-%m-%d").to_string()
+    } else {
+        date_time.format("%Y-%m-%d %H:%M:%S").to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use calamine::{CellErrorType, ExcelDateTime, ExcelDateTimeType};
+
+    fn range(rows: Vec<Vec<Data>>) -> Range<Data> {
+        let width: u32 = rows.iter().map(|row| row.len()).max().unwrap_or(0) as u32;
+        let mut range: Range<Data> = Range::new((0, 0), (rows.len() as u32 - 1, width - 1));
+        for (row_index, row) in rows.into_iter().enumerate() {
+            for (column_index, cell) in row.into_iter().enumerate() {
+                range.set_value((row_index as u32, column_index as u32), cell);
+            }
+        }
+        range
+    }
+
+    fn text(value: &str) -> Data {
+        Data::String(value.to_string())
+    }
+
+    #[test]
+    fn rows_are_keyed_by_the_normalized_header() {
+        let range: Range<Data> = range(vec![
+            vec![Data::Empty, Data::Empty, Data::Empty],
+            vec![text("Datum"), text("Bedrag"), text("Omschrijving")],
+            vec![
+                Data::DateTime(ExcelDateTime::new(45322.0, ExcelDateTimeType::DateTime, false)),
+                Data::Float(-12.5),
+                text(" Albert Heijn 1234 "),
+            ],
+            vec![Data::Empty, Data::Empty, Data::Empty],
+            vec![
+                Data::DateTime(ExcelDateTime::new(45323.5, ExcelDateTimeType::DateTime, false)),
+                Data::Int(100),
+                Data::Error(CellErrorType::NA),
+            ],
+        ]);
+
+        let rows: Value = range_to_json(&range);
+        let rows: &Vec<Value> = rows.as_array().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["datum"], "2024-01-31");
+        assert_eq!(rows[0]["bedrag"], "-12.5");
+        assert_eq!(rows[0]["omschrijving"], "Albert Heijn 1234");
+        assert_eq!(rows[1]["datum"], "2024-02-01 12:00:00");
+        assert_eq!(rows[1]["bedrag"], "100");
+        assert_eq!(rows[1]["omschrijving"], "");
+    }
+
+    #[test]
+    fn an_empty_worksheet_has_no_rows() {
+        let range: Range<Data> = range(vec![vec![Data::Empty, text("  ")]]);
+        assert_eq!(range_to_json(&range), Value::Array(vec![]));
+    }
+
+    #[test]
+    fn garbage_is_not_a_workbook() {
+        assert!(read_spreadsheet(b"Date,Amount\n2024-01-31,12.50\n").is_err());
+    }
+}
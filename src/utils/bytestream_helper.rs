@@ -26,7 +26,13 @@ pub async fn read_file_to_bytestream(file_url: &str) -> Result<Vec<u8>> {
         // If the file_url is a URL, download the file
         info!("Detected URL, attempting to download: {}", file_url);
         let client: Client = Client::new();
-        let response: Response = match client.get(file_url).send().await {
+        // an error page of the server is not the document, a non-success status fails the download
+        let response: Response = match client
+            .get(file_url)
+            .send()
+            .await
+            .and_then(Response::error_for_status)
+        {
             Ok(resp) => {
                 info!("Request sent successfully for URL: {}", file_url);
                 resp